@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use rgeometry_host::animation::{self, AnimationEncoder, AnimationFormat, AnimationOptions};
+use rgeometry_host::export::{self, FrameInfo};
+use rgeometry_host::sweep::{self, SweepOptions};
+use rgeometry_host::wasm::{parameter_by_key, Value, Viewport, Wasm};
+
+/// Render rgeometry demo modules outside of the browser.
+#[derive(Debug, Parser)]
+#[command(name = "rgeometry", version, about)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Render a single frame as SVG, PNG, PDF or TikZ.
+    Frame {
+        /// The demo module (.wasm)
+        module: PathBuf,
+        /// Where to write the frame
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Defaults to the output's extension
+        #[arg(long, value_enum)]
+        format: Option<FrameFormat>,
+        /// Time to render the frame at, in seconds
+        #[arg(long, default_value_t = 0.0)]
+        time: f64,
+        /// Pixels per user unit of PNGs
+        #[arg(long, default_value_t = 2.0)]
+        scale: f32,
+        /// Width of the viewport passed to the module, in CSS pixels
+        #[arg(long, default_value_t = Viewport::default().width)]
+        width: f32,
+        /// Height of the viewport passed to the module, in CSS pixels
+        #[arg(long, default_value_t = Viewport::default().height)]
+        height: f32,
+        /// Set a parameter by name or schema index, like `--set n=10`. Values
+        /// are written as in viewer URLs.
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        values: Vec<String>,
+    },
+    /// Render a time range of a module as an animated GIF or APNG.
+    Animate {
+        /// The demo module (.wasm)
+        module: PathBuf,
+        /// Where to write the animation
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Defaults to GIF, or APNG for .png and .apng outputs
+        #[arg(long, value_enum)]
+        format: Option<Format>,
+        /// Time of the first frame, in seconds
+        #[arg(long, default_value_t = 0.0)]
+        start: f64,
+        /// Time the animation ends at, in seconds
+        #[arg(long, default_value_t = 5.0)]
+        end: f64,
+        /// Frames per second
+        #[arg(long, default_value_t = 25.0)]
+        fps: f64,
+        /// Width of the animation in pixels. The height follows from the
+        /// frames.
+        #[arg(long, default_value_t = AnimationOptions::default().width)]
+        image_width: u32,
+        /// Width of the viewport passed to the module, in CSS pixels
+        #[arg(long, default_value_t = Viewport::default().width)]
+        width: f32,
+        /// Height of the viewport passed to the module, in CSS pixels
+        #[arg(long, default_value_t = Viewport::default().height)]
+        height: f32,
+        /// How often the animation plays. Loops forever if not given.
+        #[arg(long)]
+        loops: Option<u16>,
+        /// Set a parameter by name or schema index, like `--set n=10`. Values
+        /// are written as in viewer URLs.
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        values: Vec<String>,
+    },
+    /// Measure render time and fuel across the values of a range_i32
+    /// parameter, and plot them on log-log axes with fitted slopes.
+    Sweep {
+        /// The demo module (.wasm)
+        module: PathBuf,
+        /// Name or schema index of the swept parameter
+        parameter: String,
+        /// Where to write the plot (.svg)
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Where to write every measurement as CSV
+        #[arg(long)]
+        csv: Option<PathBuf>,
+        /// Smallest swept value. Defaults to the parameter's minimum, or 1.
+        #[arg(long)]
+        min: Option<i32>,
+        /// Largest swept value. Defaults to the parameter's maximum.
+        #[arg(long)]
+        max: Option<i32>,
+        /// Number of values, spaced evenly on a log scale
+        #[arg(long, default_value_t = 8)]
+        steps: usize,
+        /// Timed renders per value
+        #[arg(long, default_value_t = 5)]
+        samples: usize,
+        /// Time to render the frames at, in seconds
+        #[arg(long, default_value_t = 0.0)]
+        time: f64,
+        /// Width of the viewport passed to the module, in CSS pixels
+        #[arg(long, default_value_t = Viewport::default().width)]
+        width: f32,
+        /// Height of the viewport passed to the module, in CSS pixels
+        #[arg(long, default_value_t = Viewport::default().height)]
+        height: f32,
+        /// Set another parameter by name or schema index, like `--set n=10`
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        values: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Format {
+    Gif,
+    Apng,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum FrameFormat {
+    Svg,
+    Png,
+    Pdf,
+    Tikz,
+}
+
+impl From<FrameFormat> for export::Format {
+    fn from(format: FrameFormat) -> Self {
+        match format {
+            FrameFormat::Svg => export::Format::Svg,
+            FrameFormat::Png => export::Format::Png,
+            FrameFormat::Pdf => export::Format::Pdf,
+            FrameFormat::Tikz => export::Format::Tikz,
+        }
+    }
+}
+
+impl From<Format> for AnimationFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Gif => AnimationFormat::Gif,
+            Format::Apng => AnimationFormat::Apng,
+        }
+    }
+}
+
+// Apply `name=value` assignments to the module's parameters
+fn set_parameters(
+    wasm: &mut Wasm,
+    assignments: &[String],
+) -> Result<HashMap<usize, Value>, String> {
+    let mut values = HashMap::new();
+    for assignment in assignments {
+        let (name, value) = assignment
+            .split_once('=')
+            .ok_or_else(|| format!("Expected NAME=VALUE, got {:?}", assignment))?;
+        let index = parameter_by_key(wasm.schema(), name)
+            .ok_or_else(|| format!("No parameter named {:?}", name))?;
+        let param = &wasm.schema()[index];
+        let parsed = param
+            .ty
+            .parse_value(value)
+            .ok_or_else(|| format!("Invalid value {:?} for {}", value, name))?;
+        wasm.set_parameter(index, parsed.clone())?;
+        values.insert(index, parsed);
+    }
+    Ok(values)
+}
+
+fn read_bytes(module: &Path) -> Result<Vec<u8>, String> {
+    std::fs::read(module).map_err(|e| format!("Failed to read {}: {}", module.display(), e))
+}
+
+// Open a module that renders into a viewport of `width` by `height`
+fn read_module(module: &Path, width: f32, height: f32) -> Result<Wasm, String> {
+    let mut wasm = Wasm::new(&read_bytes(module)?)?;
+    wasm.set_viewport(Viewport {
+        width,
+        height,
+        ..Viewport::default()
+    });
+    Ok(wasm)
+}
+
+fn write_output(output: &Path, bytes: Vec<u8>) -> Result<(), String> {
+    std::fs::write(output, bytes)
+        .map_err(|e| format!("Failed to write {}: {}", output.display(), e))
+}
+
+fn frame(
+    module: &Path,
+    output: &Path,
+    format: export::Format,
+    time: f64,
+    scale: f32,
+    [width, height]: [f32; 2],
+    assignments: &[String],
+) -> Result<(), String> {
+    let mut wasm = read_module(module, width, height)?;
+    let values = set_parameters(&mut wasm, assignments)?;
+    let info = FrameInfo::capture(wasm.meta(), wasm.schema(), &values, time);
+    let svg = wasm.render_at(time, 0.0);
+    if wasm.has_failed() {
+        return Err(format!("Failed to render the frame at {}s", time));
+    }
+    let bytes = match format {
+        // Report what the picture is missing
+        export::Format::Tikz => {
+            let tikz = export::tikz(&svg, &info)?;
+            for item in &tikz.unsupported {
+                eprintln!("warning: skipped or approximated {}", item);
+            }
+            tikz.source.into_bytes()
+        }
+        format => export::export(&svg, format, scale, &info)?,
+    };
+    write_output(output, bytes)
+}
+
+fn animate(
+    module: &Path,
+    output: &Path,
+    format: AnimationFormat,
+    options: &AnimationOptions,
+    [width, height]: [f32; 2],
+    assignments: &[String],
+) -> Result<(), String> {
+    let mut wasm = read_module(module, width, height)?;
+    let values = set_parameters(&mut wasm, assignments)?;
+    let info = FrameInfo::capture(wasm.meta(), wasm.schema(), &values, options.start);
+    let mut encoder = AnimationEncoder::new(format, options, &info)?;
+    for index in 0..options.frame_count() {
+        encoder.add_frame(&animation::render_frame(&mut wasm, options, index)?)?;
+    }
+    write_output(output, encoder.finish()?)
+}
+
+// Run a sweep, print a table of the medians and the fitted slopes, and
+// write the plot and CSV if asked to
+fn sweep(
+    module: &Path,
+    parameter: &str,
+    output: Option<&Path>,
+    csv: Option<&Path>,
+    configure: impl FnOnce(&mut SweepOptions),
+    assignments: &[String],
+) -> Result<(), String> {
+    let bytes = read_bytes(module)?;
+    let mut wasm = Wasm::new(&bytes)?;
+    let values = set_parameters(&mut wasm, assignments)?;
+    let index = parameter_by_key(wasm.schema(), parameter)
+        .ok_or_else(|| format!("No parameter named {:?}", parameter))?;
+    let mut options = SweepOptions::new(wasm.schema(), index)?;
+    configure(&mut options);
+    let result = sweep::run(&bytes, &values, &options)?;
+
+    println!(
+        "{:>10} {:>12} {:>14}",
+        result.parameter, "median ms", "fuel"
+    );
+    for point in &result.points {
+        println!(
+            "{:>10} {:>12.4} {:>14}",
+            point.value,
+            point.median_time(),
+            point.fuel
+        );
+    }
+    let slope =
+        |fit: Option<sweep::Fit>| fit.map_or("-".to_string(), |f| format!("{:.2}", f.slope));
+    println!("time slope: {}", slope(result.time_fit()));
+    println!("fuel slope: {}", slope(result.fuel_fit()));
+
+    if let Some(output) = output {
+        write_output(output, result.plot().into_bytes())?;
+    }
+    if let Some(csv) = csv {
+        write_output(csv, result.to_csv().into_bytes())?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result = match Cli::parse().command {
+        Command::Frame {
+            module,
+            output,
+            format,
+            time,
+            scale,
+            width,
+            height,
+            values,
+        } => {
+            let extension = output.extension().and_then(|e| e.to_str());
+            let format = match (format, extension) {
+                (Some(format), _) => Ok(format.into()),
+                (None, Some("tex" | "tikz")) => Ok(export::Format::Tikz),
+                (None, Some(extension)) => export::Format::ALL
+                    .into_iter()
+                    .find(|f| f.extension() == extension)
+                    .ok_or_else(|| format!("Unknown format .{}, use --format", extension)),
+                (None, None) => Err("No output extension, use --format".to_string()),
+            };
+            let viewport = [width, height];
+            format
+                .and_then(|format| frame(&module, &output, format, time, scale, viewport, &values))
+        }
+        Command::Animate {
+            module,
+            output,
+            format,
+            start,
+            end,
+            fps,
+            image_width,
+            width,
+            height,
+            loops,
+            values,
+        } => {
+            let format = format.map(AnimationFormat::from).unwrap_or_else(|| {
+                match output.extension().and_then(|e| e.to_str()) {
+                    Some("png" | "apng") => AnimationFormat::Apng,
+                    _ => AnimationFormat::Gif,
+                }
+            });
+            let options = AnimationOptions {
+                start,
+                end,
+                fps,
+                width: image_width,
+                loops,
+            };
+            animate(&module, &output, format, &options, [width, height], &values)
+        }
+        Command::Sweep {
+            module,
+            parameter,
+            output,
+            csv,
+            min,
+            max,
+            steps,
+            samples,
+            time,
+            width,
+            height,
+            values,
+        } => sweep(
+            &module,
+            &parameter,
+            output.as_deref(),
+            csv.as_deref(),
+            |options| {
+                options.min = min.unwrap_or(options.min);
+                options.max = max.unwrap_or(options.max);
+                options.steps = steps;
+                options.samples = samples;
+                options.time = time;
+                options.viewport = Viewport {
+                    width,
+                    height,
+                    ..Viewport::default()
+                };
+            },
+            &values,
+        ),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            ExitCode::FAILURE
+        }
+    }
+}
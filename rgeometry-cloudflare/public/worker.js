@@ -0,0 +1,6 @@
+// Runs demo modules off the main thread. Loads the client bundle and hands
+// over to `worker_main`, which answers the page's requests.
+import init, { worker_main } from "./pkg/client.js";
+
+await init();
+worker_main();
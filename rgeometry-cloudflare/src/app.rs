@@ -1,10 +1,12 @@
-use std::sync::{Arc, Mutex};
-
-use crate::wasm::Wasm;
-use leptos::{html::Img, prelude::*};
+use crate::compare::CompareView;
+use crate::demo::DemoView;
+use crate::storage;
+use leptos::prelude::*;
+#[cfg(feature = "ssr")]
 use leptos_meta::*;
 use rfd::*;
 
+#[cfg(feature = "ssr")]
 pub fn shell(options: LeptosOptions) -> impl IntoView {
     view! {
     <!DOCTYPE html>
@@ -24,26 +26,47 @@ pub fn shell(options: LeptosOptions) -> impl IntoView {
     }
 }
 
+// Embed an SVG document in a data URL. '%' and '#' must be escaped, or they
+// would be read as escapes and the start of a fragment ("#ff0000" fills).
+pub(crate) fn svg_data_url(svg: &str) -> String {
+    let mut url = String::from("data:image/svg+xml,");
+    for c in svg.chars() {
+        match c {
+            '%' => url.push_str("%25"),
+            '#' => url.push_str("%23"),
+            c => url.push(c),
+        }
+    }
+    url
+}
+
 #[component]
 pub fn App() -> impl IntoView {
-    let img_ref = NodeRef::new();
+    // Bytes of the module picked last
+    let module = RwSignal::new(Vec::<u8>::new());
+    // Show two views next to each other
+    let comparing = RwSignal::new(false);
 
-    let wasm = Arc::new(Mutex::new(None));
-
-    if !cfg!(feature = "ssr") {
-        let wasm = wasm.clone();
+    let pick_module = move || {
         leptos::task::spawn_local(async move {
             log::info!("Opening file dialog...");
-            let Some(file) = AsyncFileDialog::new().pick_file().await else {
-                // return Err("No file selected".to_string());
+            let Some(file) = AsyncFileDialog::new()
+                .add_filter("WebAssembly", &["wasm"])
+                .pick_file()
+                .await
+            else {
                 return;
             };
-            let bytes = file.read().await;
-
-            // Create Wasm instance from the loaded bytes and wrap in mutex
-            let mut wasm = wasm.lock().unwrap();
-            *wasm = Some(Wasm::new(&bytes).unwrap());
+            module.set(file.read().await);
         });
+    };
+
+    if !cfg!(feature = "ssr") {
+        // Reopen the module from the last visit
+        match storage::load_module() {
+            Some(bytes) => module.set(bytes),
+            None => pick_module(),
+        }
     }
 
     // // Create a resource that fetches data and creates a Wasm instance
@@ -64,25 +87,37 @@ pub fn App() -> impl IntoView {
 
     // let img = RwSignal::new(String::new());
 
-    fn animate(node: NodeRef<Img>, wasm: Arc<Mutex<Option<Wasm>>>) {
-        {
-            let mut wasm = wasm.lock().unwrap();
-            if let Some(wasm) = wasm.as_mut() {
-                if let Some(img) = node.get_untracked() {
-                    img.set_src(&format!("data:image/svg+xml,{}", wasm.render()));
-                }
-            }
-        }
-        request_animation_frame(move || animate(node, wasm));
-    }
-    if !cfg!(feature = "ssr") {
-        request_animation_frame(move || animate(img_ref, wasm));
-    }
-
     view! {
         <div class="p-4">
             <h1 class="text-2xl font-bold mb-4">"RGeometry WASM Viewer"</h1>
-            <img node_ref=img_ref/>
+            {move || {
+                let open = view! {
+                    <button class="px-2 border rounded bg-white" on:click=move |_| pick_module()>
+                        "Open module"
+                    </button>
+                };
+                if comparing.get() {
+                    view! {
+                        <CompareView module=module>
+                            {open}
+                            <button class="px-2 border rounded bg-white" on:click=move |_| comparing.set(false)>
+                                "Single view"
+                            </button>
+                        </CompareView>
+                    }
+                    .into_any()
+                } else {
+                    view! {
+                        <DemoView module=module persist=true>
+                            {open}
+                            <button class="px-2 border rounded bg-white" on:click=move |_| comparing.set(true)>
+                                "Compare"
+                            </button>
+                        </DemoView>
+                    }
+                    .into_any()
+                }
+            }}
             // <Suspense
             //     fallback=move || view! { <p>"Loading WASM file..."</p> }
             // >
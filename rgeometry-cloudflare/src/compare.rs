@@ -0,0 +1,247 @@
+use crate::app::svg_data_url;
+use crate::demo::DemoView;
+use crate::metrics;
+use leptos::prelude::*;
+use rfd::AsyncFileDialog;
+use rgeometry_host::compare::{self, Overlay, ONLY_LEFT_COLOR, ONLY_RIGHT_COLOR};
+use rgeometry_host::wasm::Preset;
+
+// Start of the current browser frame, in milliseconds of `metrics::now`. It
+// stays the same while the frame is prepared, so views that read a clock in
+// one frame get the same time.
+fn frame_now() -> f64 {
+    document()
+        .timeline()
+        .current_time()
+        .unwrap_or_else(metrics::now)
+}
+
+/// Playback time shared by several views, in seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    // Seconds passed before the last start, and when it started, in
+    // milliseconds of `frame_now`. Not running while paused.
+    state: RwSignal<(f64, Option<f64>)>,
+}
+
+impl Clock {
+    pub fn new() -> Self {
+        Clock {
+            state: RwSignal::new((0.0, Some(frame_now()))),
+        }
+    }
+
+    /// The current time, which advances once per browser frame. Doesn't
+    /// track, as it changes continuously.
+    pub fn time(&self) -> f64 {
+        let (offset, since) = self.state.get_untracked();
+        offset + since.map_or(0.0, |since| (frame_now() - since).max(0.0) / 1000.0)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.with(|(_, since)| since.is_none())
+    }
+
+    pub fn is_paused_untracked(&self) -> bool {
+        self.state.with_untracked(|(_, since)| since.is_none())
+    }
+
+    pub fn set_paused(&self, paused: bool) {
+        if paused == self.is_paused_untracked() {
+            return;
+        }
+        let time = self.time();
+        self.state.set((time, (!paused).then(frame_now)));
+    }
+
+    /// Go back to the start, keeping the clock paused or running.
+    pub fn restart(&self) {
+        let paused = self.is_paused_untracked();
+        self.state.set((0.0, (!paused).then(frame_now)));
+    }
+}
+
+// The last frame of one side, and whether it arrived since the overlay was
+// drawn
+struct SideFrame {
+    svg: String,
+    time: f64,
+    animated: bool,
+    fresh: bool,
+}
+
+// What the two views show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    // The same module with different values on each side
+    Values,
+    // Another build of the module, on the right, with the same values
+    Builds,
+}
+
+/// Two views of a module next to each other, on one clock.
+///
+/// The right view shows either the same module, with values of its own, or
+/// another build of it, given the values of the left view. The overlay draws
+/// both frames on top of each other and highlights the elements only one of
+/// them has.
+#[component]
+pub fn CompareView(
+    #[prop(into)] module: Signal<Vec<u8>>,
+    #[prop(optional)] children: Option<Children>,
+) -> impl IntoView {
+    let mode = RwSignal::new(Mode::Values);
+    // Bytes of the other build, empty until one is picked
+    let other = RwSignal::new(Vec::<u8>::new());
+    let clock = Clock::new();
+    let show_overlay = RwSignal::new(false);
+    // Last raw frames of both sides, and their overlay if it is shown
+    let frames = StoredValue::new((None::<SideFrame>, None::<SideFrame>));
+    let overlay = RwSignal::new(None::<Result<Overlay, String>>);
+
+    // Draw the overlay when either side renders a new frame. While both
+    // sides are animated, wait until they delivered a frame for the same
+    // time, which is once per tick of a running clock; a side that is not
+    // animated keeps its last frame, whatever its time. `redraw` draws it
+    // regardless of new frames.
+    let update_overlay = move |redraw: bool| {
+        if !show_overlay.get_untracked() {
+            return;
+        }
+        let next = frames.with_value(|frames| match frames {
+            (Some(left), Some(right))
+                if (redraw || left.fresh || right.fresh)
+                    && (!(left.animated && right.animated) || left.time == right.time) =>
+            {
+                Some(compare::overlay(&left.svg, &right.svg))
+            }
+            _ => None,
+        });
+        if let Some(next) = next {
+            frames.update_value(|(left, right)| {
+                for side in [left, right].into_iter().flatten() {
+                    side.fresh = false;
+                }
+            });
+            overlay.set(Some(next));
+        }
+    };
+    let on_left = Callback::new(move |(svg, time, animated): (String, f64, bool)| {
+        frames.update_value(|frames| {
+            frames.0 = Some(SideFrame {
+                svg,
+                time,
+                animated,
+                fresh: true,
+            })
+        });
+        update_overlay(false);
+    });
+    let on_right = Callback::new(move |(svg, time, animated): (String, f64, bool)| {
+        frames.update_value(|frames| {
+            frames.1 = Some(SideFrame {
+                svg,
+                time,
+                animated,
+                fresh: true,
+            })
+        });
+        update_overlay(false);
+    });
+
+    let pick_other = move |_| {
+        leptos::task::spawn_local(async move {
+            let Some(file) = AsyncFileDialog::new()
+                .add_filter("WebAssembly", &["wasm"])
+                .pick_file()
+                .await
+            else {
+                return;
+            };
+            other.set(file.read().await);
+        });
+    };
+    let change_mode = move |ev| {
+        let next = match event_target_value(&ev).as_str() {
+            "builds" => Mode::Builds,
+            _ => Mode::Values,
+        };
+        // Frames of the replaced views no longer match
+        frames.set_value((None, None));
+        overlay.set(None);
+        mode.set(next);
+    };
+    let toggle_overlay = move |_| {
+        let next = !show_overlay.get_untracked();
+        show_overlay.set(next);
+        if next {
+            update_overlay(true);
+        } else {
+            overlay.set(None);
+        }
+    };
+
+    let swatch = |color: &'static str| {
+        view! { <span class="inline-block w-3 h-3 align-middle" style:background-color=color></span> }
+    };
+    let legend = move || match overlay.get() {
+        None => ().into_any(),
+        Some(Err(err)) => {
+            view! { <p class="text-red-500">"Cannot compare frames: " {err}</p> }.into_any()
+        }
+        Some(Ok(drawn)) => view! {
+            <div class="bg-white rounded shadow p-2 space-y-1">
+                <div class="space-x-4 text-sm">
+                    <span>{drawn.shared} " shared"</span>
+                    <span>{swatch(ONLY_LEFT_COLOR)} " " {drawn.only_left} " only left"</span>
+                    <span>{swatch(ONLY_RIGHT_COLOR)} " " {drawn.only_right} " only right"</span>
+                </div>
+                <img class="w-full max-h-[50vh] object-contain" src=svg_data_url(&drawn.svg)/>
+            </div>
+        }
+        .into_any(),
+    };
+
+    // The views are replaced when the mode changes, so the right one starts
+    // over with its module
+    let views = move || {
+        let views = match mode.get() {
+            Mode::Values => view! {
+                <DemoView module=module height="50vh" clock=clock on_frame=on_left/>
+                <DemoView module=module height="50vh" clock=clock on_frame=on_right/>
+            }
+            .into_any(),
+            Mode::Builds => {
+                let link = RwSignal::new(None::<Preset>);
+                view! {
+                    <DemoView module=module height="50vh" clock=clock link=link on_frame=on_left/>
+                    <DemoView module=other height="50vh" clock=clock link=link on_frame=on_right>
+                        <button class="px-2 border rounded bg-white" on:click=pick_other>
+                            "Open other build"
+                        </button>
+                    </DemoView>
+                }
+                .into_any()
+            }
+        };
+        view! { <div class="grid grid-cols-2 gap-4">{views}</div> }
+    };
+
+    view! {
+        <div class="space-y-2">
+            <div class="space-x-2">
+                {children.map(|children| children())}
+                <select class="px-2 border rounded bg-white" on:change=change_mode>
+                    <option value="values">"Same module, two parameter sets"</option>
+                    <option value="builds">"Two builds, same parameters"</option>
+                </select>
+                <label>
+                    <input type="checkbox" prop:checked=show_overlay on:change=toggle_overlay/>
+                    " Overlay"
+                </label>
+            </div>
+            {views}
+            {legend}
+        </div>
+    }
+}
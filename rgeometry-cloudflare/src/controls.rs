@@ -0,0 +1,569 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use leptos::prelude::*;
+use rfd::AsyncFileDialog;
+use rgeometry_host::wasm::{
+    format_color, is_visible, parse_color, RangeOptions, Scale, Schema, SchemaType, Value,
+};
+
+/// State of the seed entries that outlives the widgets, which are rebuilt
+/// whenever the schema or all values change.
+#[derive(Debug, Clone, Copy)]
+pub struct SeedRerolls {
+    /// Seed entries that are not rerolled, by schema index
+    pub locked: RwSignal<HashSet<usize>>,
+    /// Whether playback is paused, which stops the rerolls too
+    pub paused: Signal<bool>,
+    /// Receives the seeds picked by the timer, which are not worth keeping
+    pub on_reroll: Callback<(usize, Value)>,
+}
+
+/// Whether entry `index` of `schema` is a seed that rerolls by itself.
+pub fn rerolls_by_itself(schema: &Schema, index: usize) -> bool {
+    matches!(
+        schema.get(index).map(|param| &param.ty),
+        Some(SchemaType::Seed {
+            reroll_every: Some(_),
+            ..
+        })
+    )
+}
+
+/// Input widgets for the user-controlled entries of a schema. Entries driven
+/// by the host, such as time and viewport, get no widget. Ungrouped entries
+/// come first, followed by a collapsible section per group. The widgets start
+/// out at `values`, or the schema default for entries without a value.
+#[component]
+pub fn ParameterControls(
+    #[prop(into)] schema: Signal<Schema>,
+    #[prop(into)] values: Signal<HashMap<usize, Value>>,
+    seeds: SeedRerolls,
+    on_change: Callback<(usize, Value)>,
+) -> impl IntoView {
+    view! {
+        <div class="flex flex-col gap-2 mb-4">
+            {move || {
+                let schema = Arc::new(schema.get());
+                let initial = values.get_untracked();
+                // Entries by group, in order of first appearance
+                let mut groups: Vec<(Option<String>, Vec<AnyView>)> = Vec::new();
+                for (index, param) in schema.iter().enumerate() {
+                    if param.ty.default_value().is_none() {
+                        continue;
+                    }
+                    let visible = {
+                        let schema = schema.clone();
+                        move || values.with(|values| is_visible(&schema, index, values))
+                    };
+                    let entry = view! {
+                        <div class="flex flex-col" class:hidden=move || !visible()>
+                            {control(
+                                index,
+                                param.ty.clone(),
+                                param.display_label(index),
+                                initial.get(&index).cloned(),
+                                seeds,
+                                on_change,
+                            )}
+                            {param
+                                .description
+                                .clone()
+                                .map(|description| {
+                                    view! { <p class="ml-[8.5rem] text-sm text-gray-600">{description}</p> }
+                                })}
+                        </div>
+                    }
+                    .into_any();
+                    match groups.iter_mut().find(|(group, _)| *group == param.group) {
+                        Some((_, entries)) => entries.push(entry),
+                        None => groups.push((param.group.clone(), vec![entry])),
+                    }
+                }
+                groups.sort_by_key(|(group, _)| group.is_some());
+                groups
+                    .into_iter()
+                    .map(|(group, entries)| match group {
+                        None => entries.into_any(),
+                        Some(title) => view! {
+                            <details open class="bg-white/50 rounded p-2">
+                                <summary class="font-bold cursor-pointer">{title}</summary>
+                                <div class="flex flex-col gap-2 mt-2">{entries}</div>
+                            </details>
+                        }
+                        .into_any(),
+                    })
+                    .collect_view()
+            }}
+        </div>
+    }
+}
+
+// Widget for a single entry, showing `initial` if it suits the entry
+fn control(
+    index: usize,
+    param: SchemaType,
+    label: String,
+    initial: Option<Value>,
+    seeds: SeedRerolls,
+    on_change: Callback<(usize, Value)>,
+) -> AnyView {
+    let initial_i32 = match initial {
+        Some(Value::I32(v)) => Some(v),
+        _ => None,
+    };
+    match param {
+        SchemaType::Time
+        | SchemaType::Frame
+        | SchemaType::DeltaTime
+        | SchemaType::Viewport
+        | SchemaType::Camera => ().into_any(),
+        SchemaType::RangeF32 {
+            min,
+            max,
+            default,
+            ref options,
+        } => {
+            let start = match initial {
+                Some(Value::F32(v)) => v,
+                _ => default,
+            };
+            let range = [min, max].map(f64::from);
+            let options = options.clone();
+            range_control(
+                index,
+                label,
+                param,
+                range,
+                Value::F32(start),
+                options,
+                false,
+                |v| Value::F32(v as f32),
+                on_change,
+            )
+        }
+        SchemaType::RangeI32 {
+            min,
+            max,
+            default,
+            ref options,
+        } => {
+            let start = initial_i32.unwrap_or(default);
+            let range = [min, max].map(f64::from);
+            let options = options.clone();
+            range_control(
+                index,
+                label,
+                param,
+                range,
+                Value::I32(start),
+                options,
+                true,
+                |v| Value::I32(v as i32),
+                on_change,
+            )
+        }
+        SchemaType::RangeF64 {
+            min,
+            max,
+            default,
+            ref options,
+        } => {
+            let start = match initial {
+                Some(Value::F64(v)) => v,
+                _ => default,
+            };
+            let options = options.clone();
+            range_control(
+                index,
+                label,
+                param,
+                [min, max],
+                Value::F64(start),
+                options,
+                false,
+                Value::F64,
+                on_change,
+            )
+        }
+        SchemaType::RangeI64 {
+            min,
+            max,
+            default,
+            ref options,
+        } => {
+            let start = match initial {
+                Some(Value::I64(v)) => v,
+                _ => default,
+            };
+            let range = [min, max].map(|v| v as f64);
+            let options = options.clone();
+            // The f64 bounds may round outside the i64 ones
+            range_control(
+                index,
+                label,
+                param,
+                range,
+                Value::I64(start),
+                options,
+                true,
+                move |v| Value::I64((v as i64).clamp(min, max)),
+                on_change,
+            )
+        }
+        SchemaType::Bool { default } => view! {
+            <label class="flex items-center gap-2">
+                <span class="w-32">{label}</span>
+                <input
+                    type="checkbox"
+                    checked=initial_i32.map_or(default, |v| v != 0)
+                    on:change=move |ev| {
+                        let checked = event_target_checked(&ev);
+                        on_change.run((index, Value::I32(checked as i32)));
+                    }
+                />
+            </label>
+        }
+        .into_any(),
+        SchemaType::Enum { options, default } => {
+            let default = initial_i32.map_or(default as usize, |v| v as usize);
+            view! {
+                <label class="flex items-center gap-2">
+                    <span class="w-32">{label}</span>
+                    <select on:change=move |ev| {
+                        if let Ok(v) = event_target_value(&ev).parse::<i32>() {
+                            on_change.run((index, Value::I32(v)));
+                        }
+                    }>
+                        {options
+                            .into_iter()
+                            .enumerate()
+                            .map(|(i, option)| {
+                                view! {
+                                    <option value=i.to_string() selected=i == default>
+                                        {option}
+                                    </option>
+                                }
+                            })
+                            .collect_view()}
+                    </select>
+                </label>
+            }
+            .into_any()
+        }
+        SchemaType::Color { default } => {
+            let default = initial_i32.map(|v| v as u32).or(parse_color(&default));
+            let rgba = RwSignal::new(default.unwrap_or(0xff));
+            let update = move |value: u32| {
+                rgba.set(value);
+                on_change.run((index, Value::I32(value as i32)));
+            };
+            view! {
+                <label class="flex items-center gap-2">
+                    <span class="w-32">{label}</span>
+                    <input
+                        type="color"
+                        // The picker has no alpha channel, it only sees #rrggbb
+                        value=move || format_color(rgba.get())[..7].to_string()
+                        on:input=move |ev| {
+                            if let Some(rgb) = parse_color(&event_target_value(&ev)) {
+                                update(rgb & !0xff | rgba.get_untracked() & 0xff);
+                            }
+                        }
+                    />
+                    <input
+                        type="range"
+                        title="Opacity"
+                        min="0"
+                        max="255"
+                        step="1"
+                        value=move || (rgba.get() & 0xff).to_string()
+                        on:input=move |ev| {
+                            if let Ok(alpha) = event_target_value(&ev).parse::<u32>() {
+                                update(rgba.get_untracked() & !0xff | alpha.min(0xff));
+                            }
+                        }
+                    />
+                </label>
+            }
+            .into_any()
+        }
+        SchemaType::Seed {
+            default,
+            reroll_every,
+        } => {
+            let seed = RwSignal::new(match initial {
+                Some(Value::I64(v)) => v,
+                _ => default,
+            });
+            let SeedRerolls {
+                locked,
+                paused,
+                on_reroll,
+            } = seeds;
+            let is_locked = move || locked.with(|locked| locked.contains(&index));
+            let update = move |value: i64| {
+                seed.set(value);
+                on_change.run((index, Value::I64(value)));
+            };
+            if let Some(seconds) = reroll_every {
+                let interval = set_interval_with_handle(
+                    move || {
+                        let locked = locked.with_untracked(|locked| locked.contains(&index));
+                        if !locked && !paused.get_untracked() {
+                            let value = random_seed();
+                            seed.set(value);
+                            on_reroll.run((index, Value::I64(value)));
+                        }
+                    },
+                    Duration::from_secs_f64(seconds),
+                );
+                if let Ok(interval) = interval {
+                    on_cleanup(move || interval.clear());
+                }
+            }
+            // Locking keeps the seed shown, so it is passed on once more
+            let set_locked = move |lock: bool| {
+                locked.update(|locked| {
+                    if lock {
+                        locked.insert(index);
+                    } else {
+                        locked.remove(&index);
+                    }
+                });
+                on_change.run((index, Value::I64(seed.get_untracked())));
+            };
+            view! {
+                <div class="flex items-center gap-2">
+                    <span class="w-32">{label}</span>
+                    <input
+                        type="text"
+                        inputmode="numeric"
+                        class="w-48 tabular-nums"
+                        prop:value=move || seed.get().to_string()
+                        on:change=move |ev| {
+                            match event_target_value(&ev).trim().parse::<i64>() {
+                                Ok(value) => update(value),
+                                Err(_) => seed.notify(),
+                            }
+                        }
+                    />
+                    <button
+                        class="px-2 border rounded bg-white disabled:opacity-50"
+                        disabled=is_locked
+                        on:click=move |_| update(random_seed())
+                    >
+                        "Reroll"
+                    </button>
+                    <label class="flex items-center gap-1">
+                        <input
+                            type="checkbox"
+                            prop:checked=is_locked
+                            on:change=move |ev| set_locked(event_target_checked(&ev))
+                        />
+                        "Lock"
+                    </label>
+                </div>
+            }
+            .into_any()
+        }
+        SchemaType::Point2d { .. } => view! {
+            <div class="flex items-center gap-2">
+                <span class="w-32">{label}</span>
+                <span class="text-gray-600">"Drag the handle in the figure"</span>
+            </div>
+        }
+        .into_any(),
+        SchemaType::PointSet { .. } => view! {
+            <div class="flex items-center gap-2">
+                <span class="w-32">{label}</span>
+                <span class="text-gray-600">
+                    "Click the figure to add points, drag to move, right-click to remove"
+                </span>
+            </div>
+        }
+        .into_any(),
+        SchemaType::Text { default } => {
+            let default = match initial {
+                Some(Value::Text(text)) => text,
+                _ => default,
+            };
+            view! {
+                <label class="flex items-start gap-2">
+                    <span class="w-32">{label}</span>
+                    <textarea
+                        class="w-96 h-24 font-mono text-sm"
+                        on:input=move |ev| {
+                            on_change.run((index, Value::Text(event_target_value(&ev))));
+                        }
+                    >
+                        {default}
+                    </textarea>
+                </label>
+            }
+            .into_any()
+        }
+        SchemaType::File { extensions } => {
+            let file_name = RwSignal::new(None::<String>);
+            let pick_file = move |_| {
+                let extensions = extensions.clone();
+                leptos::task::spawn_local(async move {
+                    let mut dialog = AsyncFileDialog::new();
+                    if !extensions.is_empty() {
+                        dialog = dialog.add_filter("Data", &extensions);
+                    }
+                    let Some(file) = dialog.pick_file().await else {
+                        return;
+                    };
+                    file_name.set(Some(file.file_name()));
+                    on_change.run((index, Value::Bytes(file.read().await)));
+                });
+            };
+            view! {
+                <div class="flex items-center gap-2">
+                    <span class="w-32">{label}</span>
+                    <button class="px-2 border rounded bg-white" on:click=pick_file>
+                        "Choose file"
+                    </button>
+                    <span class="text-gray-600">
+                        {move || file_name.get().unwrap_or_else(|| "No file selected".to_string())}
+                    </span>
+                </div>
+            }
+            .into_any()
+        }
+    }
+}
+
+// Number of positions of a log scale slider
+const LOG_SLIDER_STEPS: u32 = 1000;
+
+// Slider plus a number field for the range types, starting at `start`.
+// Linear sliders use the native min, max and step, log sliders move through
+// LOG_SLIDER_STEPS positions mapped by the scale. The number field shows and
+// takes exact values, including 64-bit integers that don't fit an f64.
+// `to_value` turns a slider value into a value of the entry, within its
+// bounds.
+#[allow(clippy::too_many_arguments)]
+fn range_control(
+    index: usize,
+    label: String,
+    param: SchemaType,
+    [min, max]: [f64; 2],
+    start: Value,
+    options: RangeOptions,
+    integer: bool,
+    to_value: impl Fn(f64) -> Value + Copy + 'static,
+    on_change: Callback<(usize, Value)>,
+) -> AnyView {
+    let RangeOptions {
+        step,
+        scale,
+        unit,
+        precision,
+    } = options;
+    let format = move |v: f64| {
+        if integer {
+            format!("{}", v.round())
+        } else {
+            format!("{:.*}", precision.unwrap_or(2) as usize, v)
+        }
+    };
+    // Where a value is on the slider
+    let position = |value: &Value| match *value {
+        Value::F32(v) => Some(v.into()),
+        Value::I32(v) => Some(v.into()),
+        Value::F64(v) => Some(v),
+        Value::I64(v) => Some(v as f64),
+        _ => None,
+    };
+    // Text of a value, exact for 64-bit integers
+    let show = move |value: &Value| match *value {
+        Value::I64(v) => v.to_string(),
+        _ => format(position(value).unwrap_or(min)),
+    };
+    let value = RwSignal::new(position(&start).unwrap_or(min));
+    let text = RwSignal::new(show(&start));
+    let (slider_min, slider_max, slider_step) = match scale {
+        Scale::Linear => (
+            min,
+            max,
+            step.map_or(if integer { "1" } else { "any" }.to_string(), |step| {
+                step.to_string()
+            }),
+        ),
+        Scale::Log => (0.0, LOG_SLIDER_STEPS as f64, "1".to_string()),
+    };
+    let slider_position = move || match scale {
+        Scale::Linear => value.get(),
+        Scale::Log => scale.position(min, max, value.get()) * LOG_SLIDER_STEPS as f64,
+    };
+
+    let on_slider = move |ev| {
+        let Ok(at) = event_target_value(&ev).parse::<f64>() else {
+            return;
+        };
+        let mut v = match scale {
+            Scale::Linear => at,
+            Scale::Log => scale.value(min, max, at / LOG_SLIDER_STEPS as f64),
+        };
+        // Steps count from the minimum, as on linear sliders
+        if let (Scale::Log, Some(step)) = (scale, step) {
+            v = min + ((v - min) / step).round() * step;
+        }
+        if integer {
+            v = v.round();
+        }
+        let exact = to_value(v.clamp(min, max));
+        value.set(position(&exact).unwrap_or(min));
+        text.set(show(&exact));
+        on_change.run((index, exact));
+    };
+    let on_text = move |ev| {
+        let input = event_target_value(&ev);
+        let Some((exact, v)) = param
+            .parse_value(input.trim())
+            .and_then(|exact| position(&exact).map(|v| (exact, v)))
+        else {
+            // Out of range or malformed, show the current value again
+            text.notify();
+            return;
+        };
+        value.set(v);
+        text.set(input.trim().to_string());
+        on_change.run((index, exact));
+    };
+
+    view! {
+        <label class="flex items-center gap-2">
+            <span class="w-32">{label}</span>
+            <input
+                type="range"
+                min=slider_min.to_string()
+                max=slider_max.to_string()
+                step=slider_step
+                prop:value=move || slider_position().to_string()
+                on:input=on_slider
+            />
+            <input
+                type="text"
+                inputmode="decimal"
+                class="w-24 text-right tabular-nums"
+                prop:value=move || text.get()
+                on:change=on_text
+            />
+            {unit.map(|unit| view! { <span class="text-gray-600">{unit}</span> })}
+        </label>
+    }
+    .into_any()
+}
+
+// A fresh seed, kept below 2^32 so it is easy to note down and share
+fn random_seed() -> i64 {
+    let mut bytes = [0; 4];
+    if let Err(err) = getrandom::getrandom(&mut bytes) {
+        log::error!("Failed to get a random seed: {}", err);
+    }
+    u32::from_le_bytes(bytes).into()
+}
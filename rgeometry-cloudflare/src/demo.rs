@@ -0,0 +1,834 @@
+use std::collections::{HashMap, HashSet};
+use std::time::Duration;
+
+use crate::app::svg_data_url;
+use crate::compare::Clock;
+use crate::controls::{rerolls_by_itself, ParameterControls, SeedRerolls};
+use crate::download::download;
+use crate::export::{Export, ExportMenu};
+use crate::grid::GridPanel;
+use crate::metrics::{self, FrameSample, History, MetricsOverlay};
+use crate::overlay::HandleOverlay;
+use crate::presets::PresetPanel;
+use crate::query;
+use crate::storage::{self, Session, SessionKeys};
+use crate::sweep::SweepPanel;
+use crate::worker::{GuestState, GuestWorker, Response, DEFAULT_FUEL_LIMIT};
+use leptos::web_sys::js_sys::Array;
+use leptos::web_sys::{Element, HtmlDivElement, IntersectionObserver, IntersectionObserverEntry};
+use leptos::{
+    html::{Div, Img},
+    prelude::*,
+};
+use rgeometry_host::animation::{AnimationEncoder, AnimationFormat, AnimationOptions};
+use rgeometry_host::export::{self, FrameInfo};
+use rgeometry_host::viewbox::ViewBox;
+use rgeometry_host::wasm::{Metadata, Preset, Schema, Value, Viewport};
+use wasm_bindgen::prelude::*;
+
+// Frame rate limits to pick from, None for one frame per browser frame
+const FRAME_RATES: [Option<u32>; 5] = [None, Some(60), Some(30), Some(15), Some(5)];
+
+// Fuel budgets of a frame to pick from, with their labels, None for no limit
+const FUEL_LIMITS: [(Option<u64>, &str); 4] = [
+    (Some(100_000_000), "100M fuel"),
+    (Some(DEFAULT_FUEL_LIMIT), "1G fuel"),
+    (Some(10_000_000_000), "10G fuel"),
+    (None, "No fuel limit"),
+];
+
+// Reports whether an element is on screen until dropped
+struct VisibilityObserver {
+    observer: IntersectionObserver,
+    _callback: Closure<dyn FnMut(Array)>,
+}
+
+impl VisibilityObserver {
+    // Call `on_change` with whether `element` is at least partly on screen,
+    // whenever that changes
+    fn new(element: &Element, on_change: impl Fn(bool) + 'static) -> Result<Self, String> {
+        let callback = Closure::<dyn FnMut(Array)>::new(move |entries: Array| {
+            if let Some(entry) = entries.iter().last() {
+                on_change(
+                    entry
+                        .unchecked_into::<IntersectionObserverEntry>()
+                        .is_intersecting(),
+                );
+            }
+        });
+        let observer = IntersectionObserver::new(callback.as_ref().unchecked_ref())
+            .map_err(|e| format!("Failed to observe the frame: {:?}", e))?;
+        observer.observe(element);
+        Ok(VisibilityObserver {
+            observer,
+            _callback: callback,
+        })
+    }
+}
+
+impl Drop for VisibilityObserver {
+    fn drop(&mut self) {
+        self.observer.disconnect();
+    }
+}
+
+// Ask the worker for a frame of the current size and camera if it needs one.
+// Frames are shown when they arrive. Stops once the view is removed.
+fn animate(
+    container: NodeRef<Div>,
+    camera: RwSignal<Option<ViewBox>>,
+    viewport: StoredValue<Viewport>,
+    on_screen: StoredValue<bool>,
+    clock: Option<Clock>,
+    guest: StoredValue<Option<GuestWorker>, LocalStorage>,
+) {
+    let Some(visible) = on_screen.try_get_value() else {
+        return;
+    };
+    if visible && !document().hidden() {
+        if let Some(container) = container.get_untracked() {
+            viewport.set_value(Viewport {
+                width: container.client_width() as f32,
+                height: container.client_height() as f32,
+                device_pixel_ratio: window().device_pixel_ratio() as f32,
+            });
+        }
+        guest.update_value(|guest| {
+            if let Some(guest) = guest.as_mut() {
+                guest.request_frame(
+                    metrics::now(),
+                    viewport.get_value(),
+                    camera.get_untracked(),
+                    clock.map(|clock| clock.time()),
+                );
+            }
+        });
+    }
+    request_animation_frame(move || animate(container, camera, viewport, on_screen, clock, guest));
+}
+
+// An animation being exported by a worker of its own, which renders a frame
+// while the page encodes the one before
+struct AnimationJob {
+    // Number of the export, see `export_run`
+    run: u64,
+    guest: GuestWorker,
+    encoder: AnimationEncoder,
+    options: AnimationOptions,
+    // Values to render with, set once the module opened
+    state: GuestState,
+    viewport: Viewport,
+    camera: Option<ViewBox>,
+    file_name: String,
+    mime: &'static str,
+}
+
+// Take in an answer of the worker exporting animation `run`: set the values
+// once the module opened, ask for the next frame and encode the one that
+// arrived. Downloads the file after the last frame. Answers of an export
+// that is no longer in `job`, such as one that was canceled, are ignored.
+fn export_answer(
+    job: StoredValue<Option<AnimationJob>, LocalStorage>,
+    run: u64,
+    response: Response,
+    progress: RwSignal<Option<(usize, usize)>>,
+) {
+    // The export, once it ended, or why it failed
+    let ended = job.try_update_value(|slot| {
+        let job = slot.as_mut().filter(|job| job.run == run)?;
+        let total = job.options.frame_count();
+        let result = match response {
+            Response::Opened { .. } => {
+                job.guest.restore(job.state.clone());
+                job.guest
+                    .render_animation_frame(job.options, 0, job.viewport);
+                Ok(false)
+            }
+            Response::AnimationFrame(index, svg) => {
+                if index + 1 < total {
+                    job.guest
+                        .render_animation_frame(job.options, index + 1, job.viewport);
+                }
+                let svg = match job.camera {
+                    Some(camera) => camera.apply_to(&svg),
+                    None => svg,
+                };
+                progress.set(Some((index + 1, total)));
+                job.encoder.add_frame(&svg).map(|()| index + 1 == total)
+            }
+            Response::Rejected(_, err) | Response::Error(err) => Err(err),
+            _ => Ok(false),
+        };
+        match result {
+            Ok(false) => None,
+            Ok(true) => slot.take().map(Ok),
+            Err(err) => {
+                *slot = None;
+                Some(Err(err))
+            }
+        }
+    });
+    let Some(Some(ended)) = ended else {
+        return;
+    };
+    progress.set(None);
+    let result = ended.and_then(|job| {
+        let bytes = job.encoder.finish()?;
+        download(&job.file_name, job.mime, &bytes)
+    });
+    if let Err(err) = result {
+        log::error!("Failed to export: {}", err);
+    }
+}
+
+/// A live figure of a `module`: its frames, with playback, metrics and export
+/// buttons, presets, parameter controls and the complexity sweep. Every view
+/// runs its own instance of the module in its own worker, with its own
+/// values and clock, so several views can be shown side by side. An empty
+/// module shows nothing until one is given. `children` are put in front of
+/// the buttons, and `height` is a CSS height of the frame area.
+///
+/// With `persist`, values are read from and written to the URL, and the
+/// session, the user presets and the module itself are kept in local storage
+/// to be restored on the next visit. At most one view on a page should
+/// persist.
+///
+/// Views given the same `clock` show the same time, and its buttons pause
+/// and restart them all. Views given the same `link` share their values, by
+/// parameter name, so different builds of a module get the same input.
+/// `on_frame` receives every frame, its time and whether the next frame may
+/// differ, before any zoom is applied.
+#[component]
+pub fn DemoView(
+    #[prop(into)] module: Signal<Vec<u8>>,
+    #[prop(optional)] persist: bool,
+    #[prop(default = "80vh")] height: &'static str,
+    #[prop(optional)] clock: Option<Clock>,
+    #[prop(optional)] link: Option<RwSignal<Option<Preset>>>,
+    #[prop(optional)] on_frame: Option<Callback<(String, f64, bool)>>,
+    #[prop(optional)] children: Option<Children>,
+) -> impl IntoView {
+    let img_ref = NodeRef::<Img>::new();
+    let container_ref = NodeRef::<Div>::new();
+
+    // The module runs in a worker, so slow frames don't block the page
+    let guest = StoredValue::new_local(None::<GuestWorker>);
+    let schema = RwSignal::new(Schema::new());
+    let meta = StoredValue::new(Metadata::default());
+    // Size of the frame area, as last sent to the module
+    let viewport = StoredValue::new(Viewport::default());
+    // Problem with the module to show above the frame
+    let notice = RwSignal::new(None::<String>);
+    // Frames are only rendered while the frame area is on screen
+    let on_screen = StoredValue::new(true);
+    let observer = StoredValue::new_local(None::<VisibilityObserver>);
+    let frame_rate = RwSignal::new(None::<u32>);
+    let fuel_limit = RwSignal::new(Some(DEFAULT_FUEL_LIMIT));
+    // View box of the last frame, and the region of it that is shown if the
+    // user zoomed or panned
+    let view_box = RwSignal::new(None::<ViewBox>);
+    let camera = RwSignal::new(None::<ViewBox>);
+    // The displayed frame, and the bytes of the open module
+    let frame = StoredValue::new(String::new());
+    let bytes = StoredValue::new(Vec::<u8>::new());
+    // Costs of the last frames, collected while the metrics overlay is shown
+    let frame_history = RwSignal::new(None::<History>);
+    // Whether the overlay is shown, which doesn't change with every frame
+    let metrics_shown = Memo::new(move |_| frame_history.with(Option::is_some));
+    // Values the user picked, by schema index
+    let values = RwSignal::new(HashMap::<usize, Value>::new());
+    // Seeds that are not rerolled, by schema index
+    let locked = RwSignal::new(HashSet::<usize>::new());
+
+    // Storage keys of the open module's session, None unless persisted, and
+    // the content hash of the module opened last
+    let session_keys = StoredValue::new(None::<SessionKeys>);
+    let opening = StoredValue::new(None::<String>);
+    let paused = RwSignal::new(false);
+    let builtin_presets = RwSignal::new(Vec::<Preset>::new());
+    let user_presets = RwSignal::new(Vec::<Preset>::new());
+
+    // Send a request to the module's worker, if it runs
+    let with_guest = move |f: &dyn Fn(&mut GuestWorker)| {
+        guest.update_value(|guest| {
+            if let Some(guest) = guest.as_mut() {
+                f(guest);
+            }
+        })
+    };
+    // Time of the last frame of the open module
+    let time = move || {
+        guest.with_value(|guest| {
+            guest
+                .as_ref()
+                .filter(|g| g.is_open())
+                .map(GuestWorker::time)
+        })
+    };
+
+    // The values to store. Seeds that reroll by themselves are only stored
+    // once locked, so a restored seed is one the user chose to keep.
+    let kept_values = move || {
+        schema.with_untracked(|schema| {
+            values.with_untracked(|values| {
+                locked.with_untracked(|locked| {
+                    values
+                        .iter()
+                        .filter(|(index, _)| {
+                            locked.contains(index) || !rerolls_by_itself(schema, **index)
+                        })
+                        .map(|(index, value)| (*index, value.clone()))
+                        .collect::<HashMap<_, _>>()
+                })
+            })
+        })
+    };
+    // Lock the seeds among `given` that would otherwise be rerolled
+    let lock_given_seeds = move |given: &HashMap<usize, Value>| {
+        let seeds: Vec<usize> = schema.with_untracked(|schema| {
+            given
+                .keys()
+                .copied()
+                .filter(|index| rerolls_by_itself(schema, *index))
+                .collect()
+        });
+        if !seeds.is_empty() {
+            locked.update(|locked| locked.extend(seeds));
+        }
+    };
+
+    // Store the values and playback state of the open module
+    let save_session = move || {
+        let Some(keys) = session_keys.get_value() else {
+            return;
+        };
+        let Some(time) = time() else {
+            return;
+        };
+        let kept = kept_values();
+        let session = schema
+            .with_untracked(|schema| Session::capture(schema, &kept, paused.get_untracked(), time));
+        storage::save_session(&keys, &session);
+    };
+    // Share the values with the linked views
+    let share_values = move || {
+        if let Some(link) = link {
+            let shared = schema.with_untracked(|schema| {
+                values.with_untracked(|values| Preset::capture(String::new(), schema, values))
+            });
+            link.set(Some(shared));
+        }
+    };
+    // Keep the values in the URL and the session in storage, and share them
+    let values_changed = move || {
+        if persist {
+            let kept = kept_values();
+            schema.with_untracked(|schema| query::write(schema, &kept));
+        }
+        save_session();
+        share_values();
+    };
+
+    let on_change = Callback::new(move |(index, value): (usize, Value)| {
+        with_guest(&|guest| guest.set_parameter(index, value.clone()));
+        values.update(|values| {
+            values.insert(index, value);
+        });
+        values_changed();
+    });
+    // Seeds rerolled by their timer are only shared, storing them every time
+    // would rewrite the URL and storage every few seconds
+    let on_reroll = Callback::new(move |(index, value): (usize, Value)| {
+        with_guest(&|guest| guest.set_parameter(index, value.clone()));
+        values.update(|values| {
+            values.insert(index, value);
+        });
+        share_values();
+    });
+    let seeds = SeedRerolls {
+        locked,
+        paused: paused.into(),
+        on_reroll,
+    };
+
+    // Show a frame or an answer to opening a module. Errors must not touch
+    // `guest`, which is borrowed while it reports a missed deadline.
+    let on_response = move |response: Response| match response {
+        // Restore the module's stored session. Values given in the URL take
+        // precedence over stored ones.
+        Response::Opened {
+            hash,
+            schema: opened,
+            meta: opened_meta,
+            warnings,
+        } => {
+            // Another module was given since, and is opened next
+            if opening.with_value(|opening| opening.as_ref() != Some(&hash)) {
+                return;
+            }
+            let mut state = GuestState::default();
+            let mut initial = HashMap::new();
+            if persist {
+                let keys = SessionKeys::new(&hash, &opened);
+                let session = storage::load_session(&keys).unwrap_or_default();
+                initial = session
+                    .restore_values(&opened)
+                    .into_iter()
+                    .chain(query::read(&opened))
+                    .collect();
+                state = GuestState {
+                    time: session.time,
+                    paused: session.paused,
+                    values: initial.clone().into_iter().collect(),
+                };
+                bytes.with_value(|bytes| storage::save_module(bytes));
+                user_presets.set(storage::load_presets(&keys));
+                session_keys.set_value(Some(keys));
+            } else {
+                user_presets.set(Vec::new());
+            }
+            if let Some(shared) = link.and_then(|link| link.get_untracked()) {
+                initial = shared.resolve_known(&opened).into_iter().collect();
+                state.values = initial.clone().into_iter().collect();
+            }
+            if let Some(clock) = clock {
+                state.time = clock.time();
+                state.paused = clock.is_paused_untracked();
+            }
+            builtin_presets.set(opened_meta.presets.clone());
+            meta.set_value(opened_meta);
+            paused.set(state.paused);
+            camera.set(None);
+            // Seeds given by the URL, the session or a linked view are kept
+            locked.set(
+                initial
+                    .keys()
+                    .copied()
+                    .filter(|index| rerolls_by_itself(&opened, *index))
+                    .collect(),
+            );
+            values.set(initial);
+            schema.set(opened);
+            notice.set((!warnings.is_empty()).then(|| warnings.join("\n")));
+            with_guest(&|guest| guest.restore(state.clone()));
+        }
+        Response::Rejected(index, err) => {
+            log::error!("Failed to set parameter {}: {}", index, err);
+            values.update(|values| {
+                values.remove(&index);
+            });
+        }
+        Response::Frame {
+            mut svg,
+            time,
+            metrics: frame_metrics,
+            animated,
+        } => {
+            let Some(img) = img_ref.get_untracked() else {
+                return;
+            };
+            if let Some(on_frame) = on_frame {
+                on_frame.run((svg.clone(), time, animated));
+            }
+            let received = metrics::now();
+            let frame_view_box = ViewBox::from_svg(&svg);
+            if view_box.get_untracked() != frame_view_box {
+                view_box.set(frame_view_box);
+            }
+            if let Some(camera) = camera.get_untracked() {
+                svg = camera.apply_to(&svg);
+            }
+            img.set_src(&svg_data_url(&svg));
+            frame.set_value(svg);
+            if frame_history.with_untracked(Option::is_some) {
+                let shown_at = metrics::now();
+                let sample = FrameSample {
+                    at: received - frame_metrics.guest_time,
+                    frame: frame_metrics,
+                    svg_time: shown_at - received,
+                    shown_at,
+                    decode_time: None,
+                };
+                frame_history.update(|history| {
+                    if let Some(history) = history {
+                        history.push(sample);
+                    }
+                });
+            }
+        }
+        Response::Error(err) => {
+            log::error!("{}", err);
+            notice.set(Some(err));
+        }
+        Response::OutOfFuel(limit) => {
+            let message = format!(
+                "A frame consumed all of its {} fuel and was stopped. Pick a larger fuel budget to keep rendering.",
+                limit
+            );
+            log::error!("{}", message);
+            notice.set(Some(message));
+        }
+        Response::Ready | Response::AnimationFrame(..) | Response::Measured(_) => {}
+    };
+    if !cfg!(feature = "ssr") {
+        match GuestWorker::new(on_response) {
+            Ok(worker) => guest.set_value(Some(worker)),
+            Err(err) => notice.set(Some(err)),
+        }
+    }
+
+    // Open every module given in the worker, which restores its session once
+    // the module is instantiated
+    Effect::new(move |_| {
+        let opened = module.with(|module| {
+            bytes.set_value(module.clone());
+            !module.is_empty()
+        });
+        if opened {
+            session_keys.set_value(None);
+            opening.set_value(Some(bytes.with_value(|bytes| storage::content_hash(bytes))));
+            bytes.with_value(|bytes| with_guest(&|guest| guest.open(bytes)));
+        }
+    });
+
+    let toggle_paused = move |_| {
+        let next = !paused.get_untracked();
+        match clock {
+            Some(clock) => clock.set_paused(next),
+            None => {
+                with_guest(&|guest| guest.set_paused(next));
+                paused.set(next);
+                save_session();
+            }
+        }
+    };
+    let restart = move |_| match clock {
+        Some(clock) => clock.restart(),
+        None => {
+            with_guest(&|guest| guest.set_time(0.0));
+            save_session();
+        }
+    };
+    // Follow the shared clock when it is paused, resumed or restarted
+    if let Some(clock) = clock {
+        Effect::new(move |_| {
+            let next = clock.is_paused();
+            let time = clock.time();
+            with_guest(&|guest| {
+                guest.set_paused(next);
+                guest.set_time(time);
+            });
+            paused.set(next);
+        });
+    }
+    // Take the values of linked views
+    if let Some(link) = link {
+        Effect::new(move |_| {
+            let Some(shared) = link.get() else {
+                return;
+            };
+            let resolved: HashMap<_, _> = schema
+                .with_untracked(|schema| shared.resolve_known(schema))
+                .into_iter()
+                .collect();
+            // Views see their own changes too
+            if values.with_untracked(|values| *values == resolved) {
+                return;
+            }
+            with_guest(&|guest| {
+                guest.reset_parameters();
+                for (index, value) in &resolved {
+                    guest.set_parameter(*index, value.clone());
+                }
+            });
+            values.set(resolved);
+            // Rebuild the controls so they show the new values
+            schema.notify();
+        });
+    }
+
+    // Replace all values with those of a preset
+    let apply_preset = Callback::new(move |preset: Vec<(usize, Value)>| {
+        with_guest(&|guest| {
+            guest.reset_parameters();
+            for (index, value) in &preset {
+                guest.set_parameter(*index, value.clone());
+            }
+        });
+        let preset: HashMap<_, _> = preset.into_iter().collect();
+        lock_given_seeds(&preset);
+        values.set(preset);
+        // Rebuild the controls so they show the new values
+        schema.notify();
+        values_changed();
+    });
+    // Show a cell of the parameter grid in the view, with the values of the
+    // other parameters it was rendered with
+    let open_cell = Callback::new(move |cell: Preset| {
+        let mut all = values.get_untracked();
+        all.extend(schema.with_untracked(|schema| cell.resolve_known(schema)));
+        apply_preset.run(all.into_iter().collect());
+        if let Some(container) = container_ref.get_untracked() {
+            container.scroll_into_view();
+        }
+    });
+    // Frames of the animation being exported and how many it has, the
+    // export itself, and the number of the last one started
+    let exporting = RwSignal::new(None::<(usize, usize)>);
+    let export_job = StoredValue::new_local(None::<AnimationJob>);
+    let export_run = StoredValue::new(0u64);
+    // Export an animation with a fresh instance of the module, so it doesn't
+    // depend on the frames shown so far. Answers are taken in after the
+    // worker's handler returns, as the last one drops the worker.
+    let start_animation = move |format: AnimationFormat,
+                                options: &AnimationOptions,
+                                info: &FrameInfo,
+                                viewport: Viewport| {
+        let encoder = AnimationEncoder::new(format, options, info)?;
+        export_run.update_value(|run| *run += 1);
+        let run = export_run.get_value();
+        let mut worker = GuestWorker::new(move |response| {
+            set_timeout(
+                move || export_answer(export_job, run, response, exporting),
+                Duration::ZERO,
+            )
+        })?;
+        worker.set_fuel_limit(fuel_limit.get_untracked());
+        bytes.with_value(|bytes| worker.open(bytes));
+        export_job.set_value(Some(AnimationJob {
+            run,
+            guest: worker,
+            encoder,
+            options: *options,
+            state: GuestState {
+                time: options.start,
+                paused: true,
+                values: values.get_untracked().into_iter().collect(),
+            },
+            viewport,
+            camera: camera.get_untracked(),
+            file_name: info.file_name(format.extension()),
+            mime: format.mime(),
+        }));
+        exporting.set(Some((0, options.frame_count())));
+        Ok::<_, String>(())
+    };
+    // Dropping the export ends its worker
+    let cancel_export = Callback::new(move |()| {
+        export_job.set_value(None);
+        exporting.set(None);
+    });
+    // Save the displayed frame or an animation, along with the module title,
+    // time and values
+    let export = Callback::new(move |export: Export| {
+        let Some(time) = time() else {
+            return;
+        };
+        let info = meta.with_value(|meta| {
+            schema.with_untracked(|schema| {
+                values.with_untracked(|values| FrameInfo::capture(meta, schema, values, time))
+            })
+        });
+        let viewport = viewport.get_value();
+        let result = match export {
+            Export::Frame(format, scale) => frame
+                .with_value(|svg| export::export(svg, format, scale, &info))
+                .map(|bytes| (bytes, info.file_name(format.extension()), format.mime())),
+            Export::Animation(format, options) => {
+                let info = FrameInfo {
+                    time: options.start,
+                    ..info
+                };
+                if let Err(err) = start_animation(format, &options, &info, viewport) {
+                    log::error!("Failed to export: {}", err);
+                }
+                return;
+            }
+        };
+        let result = result.and_then(|(bytes, file_name, mime)| download(&file_name, mime, &bytes));
+        if let Err(err) = result {
+            log::error!("Failed to export: {}", err);
+        }
+    });
+    Effect::new(move |_| {
+        user_presets.with(|presets| {
+            if let Some(keys) = session_keys.get_value() {
+                storage::save_presets(&keys, presets);
+            }
+        })
+    });
+
+    if !cfg!(feature = "ssr") {
+        if persist {
+            // The time is only stored on leaving, not on every frame
+            let on_unload =
+                window_event_listener(leptos::ev::beforeunload, move |_| save_session());
+            on_cleanup(move || {
+                save_session();
+                on_unload.remove();
+            });
+        }
+        container_ref.on_load(move |container: HtmlDivElement| {
+            let visibility = VisibilityObserver::new(&container, move |visible| {
+                _ = on_screen.try_update_value(|on_screen| *on_screen = visible);
+            });
+            match visibility {
+                Ok(visibility) => observer.set_value(Some(visibility)),
+                Err(err) => log::error!("{}", err),
+            }
+        });
+        request_animation_frame(move || {
+            animate(container_ref, camera, viewport, on_screen, clock, guest)
+        });
+    }
+
+    view! {
+        <div class="flex gap-2 mb-4">
+            {children.map(|children| children())}
+            <button class="px-2 border rounded bg-white w-16" on:click=toggle_paused>
+                {move || if paused.get() { "Play" } else { "Pause" }}
+            </button>
+            <button class="px-2 border rounded bg-white" on:click=restart>
+                "Restart"
+            </button>
+            <button
+                class="px-2 border rounded bg-white disabled:opacity-50"
+                title="Fit to content"
+                disabled=move || camera.get().is_none()
+                on:click=move |_| camera.set(None)
+            >
+                "Fit"
+            </button>
+            <button
+                class="px-2 border rounded bg-white"
+                title="Show frame rate, frame times and memory"
+                on:click=move |_| {
+                    frame_history
+                        .update(|history| {
+                            *history = match history {
+                                Some(_) => None,
+                                None => Some(History::default()),
+                            };
+                        })
+                }
+            >
+                {move || {
+                    if metrics_shown.get() { "Hide metrics" } else { "Metrics" }
+                }}
+            </button>
+            <select
+                class="px-2 border rounded bg-white"
+                title="Frame rate limit"
+                on:change=move |ev| {
+                    let fps = event_target_value(&ev).parse::<u32>().ok();
+                    frame_rate.set(fps);
+                    with_guest(&|guest| guest.set_frame_rate(fps.map(f64::from)));
+                }
+            >
+                {FRAME_RATES
+                    .map(|fps| {
+                        let label = fps.map_or("Max fps".to_string(), |fps| format!("{} fps", fps));
+                        view! {
+                            <option
+                                value=fps.map(|fps| fps.to_string()).unwrap_or_default()
+                                selected=move || frame_rate.get() == fps
+                            >
+                                {label}
+                            </option>
+                        }
+                    })}
+            </select>
+            <select
+                class="px-2 border rounded bg-white"
+                title="Fuel a frame may consume, roughly its number of instructions"
+                on:change=move |ev| {
+                    let limit = event_target_value(&ev).parse::<u64>().ok();
+                    fuel_limit.set(limit);
+                    with_guest(&|guest| guest.set_fuel_limit(limit));
+                }
+            >
+                {FUEL_LIMITS
+                    .map(|(limit, label)| {
+                        view! {
+                            <option
+                                value=limit.map(|limit| limit.to_string()).unwrap_or_default()
+                                selected=move || fuel_limit.get() == limit
+                            >
+                                {label}
+                            </option>
+                        }
+                    })}
+            </select>
+            <ExportMenu on_export=export progress=exporting on_cancel=cancel_export/>
+        </div>
+        {move || {
+            notice
+                .get()
+                .map(|message| {
+                    view! {
+                        <div class="flex gap-2 mb-4 p-2 border rounded border-red-300 bg-red-50">
+                            <span class="flex-1 text-red-700 whitespace-pre-line">{message}</span>
+                            <button class="px-2" on:click=move |_| notice.set(None)>
+                                "Dismiss"
+                            </button>
+                        </div>
+                    }
+                })
+        }}
+        <PresetPanel
+            schema=schema
+            values=values
+            builtin=builtin_presets
+            user=user_presets
+            on_apply=apply_preset
+        />
+        <ParameterControls schema=schema values=values seeds=seeds on_change=on_change/>
+        <SweepPanel
+            schema=schema
+            values=values
+            module=bytes
+            fuel_limit=fuel_limit
+            viewport=viewport
+        />
+        <GridPanel
+            schema=schema
+            values=values
+            module=bytes
+            fuel_limit=fuel_limit
+            viewport=viewport
+            on_open=open_cell
+        />
+        <div node_ref=container_ref class="w-full" style:height=height>
+            <div class="relative inline-block">
+                <img
+                    node_ref=img_ref
+                    class="block max-w-full"
+                    style:max-height=height
+                    on:load=move |_| {
+                        if frame_history.with_untracked(Option::is_some) {
+                            let at = metrics::now();
+                            frame_history
+                                .update(|history| {
+                                    if let Some(history) = history {
+                                        history.loaded(at);
+                                    }
+                                });
+                        }
+                    }
+                />
+                <HandleOverlay
+                    schema=schema
+                    values=values
+                    content=view_box
+                    camera=camera
+                    on_change=on_change
+                />
+                <Show when=move || metrics_shown.get()>
+                    <MetricsOverlay history=Signal::derive(move || {
+                        frame_history.get().unwrap_or_default()
+                    })/>
+                </Show>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,31 @@
+use std::time::Duration;
+
+use leptos::prelude::{document, set_timeout};
+use leptos::web_sys::js_sys::{Array, Uint8Array};
+use leptos::web_sys::{Blob, BlobPropertyBag, HtmlAnchorElement, Url};
+use wasm_bindgen::JsCast;
+
+/// Offer `bytes` to the user as a file download.
+pub fn download(file_name: &str, mime: &str, bytes: &[u8]) -> Result<(), String> {
+    let parts = Array::of1(&Uint8Array::from(bytes));
+    let options = BlobPropertyBag::new();
+    options.set_type(mime);
+    let blob = Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+        .map_err(|e| format!("Failed to create blob: {:?}", e))?;
+    let url = Url::create_object_url_with_blob(&blob)
+        .map_err(|e| format!("Failed to create object URL: {:?}", e))?;
+    let anchor: HtmlAnchorElement = document()
+        .create_element("a")
+        .map_err(|e| format!("Failed to create link: {:?}", e))?
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+    // Some browsers only start the download after the click returns, and
+    // fail it if the URL is gone by then
+    set_timeout(
+        move || _ = Url::revoke_object_url(&url),
+        Duration::from_secs(10),
+    );
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::str::FromStr;
+
+use leptos::prelude::*;
+use rgeometry_host::animation::{AnimationFormat, AnimationOptions};
+use rgeometry_host::export::Format;
+
+/// What the user asked to download.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Export {
+    /// The displayed frame, with the scale of PNGs
+    Frame(Format, f32),
+    /// A time range rendered at fixed times
+    Animation(AnimationFormat, AnimationOptions),
+}
+
+impl Export {
+    fn key(&self) -> &'static str {
+        match self {
+            Export::Frame(format, _) => format.extension(),
+            Export::Animation(AnimationFormat::Gif, _) => "gif",
+            Export::Animation(AnimationFormat::Apng, _) => "apng",
+        }
+    }
+}
+
+// Text field bound to a number. Input that doesn't parse or fails `accept`
+// is ignored.
+pub(crate) fn number_input<T>(
+    title: &'static str,
+    value: RwSignal<T>,
+    accept: fn(&T) -> bool,
+) -> impl IntoView
+where
+    T: FromStr + ToString + Send + Sync + 'static,
+{
+    view! {
+        <input
+            type="number"
+            class="w-16"
+            title=title
+            placeholder=title
+            prop:value=move || value.with(T::to_string)
+            on:change=move |ev| {
+                if let Ok(parsed) = event_target_value(&ev).parse::<T>() {
+                    if accept(&parsed) {
+                        value.set(parsed);
+                    }
+                }
+            }
+        />
+    }
+}
+
+/// Format picker and download button for the displayed frame or, as GIF
+/// and APNG, a time range of the animation. While an animation is exported,
+/// `progress` holds its rendered and total frames, and the button cancels it.
+#[component]
+pub fn ExportMenu(
+    on_export: Callback<Export>,
+    #[prop(into)] progress: Signal<Option<(usize, usize)>>,
+    on_cancel: Callback<()>,
+) -> impl IntoView {
+    let defaults = AnimationOptions::default();
+    let choices: Vec<(&str, String)> = Format::ALL
+        .into_iter()
+        .map(|f| (f.extension(), f.name().to_string()))
+        .chain(
+            AnimationFormat::ALL
+                .into_iter()
+                .map(|f| (Export::Animation(f, defaults).key(), f.name().to_string())),
+        )
+        .collect();
+    let choice = RwSignal::new("svg");
+    let scale = RwSignal::new(2.0f32);
+    let start = RwSignal::new(defaults.start);
+    let end = RwSignal::new(defaults.end);
+    let fps = RwSignal::new(defaults.fps);
+    let width = RwSignal::new(defaults.width);
+    // Empty for forever
+    let loops = RwSignal::new(String::new());
+
+    let export = move || {
+        let options = AnimationOptions {
+            start: start.get_untracked(),
+            end: end.get_untracked(),
+            fps: fps.get_untracked(),
+            width: width.get_untracked(),
+            loops: loops.get_untracked().trim().parse().ok(),
+        };
+        let frame = Format::ALL
+            .into_iter()
+            .map(|f| Export::Frame(f, scale.get_untracked()));
+        let animation = AnimationFormat::ALL
+            .into_iter()
+            .map(|f| Export::Animation(f, options));
+        frame
+            .chain(animation)
+            .find(|export| export.key() == choice.get_untracked())
+    };
+    let is_animation = move || matches!(choice.get(), "gif" | "apng");
+
+    view! {
+        <div class="flex items-center gap-2">
+            <select on:change=move |ev| {
+                let value = event_target_value(&ev);
+                if let Some((key, _)) = choices.iter().find(|(key, _)| *key == value) {
+                    choice.set(key);
+                }
+            }>
+                {choices
+                    .clone()
+                    .into_iter()
+                    .map(|(key, label)| {
+                        view! {
+                            <option value=key selected=move || choice.get() == key>
+                                {label}
+                            </option>
+                        }
+                    })
+                    .collect_view()}
+            </select>
+            <Show when=move || choice.get() == "png">
+                {number_input("Pixels per unit", scale, |v| *v > 0.0)}
+                "x"
+            </Show>
+            <Show when=is_animation>
+                {number_input("Start", start, |v| *v >= 0.0)}
+                "-"
+                {number_input("End", end, |v| *v > 0.0)}
+                "s at"
+                {number_input("Frames per second", fps, |v| *v > 0.0)}
+                "fps,"
+                <Show when=move || {
+                    choice.get() == "gif" && fps.get() > AnimationFormat::Gif.max_fps()
+                }>
+                    <span class="text-red-700">
+                        {format!("GIFs play at most {} fps,", AnimationFormat::Gif.max_fps())}
+                    </span>
+                </Show>
+                {number_input("Width", width, |v| *v > 0)}
+                "px, plays"
+                <input
+                    type="number"
+                    class="w-16"
+                    min="1"
+                    placeholder="forever"
+                    title="How often the animation plays"
+                    prop:value=move || loops.get()
+                    on:change=move |ev| loops.set(event_target_value(&ev))
+                />
+            </Show>
+            <Show
+                when=move || progress.get().is_some()
+                fallback=move || {
+                    view! {
+                        <button
+                            class="px-2 border rounded bg-white"
+                            on:click=move |_| {
+                                if let Some(export) = export() {
+                                    on_export.run(export);
+                                }
+                            }
+                        >
+                            "Download"
+                        </button>
+                    }
+                }
+            >
+                {move || {
+                    progress
+                        .get()
+                        .map(|(done, total)| {
+                            view! {
+                                <progress max=total value=done></progress>
+                                {format!("{}/{} frames", done, total)}
+                            }
+                        })
+                }}
+                <button class="px-2 border rounded bg-white" on:click=move |_| on_cancel.run(())>
+                    "Cancel"
+                </button>
+            </Show>
+        </div>
+    }
+}
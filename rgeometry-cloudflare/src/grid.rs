@@ -0,0 +1,479 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::app::svg_data_url;
+use crate::metrics;
+use crate::worker::{GuestState, GuestWorker, Response};
+use leptos::prelude::*;
+use rgeometry_host::grid::{Grid, GridAxis};
+use rgeometry_host::wasm::{Preset, Schema, Value, Viewport};
+
+// A rendered cell: its values by parameter name, labelled, and the frame or
+// why it failed
+type Cell = (Preset, Result<String, String>);
+
+// The cells of a grid left to render, and how far the worker got
+#[derive(Default)]
+struct GridRun {
+    // Values of the other parameters, and the cells by schema index and as
+    // presets
+    values: HashMap<usize, Value>,
+    pending: VecDeque<(Vec<(usize, Value)>, Preset)>,
+    // The cell being rendered, and why it failed if it did
+    current: Option<(Preset, Option<String>)>,
+    // Whether the module opened, and whether it has to be opened again
+    // because a cell failed it
+    opened: bool,
+    reopen: bool,
+    // Why the module failed to open, which ends the run
+    failure: Option<String>,
+}
+
+// What to ask the worker next
+enum GridStep {
+    Open,
+    Wait,
+    // Render the current cell, after setting its values if it is new
+    Render(Option<GuestState>),
+    Done,
+}
+
+impl GridRun {
+    fn step(&mut self) -> GridStep {
+        if self.failure.is_some() {
+            return GridStep::Done;
+        }
+        if std::mem::take(&mut self.reopen) {
+            self.opened = false;
+            return GridStep::Open;
+        }
+        if !self.opened {
+            return GridStep::Wait;
+        }
+        if self.current.is_some() {
+            return GridStep::Render(None);
+        }
+        let Some((cell, preset)) = self.pending.pop_front() else {
+            return GridStep::Done;
+        };
+        // Restoring resets the values the last cell set
+        let mut values = self.values.clone();
+        values.extend(cell);
+        self.current = Some((preset, None));
+        GridStep::Render(Some(GuestState {
+            time: 0.0,
+            paused: true,
+            values: values.into_iter().collect(),
+        }))
+    }
+
+    // Take in a response of the worker, and return the cell it finished
+    fn answer(&mut self, response: Response) -> Option<Cell> {
+        match response {
+            Response::Opened { .. } => {
+                self.opened = true;
+                None
+            }
+            Response::Error(err) if !self.opened => {
+                self.failure = Some(err);
+                None
+            }
+            Response::Rejected(_, err) => {
+                if let Some((_, failure)) = &mut self.current {
+                    failure.get_or_insert(err);
+                }
+                None
+            }
+            // The module failed, or missed the deadline and was restarted, so
+            // it is opened again for the next cell. A frame that follows is
+            // the last one before the failure.
+            Response::OutOfFuel(_) | Response::Error(_) => {
+                self.reopen = true;
+                let err = match response {
+                    Response::OutOfFuel(limit) => {
+                        format!("The cell consumed all of its {} fuel", limit)
+                    }
+                    Response::Error(err) => err,
+                    _ => unreachable!(),
+                };
+                let (preset, _) = self.current.take()?;
+                Some((preset, Err(err)))
+            }
+            Response::Frame { svg, .. } => {
+                let (preset, failure) = self.current.take()?;
+                Some((preset, failure.map_or(Ok(svg), Err)))
+            }
+            Response::Ready | Response::AnimationFrame(..) | Response::Measured(_) => None,
+        }
+    }
+}
+
+// A grid run and where it reports to. Starting or stopping a run moves
+// `current` past its `id`, so it stops asking for cells.
+#[derive(Clone, Copy)]
+struct GridJob {
+    id: u64,
+    current: StoredValue<u64>,
+    module: StoredValue<Vec<u8>>,
+    guest: StoredValue<Option<GuestWorker>, LocalStorage>,
+    run: StoredValue<GridRun>,
+    viewport: Viewport,
+    running: RwSignal<bool>,
+    error: RwSignal<Option<String>>,
+}
+
+// Ask the grid's worker for what the run needs next, once per browser frame,
+// until every cell is rendered. The worker renders the cells, so the page
+// stays responsive and a cell that takes too long is given up on.
+fn grid_step(job: GridJob) {
+    if job.current.try_get_value() != Some(job.id) {
+        return;
+    }
+    let Some(step) = job.run.try_update_value(GridRun::step) else {
+        return;
+    };
+    if let GridStep::Done = step {
+        job.error
+            .set(job.run.try_update_value(|run| run.failure.take()).flatten());
+        job.running.set(false);
+        job.guest.set_value(None);
+        return;
+    }
+    job.guest.update_value(|guest| {
+        let Some(guest) = guest.as_mut() else {
+            return;
+        };
+        match step {
+            GridStep::Open => job.module.with_value(|bytes| guest.open(bytes)),
+            GridStep::Render(state) => {
+                if let Some(state) = state {
+                    guest.restore(state);
+                }
+                guest.request_frame(metrics::now(), job.viewport, None, Some(0.0));
+            }
+            GridStep::Wait | GridStep::Done => {}
+        }
+    });
+    request_animation_frame(move || grid_step(job));
+}
+
+// Picker of the parameter on one axis, and the text field of its values.
+// `optional` adds a choice for no parameter.
+fn axis_input(
+    title: &'static str,
+    candidates: Memo<Vec<(usize, String)>>,
+    parameter: RwSignal<Option<usize>>,
+    text: RwSignal<String>,
+    optional: bool,
+    pick: impl Fn(Option<usize>, RwSignal<Option<usize>>, RwSignal<String>) + Copy + 'static,
+) -> impl IntoView {
+    view! {
+        <label>{title}</label>
+        <select on:change=move |ev| pick(event_target_value(&ev).parse().ok(), parameter, text)>
+            {optional.then(|| view! { <option value="" selected=move || parameter.get().is_none()>"None"</option> })}
+            {move || {
+                candidates
+                    .get()
+                    .into_iter()
+                    .map(|(i, label)| {
+                        view! {
+                            <option value=i selected=move || parameter.get() == Some(i)>
+                                {label}
+                            </option>
+                        }
+                    })
+                    .collect_view()
+            }}
+        </select>
+        <input
+            type="text"
+            class="w-48"
+            title="Values, separated by commas"
+            disabled=move || parameter.get().is_none()
+            prop:value=move || text.get()
+            on:change=move |ev| text.set(event_target_value(&ev))
+        />
+    }
+}
+
+/// Renders the module for every combination of values of one or two
+/// parameters, such as a point count across columns and a seed across rows,
+/// while the other parameters keep their `values`. The cells are rendered one
+/// after another at time 0, in the size of the view, by one instance in a
+/// worker of its own, whose values are reset between cells. Frames are
+/// stopped after `fuel_limit` fuel or `FRAME_DEADLINE`, and the module opened
+/// again for the next cell. Clicking a cell passes its values, by parameter
+/// name, to `on_open`.
+#[component]
+pub fn GridPanel(
+    #[prop(into)] schema: Signal<Schema>,
+    #[prop(into)] values: Signal<HashMap<usize, Value>>,
+    module: StoredValue<Vec<u8>>,
+    #[prop(into)] fuel_limit: Signal<Option<u64>>,
+    viewport: StoredValue<Viewport>,
+    on_open: Callback<Preset>,
+) -> impl IntoView {
+    // The parameters that can be put on an axis, with their labels
+    let candidates = Memo::new(move |_| {
+        schema.with(|schema| {
+            schema
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| GridAxis::accepts(schema, *i))
+                .map(|(i, param)| (i, param.display_label(i)))
+                .collect::<Vec<_>>()
+        })
+    });
+    let columns = RwSignal::new(None::<usize>);
+    let column_values = RwSignal::new(String::new());
+    let rows = RwSignal::new(None::<usize>);
+    let row_values = RwSignal::new(String::new());
+    // Number of columns of the rendered grid, and its cells so far
+    let width = RwSignal::new(1usize);
+    let cells = RwSignal::new(Vec::<Cell>::new());
+    let running = RwSignal::new(false);
+    let error = RwSignal::new(None::<String>);
+    // The worker rendering the grid, and the cells it has left
+    let guest = StoredValue::new_local(None::<GuestWorker>);
+    let grid_run = StoredValue::new(GridRun::default());
+    // Number of the last run started or stopped
+    let generation = StoredValue::new(0u64);
+    // Stopping ends the worker, and with it a cell that takes too long
+    let stop = move || {
+        generation.update_value(|id| *id += 1);
+        running.set(false);
+        guest.set_value(None);
+    };
+    let on_response = move |response: Response| {
+        if let Some(Some(cell)) = grid_run.try_update_value(|run| run.answer(response)) {
+            cells.update(|cells| cells.push(cell));
+        }
+    };
+
+    // Fill in the default values of the picked parameter
+    let pick =
+        move |index: Option<usize>, parameter: RwSignal<Option<usize>>, text: RwSignal<String>| {
+            parameter.set(index);
+            let defaults = index.and_then(|index| {
+                schema.with_untracked(|schema| {
+                    GridAxis::new(schema, index)
+                        .ok()
+                        .map(|axis| axis.format(schema))
+                })
+            });
+            text.set(defaults.unwrap_or_default());
+        };
+    Effect::new(move |_| {
+        let first = candidates.with(|candidates| candidates.first().map(|(i, _)| *i));
+        stop();
+        cells.set(Vec::new());
+        pick(first, columns, column_values);
+        pick(None, rows, row_values);
+    });
+
+    let run = move |_| {
+        let Some(column) = columns.get_untracked() else {
+            return;
+        };
+        // Every cell is labelled with its own values only
+        let parsed = schema.with_untracked(|schema| {
+            let columns = GridAxis::parse(schema, column, &column_values.get_untracked())?;
+            let rows = rows
+                .get_untracked()
+                .map(|row| GridAxis::parse(schema, row, &row_values.get_untracked()))
+                .transpose()?;
+            let grid = Grid { columns, rows };
+            grid.validate(schema)?;
+            let pending: VecDeque<_> = (0..grid.len())
+                .map(|index| (grid.cell(index), grid.preset(schema, index)))
+                .collect();
+            Ok::<_, String>((grid.width(), pending))
+        });
+        let (grid_width, pending) = match parsed {
+            Ok(parsed) => parsed,
+            Err(err) => {
+                error.set(Some(err));
+                return;
+            }
+        };
+        let mut worker = match GuestWorker::new(on_response) {
+            Ok(worker) => worker,
+            Err(err) => {
+                error.set(Some(err));
+                return;
+            }
+        };
+        worker.set_fuel_limit(fuel_limit.get_untracked());
+        stop();
+        guest.set_value(Some(worker));
+        grid_run.set_value(GridRun {
+            values: values.get_untracked(),
+            pending,
+            reopen: true,
+            ..GridRun::default()
+        });
+        error.set(None);
+        width.set(grid_width);
+        cells.set(Vec::new());
+        running.set(true);
+        grid_step(GridJob {
+            id: generation.get_value(),
+            current: generation,
+            module,
+            guest,
+            run: grid_run,
+            viewport: viewport.get_value(),
+            running,
+            error,
+        });
+    };
+
+    view! {
+        <Show when=move || candidates.with(|candidates| !candidates.is_empty())>
+            <details class="mb-4">
+                <summary class="cursor-pointer">"Parameter grid"</summary>
+                <div class="flex items-center gap-2 mt-2">
+                    {axis_input("Columns", candidates, columns, column_values, false, pick)}
+                    {axis_input("Rows", candidates, rows, row_values, true, pick)}
+                    <Show
+                        when=move || running.get()
+                        fallback=move || {
+                            view! {
+                                <button class="px-2 border rounded bg-white" on:click=run>
+                                    "Render"
+                                </button>
+                            }
+                        }
+                    >
+                        <button
+                            class="px-2 border rounded bg-white"
+                            on:click=move |_| stop()
+                        >
+                            "Stop"
+                        </button>
+                    </Show>
+                </div>
+                {move || error.get().map(|err| view! { <p class="text-red-500">{err}</p> })}
+                <div
+                    class="grid gap-2 mt-2"
+                    style:grid-template-columns=move || {
+                        format!("repeat({}, minmax(0, 1fr))", width.get())
+                    }
+                >
+                    {move || {
+                        cells
+                            .get()
+                            .into_iter()
+                            .map(|(cell, frame)| {
+                                let label = cell.name.clone();
+                                let frame = match frame {
+                                    Ok(svg) => {
+                                        view! {
+                                            <img class="w-full bg-white" src=svg_data_url(&svg)/>
+                                        }
+                                            .into_any()
+                                    }
+                                    Err(err) => {
+                                        view! {
+                                            <p class="w-full bg-red-100 text-red-500 p-2">{err}</p>
+                                        }
+                                            .into_any()
+                                    }
+                                };
+                                view! {
+                                    <button
+                                        class="text-left border rounded bg-white p-1 hover:border-sky-500"
+                                        title="Open in the viewer"
+                                        on:click=move |_| on_open.run(cell.clone())
+                                    >
+                                        {frame}
+                                        <span class="text-xs">{label}</span>
+                                    </button>
+                                }
+                            })
+                            .collect_view()
+                    }}
+                </div>
+            </details>
+        </Show>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str) -> Preset {
+        Preset {
+            name: name.to_string(),
+            values: Default::default(),
+        }
+    }
+
+    fn opened() -> Response {
+        Response::Opened {
+            hash: String::new(),
+            schema: Schema::new(),
+            meta: Default::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    fn frame() -> Response {
+        Response::Frame {
+            svg: "<svg/>".to_string(),
+            time: 0.0,
+            metrics: Default::default(),
+            animated: false,
+        }
+    }
+
+    #[test]
+    fn run_steps() {
+        let mut run = GridRun {
+            values: HashMap::from([(0, Value::I32(1))]),
+            pending: VecDeque::from([
+                (vec![(1, Value::I32(2))], preset("a")),
+                (vec![(1, Value::I32(3))], preset("b")),
+            ]),
+            reopen: true,
+            ..GridRun::default()
+        };
+        assert!(matches!(run.step(), GridStep::Open));
+        assert!(matches!(run.step(), GridStep::Wait));
+        assert_eq!(run.answer(opened()), None);
+        match run.step() {
+            GridStep::Render(Some(mut state)) => {
+                state.values.sort_by_key(|(index, _)| *index);
+                assert_eq!(state.values, [(0, Value::I32(1)), (1, Value::I32(2))]);
+            }
+            _ => panic!("Expected the first cell"),
+        }
+        assert!(matches!(run.step(), GridStep::Render(None)));
+        assert_eq!(run.answer(Response::Rejected(1, "No".to_string())), None);
+        assert_eq!(
+            run.answer(frame()),
+            Some((preset("a"), Err("No".to_string())))
+        );
+
+        // A cell that fails the module opens it again
+        assert!(matches!(run.step(), GridStep::Render(Some(_))));
+        assert_eq!(
+            run.answer(Response::OutOfFuel(5)),
+            Some((
+                preset("b"),
+                Err("The cell consumed all of its 5 fuel".to_string())
+            ))
+        );
+        assert_eq!(run.answer(frame()), None);
+        assert!(matches!(run.step(), GridStep::Open));
+        run.answer(opened());
+        assert!(matches!(run.step(), GridStep::Done));
+
+        // A module that doesn't open ends the run
+        run.reopen = true;
+        run.step();
+        run.answer(Response::Error("Broken".to_string()));
+        assert!(matches!(run.step(), GridStep::Done));
+        assert_eq!(run.failure.as_deref(), Some("Broken"));
+    }
+}
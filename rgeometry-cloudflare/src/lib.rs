@@ -1,5 +1,17 @@
 mod app;
-mod wasm;
+mod compare;
+mod controls;
+mod demo;
+mod download;
+mod export;
+mod grid;
+mod metrics;
+mod overlay;
+mod presets;
+mod query;
+mod storage;
+mod sweep;
+mod worker;
 
 #[cfg(feature = "hydrate")]
 #[wasm_bindgen::prelude::wasm_bindgen]
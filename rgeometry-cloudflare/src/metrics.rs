@@ -0,0 +1,234 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use leptos::prelude::*;
+use rgeometry_host::wasm::FrameMetrics;
+
+/// Number of frames the overlay keeps and graphs.
+const HISTORY_LEN: usize = 120;
+
+// How often the overlay is redrawn, in milliseconds. Frames can arrive far
+// more often than the numbers can be read.
+const REFRESH_INTERVAL: u64 = 250;
+
+// Size of the graphs, in CSS pixels
+const GRAPH_WIDTH: f64 = 160.0;
+const GRAPH_HEIGHT: f64 = 32.0;
+
+/// Milliseconds since the page loaded.
+pub fn now() -> f64 {
+    window().performance().map_or(0.0, |p| p.now())
+}
+
+/// Where the time of one displayed frame went.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameSample {
+    /// When the frame was rendered, in milliseconds
+    pub at: f64,
+    /// Measured by the runtime
+    pub frame: FrameMetrics,
+    /// Time spent turning the SVG into the image's data URL, in milliseconds
+    pub svg_time: f64,
+    /// When the image's source was set
+    pub shown_at: f64,
+    /// Time from setting the image's source until it loaded, which includes
+    /// the browser decoding the SVG. None until the image has loaded.
+    pub decode_time: Option<f64>,
+}
+
+/// The last frames that were displayed, oldest first.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    samples: VecDeque<FrameSample>,
+}
+
+impl History {
+    pub fn push(&mut self, sample: FrameSample) {
+        if self.samples.len() == HISTORY_LEN {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    /// Record that the image of the last frame loaded at `at`.
+    pub fn loaded(&mut self, at: f64) {
+        if let Some(last) = self.samples.back_mut() {
+            last.decode_time.get_or_insert(at - last.shown_at);
+        }
+    }
+
+    /// Frames per second over the kept frames.
+    pub fn fps(&self) -> Option<f64> {
+        let first = self.samples.front()?;
+        let last = self.samples.back()?;
+        let elapsed = last.at - first.at;
+        (elapsed > 0.0).then(|| (self.samples.len() - 1) as f64 * 1000.0 / elapsed)
+    }
+
+    /// Time between consecutive frames, in milliseconds.
+    pub fn frame_times(&self) -> Vec<f64> {
+        let times = self.samples.iter().map(|s| s.at);
+        times
+            .clone()
+            .skip(1)
+            .zip(times)
+            .map(|(b, a)| b - a)
+            .collect()
+    }
+
+    pub fn series(&self, value: impl Fn(&FrameSample) -> f64) -> Vec<f64> {
+        self.samples.iter().map(value).collect()
+    }
+
+    pub fn last(&self) -> Option<&FrameSample> {
+        self.samples.back()
+    }
+}
+
+/// Points of a polyline graphing `values` between 0 and their maximum, the
+/// last value at the right edge.
+fn sparkline(values: &[f64], width: f64, height: f64) -> String {
+    let max = values.iter().copied().fold(0.0, f64::max);
+    let step = width / (HISTORY_LEN - 1) as f64;
+    let offset = width - step * values.len().saturating_sub(1) as f64;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let y = if max > 0.0 {
+                height * (1.0 - value / max)
+            } else {
+                height
+            };
+            format!("{:.1},{:.1}", offset + i as f64 * step, y)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn graph(label: String, values: Vec<f64>, color: &'static str) -> impl IntoView {
+    let points = sparkline(&values, GRAPH_WIDTH, GRAPH_HEIGHT);
+    view! {
+        <div>{label}</div>
+        <svg
+            width=GRAPH_WIDTH
+            height=GRAPH_HEIGHT
+            class="bg-black/40"
+        >
+            <polyline points=points fill="none" stroke=color stroke-width="1"/>
+        </svg>
+    }
+}
+
+fn kilobytes(bytes: usize) -> String {
+    format!("{:.1} KB", bytes as f64 / 1024.0)
+}
+
+/// Frame rate and where frames spend their time: in the module, building the
+/// image's data URL, or in the browser loading the image. Graphs the time
+/// between frames, the module's time and its memory over the last frames.
+/// Redrawn a few times a second rather than for every frame.
+#[component]
+pub fn MetricsOverlay(#[prop(into)] history: Signal<History>) -> impl IntoView {
+    // The history as last drawn
+    let shown = RwSignal::new(history.get_untracked());
+    let interval = set_interval_with_handle(
+        move || {
+            let latest = history.get_untracked();
+            if shown.with_untracked(|shown| *shown != latest) {
+                shown.set(latest);
+            }
+        },
+        Duration::from_millis(REFRESH_INTERVAL),
+    );
+    if let Ok(interval) = interval {
+        on_cleanup(move || interval.clear());
+    }
+    move || {
+        shown.with(|history| {
+            let last = history.last().copied().unwrap_or_default();
+            let fps = history
+                .fps()
+                .map_or("-".to_string(), |fps| format!("{:.0}", fps));
+            let decode = last
+                .decode_time
+                .map_or("-".to_string(), |ms| format!("{:.1} ms", ms));
+            let fuel = last
+                .frame
+                .fuel
+                .map(|fuel| view! { <div>"Fuel: " {fuel}</div> });
+            let memory = last.frame.memory_bytes;
+            view! {
+                <div class="absolute top-0 right-0 m-1 p-1 text-xs font-mono text-white bg-black/60 pointer-events-none">
+                    <div>{format!("{} fps", fps)}</div>
+                    <div>{format!("Module: {:.1} ms", last.frame.guest_time)}</div>
+                    <div>{format!("SVG: {} in {:.1} ms", kilobytes(last.frame.svg_bytes), last.svg_time)}</div>
+                    <div>{format!("Image load: {}", decode)}</div>
+                    {fuel}
+                    {graph("Frame time".to_string(), history.frame_times(), "lime")}
+                    {graph(
+                        "Module time".to_string(),
+                        history.series(|s| s.frame.guest_time),
+                        "orange",
+                    )}
+                    {graph(
+                        format!(
+                            "Memory: {} (+{})",
+                            kilobytes(memory),
+                            kilobytes(last.frame.memory_growth),
+                        ),
+                        history.series(|s| s.frame.memory_bytes as f64),
+                        "cyan",
+                    )}
+                </div>
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at: f64) -> FrameSample {
+        FrameSample {
+            at,
+            ..FrameSample::default()
+        }
+    }
+
+    #[test]
+    fn history() {
+        let mut history = History::default();
+        assert_eq!(history.fps(), None);
+        for i in 0..=HISTORY_LEN {
+            history.push(sample(i as f64 * 20.0));
+        }
+        // The oldest frame was dropped
+        assert_eq!(history.series(|s| s.at).len(), HISTORY_LEN);
+        assert_eq!(history.fps(), Some(50.0));
+        assert!(history.frame_times().iter().all(|t| *t == 20.0));
+
+        history.push(FrameSample {
+            shown_at: 5000.0,
+            ..sample(4999.0)
+        });
+        history.loaded(5003.0);
+        history.loaded(5004.0);
+        assert_eq!(history.last().unwrap().decode_time, Some(3.0));
+    }
+
+    #[test]
+    fn sparkline_points() {
+        let step = 100.0 / (HISTORY_LEN - 1) as f64;
+        assert_eq!(
+            sparkline(&[0.0, 2.0, 1.0], 100.0, 10.0),
+            format!(
+                "{:.1},10.0 {:.1},0.0 100.0,5.0",
+                100.0 - 2.0 * step,
+                100.0 - step
+            )
+        );
+        assert_eq!(sparkline(&[], 100.0, 10.0), "");
+    }
+}
@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use leptos::{
+    ev::{PointerEvent, WheelEvent},
+    prelude::*,
+};
+use rgeometry_host::viewbox::ViewBox;
+use rgeometry_host::wasm::{is_visible, Schema, SchemaType, Value};
+
+// How far the camera may zoom in and out, relative to the frame's own view box
+const MAX_ZOOM: f64 = 1e5;
+const MIN_ZOOM: f64 = 0.1;
+
+/// The points of one `point2d` or `point_set` schema entry.
+#[derive(Debug, Clone)]
+struct Handles {
+    index: usize,
+    points: Vec<[f32; 2]>,
+    bounds: Option<[f32; 4]>,
+    // Point sets can grow and shrink, a point2d is always a single point
+    is_set: bool,
+}
+
+impl Handles {
+    fn value(&self) -> Value {
+        if self.is_set {
+            Value::PointSet(self.points.clone())
+        } else {
+            Value::Point2d(self.points[0])
+        }
+    }
+
+    fn clamp(&self, [x, y]: [f32; 2]) -> [f32; 2] {
+        match self.bounds {
+            Some([min_x, min_y, max_x, max_y]) => [x.clamp(min_x, max_x), y.clamp(min_y, max_y)],
+            None => [x, y],
+        }
+    }
+}
+
+/// Draggable handles for the `point2d` and `point_set` entries of a schema,
+/// drawn on top of the rendered frame in the frame's own coordinate system.
+/// Clicking an empty spot adds a point to the first point set, and
+/// right-clicking a point of a set removes it. Handles start out at `values`
+/// and are hidden along with their entry's control.
+///
+/// The overlay also moves the `camera`, the region of the frame that is
+/// shown, or None to show the frame's own view box (`content`). The wheel
+/// zooms, and dragging the background with the middle button, or with the
+/// left one when there is no point set to add to, pans. On touch screens two
+/// fingers pan and pinch.
+#[component]
+pub fn HandleOverlay(
+    #[prop(into)] schema: Signal<Schema>,
+    #[prop(into)] values: Signal<HashMap<usize, Value>>,
+    #[prop(into)] content: Signal<Option<ViewBox>>,
+    camera: RwSignal<Option<ViewBox>>,
+    on_change: Callback<(usize, Value)>,
+) -> impl IntoView {
+    let view_box = Signal::derive(move || camera.get().or_else(|| content.get()));
+    let groups = RwSignal::new(Vec::<Handles>::new());
+    Effect::new(move |_| {
+        let initial = values.get_untracked();
+        groups.set(
+            schema
+                .get()
+                .into_iter()
+                .enumerate()
+                .filter_map(|(index, param)| match (param.ty, initial.get(&index)) {
+                    (SchemaType::Point2d { default, bounds }, value) => Some(Handles {
+                        index,
+                        points: match value {
+                            Some(Value::Point2d(point)) => vec![*point],
+                            _ => vec![default],
+                        },
+                        bounds,
+                        is_set: false,
+                    }),
+                    (SchemaType::PointSet { default, bounds }, value) => Some(Handles {
+                        index,
+                        points: match value {
+                            Some(Value::PointSet(points)) => points.clone(),
+                            _ => default,
+                        },
+                        bounds,
+                        is_set: true,
+                    }),
+                    _ => None,
+                })
+                .collect(),
+        )
+    });
+    let visible = move |index: usize| {
+        schema.with(|schema| values.with(|values| is_visible(schema, index, values)))
+    };
+    // (position in `groups`, position in its points) of the handle being dragged
+    let dragging = RwSignal::new(None::<(usize, usize)>);
+    // Client positions of the captured pointers, by pointer id. They drag a
+    // handle if `dragging` is set and pan the camera otherwise.
+    let pointers = StoredValue::new(Vec::<(i32, [f64; 2])>::new());
+    let svg_ref = NodeRef::new();
+
+    // [left, top, width, height] of the overlay in client coordinates
+    let client_rect = move || -> Option<[f64; 4]> {
+        let svg: leptos::web_sys::SvgElement = svg_ref.get_untracked()?;
+        let rect = svg.get_bounding_client_rect();
+        Some([rect.left(), rect.top(), rect.width(), rect.height()])
+    };
+    let client_point = |ev: &PointerEvent| [ev.client_x() as f64, ev.client_y() as f64];
+    // Pointer position in user coordinates, narrowed to the f32 of points
+    let user_point = move |ev: &PointerEvent| -> Option<[f32; 2]> {
+        let view_box = view_box.get_untracked()?;
+        let point = view_box.client_to_user(client_rect()?, client_point(ev));
+        Some(point.map(|v| v as f32))
+    };
+    // Move the camera unless that zooms in or out too far
+    let set_camera = move |new: ViewBox| {
+        let Some(content) = content.get_untracked() else {
+            return;
+        };
+        let zoom = content.width / new.width;
+        if (MIN_ZOOM..=MAX_ZOOM).contains(&zoom) {
+            camera.set(Some(new));
+        }
+    };
+    let capture = move |ev: &PointerEvent| {
+        if let Some(svg) = svg_ref.get_untracked() {
+            let svg: leptos::web_sys::SvgElement = svg;
+            _ = svg.set_pointer_capture(ev.pointer_id());
+        }
+        pointers.update_value(|pointers| pointers.push((ev.pointer_id(), client_point(ev))));
+    };
+    let emit = move |group: usize| {
+        if let Some(handles) = groups.with_untracked(|groups| groups.get(group).cloned()) {
+            on_change.run((handles.index, handles.value()));
+        }
+    };
+
+    let on_pointer_move = move |ev: PointerEvent| {
+        let id = ev.pointer_id();
+        if !pointers.with_value(|pointers| pointers.iter().any(|(i, _)| *i == id)) {
+            return;
+        }
+        let old = pointers.get_value();
+        pointers.update_value(|pointers| {
+            for (i, position) in pointers.iter_mut() {
+                if *i == id {
+                    *position = client_point(&ev);
+                }
+            }
+        });
+        if dragging.get_untracked().is_none() {
+            let (Some(view_box), Some(rect)) = (view_box.get_untracked(), client_rect()) else {
+                return;
+            };
+            let new = pointers.get_value();
+            // Midpoint and spread of the pointers before and after the move
+            let center = |pointers: &[(i32, [f64; 2])]| {
+                let n = pointers.len() as f64;
+                let [x, y] = pointers
+                    .iter()
+                    .fold([0.0, 0.0], |[x, y], (_, [px, py])| [x + px, y + py]);
+                [x / n, y / n]
+            };
+            let spread = |pointers: &[(i32, [f64; 2])]| match pointers {
+                [(_, [x0, y0]), (_, [x1, y1]), ..] => (x1 - x0).hypot(y1 - y0),
+                _ => 0.0,
+            };
+            let anchor = view_box.client_to_user(rect, center(&old));
+            let zoomed = match (spread(&old), spread(&new)) {
+                (old, new) if old > 0.0 && new > 0.0 => view_box.zoom_at(anchor, old / new),
+                _ => view_box,
+            };
+            // Keep the point under the midpoint under it
+            let [x, y] = zoomed.client_to_user(rect, center(&new));
+            set_camera(zoomed.translate([anchor[0] - x, anchor[1] - y]));
+            return;
+        }
+        let Some((group, point)) = dragging.get_untracked() else {
+            return;
+        };
+        let Some(position) = user_point(&ev) else {
+            return;
+        };
+        groups.update(|groups| {
+            let handles = &mut groups[group];
+            handles.points[point] = handles.clamp(position);
+        });
+        emit(group);
+    };
+
+    // Pressing outside every handle adds a point to the first point set and
+    // starts dragging it, or pans if there is no point set. A second finger
+    // always pans and pinches.
+    let on_pointer_down = move |ev: PointerEvent| {
+        let Some(position) = user_point(&ev) else {
+            return;
+        };
+        let group = groups.with_untracked(|groups| {
+            groups
+                .iter()
+                .position(|h| h.is_set && untrack(|| visible(h.index)))
+        });
+        let second_touch = ev.pointer_type() == "touch" && !ev.is_primary();
+        if ev.button() == 1 || (ev.button() == 0 && group.is_none()) || second_touch {
+            ev.prevent_default();
+            dragging.set(None);
+            capture(&ev);
+            return;
+        }
+        let (0, Some(group)) = (ev.button(), group) else {
+            return;
+        };
+        let point = groups.try_update(|groups| {
+            let handles = &mut groups[group];
+            handles.points.push(handles.clamp(position));
+            handles.points.len() - 1
+        });
+        dragging.set(point.map(|point| (group, point)));
+        capture(&ev);
+        emit(group);
+    };
+
+    let on_pointer_up = move |ev: PointerEvent| {
+        let id = ev.pointer_id();
+        pointers.update_value(|pointers| pointers.retain(|(i, _)| *i != id));
+        dragging.set(None);
+    };
+
+    let on_wheel = move |ev: WheelEvent| {
+        let (Some(view_box), Some(rect)) = (view_box.get_untracked(), client_rect()) else {
+            return;
+        };
+        ev.prevent_default();
+        // Deltas may be given in lines or pages rather than pixels
+        let pixels = match ev.delta_mode() {
+            0 => ev.delta_y(),
+            1 => ev.delta_y() * 16.0,
+            _ => ev.delta_y() * 400.0,
+        };
+        let anchor = view_box.client_to_user(rect, [ev.client_x() as f64, ev.client_y() as f64]);
+        set_camera(view_box.zoom_at(anchor, (pixels * 0.002).exp()));
+    };
+
+    view! {
+        <svg
+            node_ref=svg_ref
+            class="absolute inset-0 w-full h-full touch-none"
+            viewBox=move || view_box.get().map(|view_box| view_box.to_string())
+            on:pointerdown=on_pointer_down
+            on:pointermove=on_pointer_move
+            on:pointerup=on_pointer_up
+            on:pointercancel=on_pointer_up
+            on:wheel=on_wheel
+        >
+            {move || {
+                let radius = view_box
+                    .get()
+                    .map_or(1.0, |view_box| view_box.width.max(view_box.height) * 0.015);
+                groups
+                    .get()
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, handles)| visible(handles.index))
+                    .flat_map(|(group, handles)| {
+                        let is_set = handles.is_set;
+                        handles
+                            .points
+                            .into_iter()
+                            .enumerate()
+                            .map(move |(point, [x, y])| {
+                                view! {
+                                    <circle
+                                        cx=x.to_string()
+                                        cy=y.to_string()
+                                        r=radius.to_string()
+                                        fill=if is_set { "black" } else { "white" }
+                                        fill-opacity="0.6"
+                                        stroke=if is_set { "white" } else { "black" }
+                                        stroke-width="2"
+                                        vector-effect="non-scaling-stroke"
+                                        class="cursor-move"
+                                        on:pointerdown=move |ev| {
+                                            ev.stop_propagation();
+                                            if ev.button() == 0 {
+                                                dragging.set(Some((group, point)));
+                                                capture(&ev);
+                                            }
+                                        }
+                                        on:contextmenu=move |ev| {
+                                            ev.prevent_default();
+                                            if is_set {
+                                                dragging.set(None);
+                                                groups.update(|groups| {
+                                                    groups[group].points.remove(point);
+                                                });
+                                                emit(group);
+                                            }
+                                        }
+                                    />
+                                }
+                            })
+                    })
+                    .collect_view()
+            }}
+        </svg>
+    }
+}
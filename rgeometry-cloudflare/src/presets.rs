@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use crate::download::download;
+use leptos::prelude::*;
+use rfd::AsyncFileDialog;
+use rgeometry_host::wasm::{validate_presets, Preset, Schema, Value};
+
+// Add presets, replacing those with the same name
+fn merge(presets: &mut Vec<Preset>, new: Vec<Preset>) {
+    for preset in new {
+        match presets.iter_mut().find(|p| p.name == preset.name) {
+            Some(existing) => *existing = preset,
+            None => presets.push(preset),
+        }
+    }
+}
+
+/// Picker for the presets shipped in the module metadata (`builtin`) and
+/// those saved by the user. Picking a preset applies it. The current values
+/// can be saved as a user preset, and user presets can be exported to and
+/// imported from JSON files.
+#[component]
+pub fn PresetPanel(
+    #[prop(into)] schema: Signal<Schema>,
+    #[prop(into)] values: Signal<HashMap<usize, Value>>,
+    #[prop(into)] builtin: Signal<Vec<Preset>>,
+    user: RwSignal<Vec<Preset>>,
+    on_apply: Callback<Vec<(usize, Value)>>,
+) -> impl IntoView {
+    // Name of the picked user preset, if any
+    let selected = RwSignal::new(None::<String>);
+    let name = RwSignal::new(String::new());
+
+    let on_pick = move |ev| {
+        let key = event_target_value(&ev);
+        let preset = match key.split_once(':') {
+            Some(("builtin", i)) => {
+                selected.set(None);
+                i.parse()
+                    .ok()
+                    .and_then(|i: usize| builtin.with_untracked(|p| p.get(i).cloned()))
+            }
+            Some(("user", i)) => {
+                let preset = i
+                    .parse()
+                    .ok()
+                    .and_then(|i: usize| user.with_untracked(|p| p.get(i).cloned()));
+                selected.set(preset.as_ref().map(|preset| preset.name.clone()));
+                preset
+            }
+            _ => None,
+        };
+        let Some(preset) = preset else {
+            return;
+        };
+        match schema.with_untracked(|schema| preset.resolve(schema)) {
+            Ok(values) => on_apply.run(values),
+            Err(err) => log::error!("Failed to apply preset {:?}: {}", preset.name, err),
+        }
+    };
+
+    let save = move |_| {
+        let name = name.get_untracked().trim().to_string();
+        if name.is_empty() {
+            return;
+        }
+        let preset = schema.with_untracked(|schema| {
+            values.with_untracked(|values| Preset::capture(name.clone(), schema, values))
+        });
+        user.update(|presets| merge(presets, vec![preset]));
+        selected.set(Some(name));
+    };
+
+    let delete = move |_| {
+        if let Some(name) = selected.get_untracked() {
+            user.update(|presets| presets.retain(|preset| preset.name != name));
+            selected.set(None);
+        }
+    };
+
+    let export = move |_| {
+        let json = user.with_untracked(serde_json::to_string_pretty);
+        let result = json
+            .map_err(|e| e.to_string())
+            .and_then(|json| download("presets.json", "application/json", json.as_bytes()));
+        if let Err(err) = result {
+            log::error!("Failed to export presets: {}", err);
+        }
+    };
+
+    let import = move |_| {
+        leptos::task::spawn_local(async move {
+            let Some(file) = AsyncFileDialog::new()
+                .add_filter("JSON", &["json"])
+                .pick_file()
+                .await
+            else {
+                return;
+            };
+            let presets = serde_json::from_slice::<Vec<Preset>>(&file.read().await)
+                .map_err(|e| e.to_string())
+                .and_then(|presets| {
+                    schema.with_untracked(|schema| validate_presets(&presets, schema))?;
+                    Ok(presets)
+                });
+            match presets {
+                Ok(presets) => user.update(|user| merge(user, presets)),
+                Err(err) => log::error!("Failed to import presets: {}", err),
+            }
+        });
+    };
+
+    view! {
+        <div class="flex items-center gap-2 mb-4">
+            <select on:change=on_pick>
+                <option value="" selected=move || selected.get().is_none()>
+                    "Presets"
+                </option>
+                {move || {
+                    builtin
+                        .get()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, preset)| {
+                            view! { <option value=format!("builtin:{}", i)>{preset.name}</option> }
+                        })
+                        .collect_view()
+                }}
+                {move || {
+                    user.get()
+                        .into_iter()
+                        .enumerate()
+                        .map(|(i, preset)| {
+                            let is_selected = selected.get().as_ref() == Some(&preset.name);
+                            view! {
+                                <option value=format!("user:{}", i) selected=is_selected>
+                                    {format!("{} (saved)", preset.name)}
+                                </option>
+                            }
+                        })
+                        .collect_view()
+                }}
+            </select>
+            <input
+                type="text"
+                placeholder="Preset name"
+                class="w-40"
+                prop:value=move || name.get()
+                on:input=move |ev| name.set(event_target_value(&ev))
+            />
+            <button class="px-2 border rounded bg-white" on:click=save>
+                "Save"
+            </button>
+            <button
+                class="px-2 border rounded bg-white disabled:opacity-50"
+                disabled=move || selected.get().is_none()
+                on:click=delete
+            >
+                "Delete"
+            </button>
+            <button class="px-2 border rounded bg-white" on:click=import>
+                "Import"
+            </button>
+            <button
+                class="px-2 border rounded bg-white disabled:opacity-50"
+                disabled=move || user.with(Vec::is_empty)
+                on:click=export
+            >
+                "Export"
+            </button>
+        </div>
+    }
+}
@@ -0,0 +1,61 @@
+use std::collections::HashMap;
+
+use leptos::prelude::window;
+use leptos::web_sys::UrlSearchParams;
+use rgeometry_host::wasm::{Schema, Value};
+use wasm_bindgen::JsValue;
+
+// Query string of the current page as parsed parameters
+fn search_params() -> Option<UrlSearchParams> {
+    let search = window().location().search().ok()?;
+    UrlSearchParams::new_with_str(&search).ok()
+}
+
+/// Values for the named entries of `schema` that are given in the query string
+/// of the current page. Unknown names and malformed values are skipped.
+pub fn read(schema: &Schema) -> Vec<(usize, Value)> {
+    let Some(params) = search_params() else {
+        return Vec::new();
+    };
+    schema
+        .iter()
+        .enumerate()
+        .filter_map(|(index, param)| {
+            let text = params.get(param.name.as_deref()?)?;
+            let value = param.ty.parse_value(&text);
+            if value.is_none() {
+                log::warn!("Ignoring invalid URL value {:?} for {:?}", text, param.name);
+            }
+            Some((index, value?))
+        })
+        .collect()
+}
+
+/// Store the values of the named entries of `schema` in the query string,
+/// without adding a history entry. Values equal to the default are left out.
+pub fn write(schema: &Schema, values: &HashMap<usize, Value>) {
+    let Some(params) = search_params() else {
+        return;
+    };
+    for (index, param) in schema.iter().enumerate() {
+        let Some(name) = &param.name else {
+            continue;
+        };
+        let value = values
+            .get(&index)
+            .filter(|v| Some(*v) != param.ty.default_value().as_ref());
+        match value.and_then(|value| param.ty.format_value(value)) {
+            Some(text) => params.set(name, &text),
+            None => params.delete(name),
+        }
+    }
+    let query = String::from(params.to_string());
+    let url = if query.is_empty() {
+        window().location().pathname().unwrap_or_default()
+    } else {
+        format!("?{}", query)
+    };
+    if let Ok(history) = window().history() {
+        _ = history.replace_state_with_url(&JsValue::NULL, "", Some(&url));
+    }
+}
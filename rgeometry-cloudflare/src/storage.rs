@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+
+use base64::prelude::*;
+use leptos::prelude::window;
+use leptos::web_sys::Storage;
+use rgeometry_host::wasm::{parameter_index, Preset, Schema, SchemaType, Value};
+use serde::{Deserialize, Serialize};
+
+const LAST_MODULE_KEY: &str = "rgeometry:last-module";
+
+/// 64-bit FNV-1a hash of `bytes`, as 16 hex digits.
+pub fn content_hash(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{:016x}", hash)
+}
+
+/// Storage keys of the session of a module. Sessions are stored under the
+/// hash of the module bytes and under the hash of its schema, so values
+/// survive rebuilds that leave the schema alone. Presets only depend on the
+/// schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionKeys {
+    module: String,
+    schema: String,
+    presets: String,
+}
+
+impl SessionKeys {
+    /// Keys of the module with the `content_hash` `module_hash`.
+    pub fn new(module_hash: &str, schema: &Schema) -> Self {
+        let schema = serde_json::to_string(schema).unwrap_or_default();
+        let schema_hash = content_hash(schema.as_bytes());
+        SessionKeys {
+            module: format!("rgeometry:session:{}", module_hash),
+            schema: format!("rgeometry:schema-session:{}", schema_hash),
+            presets: format!("rgeometry:presets:{}", schema_hash),
+        }
+    }
+}
+
+/// A parameter value in its URL form, with what identifies its entry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedValue {
+    pub index: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Schema type tag of the entry, such as "range_f32"
+    pub kind: String,
+    pub value: String,
+}
+
+/// Viewer state of one module.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Session {
+    pub values: Vec<SavedValue>,
+    pub paused: bool,
+    pub time: f64,
+}
+
+// The "type" tag of a schema entry
+fn kind(ty: &SchemaType) -> String {
+    match serde_json::to_value(ty) {
+        Ok(serde_json::Value::Object(fields)) => match fields.get("type") {
+            Some(serde_json::Value::String(kind)) => kind.clone(),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+impl Session {
+    /// Record the picked values. File contents are not stored.
+    pub fn capture(
+        schema: &Schema,
+        values: &HashMap<usize, Value>,
+        paused: bool,
+        time: f64,
+    ) -> Self {
+        let mut values: Vec<SavedValue> = values
+            .iter()
+            .filter_map(|(index, value)| {
+                let param = schema.get(*index)?;
+                Some(SavedValue {
+                    index: *index,
+                    name: param.name.clone(),
+                    kind: kind(&param.ty),
+                    value: param.ty.format_value(value)?,
+                })
+            })
+            .collect();
+        values.sort_by_key(|saved| saved.index);
+        Session {
+            values,
+            paused,
+            time,
+        }
+    }
+
+    /// The saved values that still suit `schema`. Named entries are matched
+    /// by name, others by position, and the type of the entry must not have
+    /// changed.
+    pub fn restore_values(&self, schema: &Schema) -> Vec<(usize, Value)> {
+        self.values
+            .iter()
+            .filter_map(|saved| {
+                let index = match &saved.name {
+                    Some(name) => parameter_index(schema, name)?,
+                    None => saved.index,
+                };
+                let param = schema.get(index)?;
+                if kind(&param.ty) != saved.kind {
+                    return None;
+                }
+                Some((index, param.ty.parse_value(&saved.value)?))
+            })
+            .collect()
+    }
+}
+
+fn local_storage() -> Option<Storage> {
+    window().local_storage().ok().flatten()
+}
+
+/// The stored session for the module, if any.
+pub fn load_session(keys: &SessionKeys) -> Option<Session> {
+    let storage = local_storage()?;
+    [&keys.module, &keys.schema].into_iter().find_map(|key| {
+        let json = storage.get_item(key).ok()??;
+        serde_json::from_str(&json).ok()
+    })
+}
+
+pub fn save_session(keys: &SessionKeys, session: &Session) {
+    let (Some(storage), Ok(json)) = (local_storage(), serde_json::to_string(session)) else {
+        return;
+    };
+    for key in [&keys.module, &keys.schema] {
+        if let Err(err) = storage.set_item(key, &json) {
+            log::warn!("Failed to store the session: {:?}", err);
+        }
+    }
+}
+
+/// Presets the user saved for the module.
+pub fn load_presets(keys: &SessionKeys) -> Vec<Preset> {
+    let json = local_storage().and_then(|storage| storage.get_item(&keys.presets).ok()?);
+    json.and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default()
+}
+
+pub fn save_presets(keys: &SessionKeys, presets: &[Preset]) {
+    let (Some(storage), Ok(json)) = (local_storage(), serde_json::to_string(presets)) else {
+        return;
+    };
+    if let Err(err) = storage.set_item(&keys.presets, &json) {
+        log::warn!("Failed to store the presets: {:?}", err);
+    }
+}
+
+/// The bytes of the module that was opened last.
+pub fn load_module() -> Option<Vec<u8>> {
+    let encoded = local_storage()?.get_item(LAST_MODULE_KEY).ok()??;
+    BASE64_STANDARD.decode(encoded).ok()
+}
+
+/// Remember the module so it is opened again on the next visit. Modules that
+/// don't fit in the storage quota are forgotten instead.
+pub fn save_module(bytes: &[u8]) {
+    let Some(storage) = local_storage() else {
+        return;
+    };
+    if let Err(err) = storage.set_item(LAST_MODULE_KEY, &BASE64_STANDARD.encode(bytes)) {
+        log::warn!("Failed to store the module: {:?}", err);
+        _ = storage.remove_item(LAST_MODULE_KEY);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(json: &str) -> Schema {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn fnv_hash() {
+        assert_eq!(content_hash(b""), "cbf29ce484222325");
+        assert_eq!(content_hash(b"a"), "af63dc4c8601ec8c");
+        assert_ne!(content_hash(b"ab"), content_hash(b"ba"));
+    }
+
+    #[test]
+    fn session_round_trip() {
+        let old = schema(
+            r#"[
+                {"type": "time"},
+                {"type": "range_f32", "min": 0, "max": 1, "default": 0.5},
+                {"type": "bool", "default": false, "name": "flag"},
+                {"type": "enum", "options": ["a", "b"], "default": 0}
+            ]"#,
+        );
+        let values = HashMap::from([
+            (1, Value::F32(0.25)),
+            (2, Value::I32(1)),
+            (3, Value::I32(1)),
+        ]);
+        let session = Session::capture(&old, &values, true, 3.5);
+        let json = serde_json::to_string(&session).unwrap();
+        let session: Session = serde_json::from_str(&json).unwrap();
+        assert!(session.paused);
+        assert_eq!(session.time, 3.5);
+        let mut restored = session.restore_values(&old);
+        restored.sort_by_key(|(index, _)| *index);
+        assert_eq!(
+            restored,
+            [
+                (1, Value::F32(0.25)),
+                (2, Value::I32(1)),
+                (3, Value::I32(1))
+            ]
+        );
+
+        // The named flag moved, the range shrank below the saved value and
+        // the enum became a range
+        let new = schema(
+            r#"[
+                {"type": "bool", "default": false, "name": "flag"},
+                {"type": "range_f32", "min": 0.5, "max": 1, "default": 0.5},
+                {"type": "time"},
+                {"type": "range_i32", "min": 0, "max": 5, "default": 0}
+            ]"#,
+        );
+        assert_eq!(session.restore_values(&new), [(0, Value::I32(1))]);
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crate::app::svg_data_url;
+use crate::download::download;
+use crate::export::number_input;
+use crate::worker::{GuestWorker, Response};
+use leptos::prelude::*;
+use rgeometry_host::sweep::{Fit, Sweep, SweepOptions};
+use rgeometry_host::wasm::{Schema, SchemaType, Value, Viewport};
+
+// A sweep and where it reports to. Starting or stopping a sweep moves
+// `current` past its `id`, so the answers of its worker are ignored.
+#[derive(Clone, Copy)]
+struct SweepRun {
+    id: u64,
+    current: StoredValue<u64>,
+    // The worker measuring the values, and what it measures
+    guest: StoredValue<Option<GuestWorker>, LocalStorage>,
+    module: StoredValue<Vec<u8>>,
+    values: StoredValue<HashMap<usize, Value>>,
+    options: SweepOptions,
+    result: RwSignal<Option<Sweep>>,
+    running: RwSignal<bool>,
+    error: RwSignal<Option<String>>,
+}
+
+impl SweepRun {
+    // Ask the worker to measure the next value, or end the sweep after the
+    // last one. The worker renders the frames, so the page stays responsive,
+    // and stopping the sweep ends the worker along with a frame that takes
+    // too long.
+    fn measure_next(self) {
+        if self.current.try_get_value() != Some(self.id) {
+            return;
+        }
+        let next = self
+            .result
+            .with_untracked(|sweep| sweep.as_ref().map_or(0, |sweep| sweep.points.len()));
+        let Some(value) = self.options.values().get(next).copied() else {
+            self.end();
+            return;
+        };
+        self.guest.update_value(|guest| {
+            if let Some(guest) = guest {
+                self.module.with_value(|module| {
+                    self.values
+                        .with_value(|values| guest.measure(module, values, self.options, value))
+                });
+            }
+        });
+    }
+
+    // Take in an answer of the worker. Answers are taken in after the
+    // worker's handler returns, as the last one drops the worker.
+    fn answer(self, response: Response) {
+        if self.current.try_get_value() != Some(self.id) {
+            return;
+        }
+        match response {
+            Response::Measured(point) => {
+                self.result.update(|sweep| {
+                    if let Some(sweep) = sweep {
+                        sweep.points.push(point);
+                    }
+                });
+                self.measure_next();
+            }
+            Response::Error(err) => {
+                self.error.set(Some(err));
+                self.end();
+            }
+            _ => {}
+        }
+    }
+
+    fn end(self) {
+        self.running.set(false);
+        self.guest.set_value(None);
+    }
+}
+
+fn slope(fit: Option<Fit>) -> String {
+    fit.map_or("-".to_string(), |fit| format!("{:.2}", fit.slope))
+}
+
+/// Sweeps a `range_i32` parameter, such as the number of points, over values
+/// spaced evenly on a log scale while the other parameters keep their
+/// `values`. Every value is rendered by fresh instances of the `module`, in a
+/// worker of the sweep's own, timing several frames and counting the fuel of
+/// one, which must stay within `fuel_limit`. The medians and fuel are plotted
+/// on log-log axes with their fitted slopes.
+#[component]
+pub fn SweepPanel(
+    #[prop(into)] schema: Signal<Schema>,
+    #[prop(into)] values: Signal<HashMap<usize, Value>>,
+    module: StoredValue<Vec<u8>>,
+    #[prop(into)] fuel_limit: Signal<Option<u64>>,
+    viewport: StoredValue<Viewport>,
+) -> impl IntoView {
+    // The range_i32 parameters, with their labels
+    let candidates = Memo::new(move |_| {
+        schema.with(|schema| {
+            schema
+                .iter()
+                .enumerate()
+                .filter(|(_, param)| matches!(param.ty, SchemaType::RangeI32 { .. }))
+                .map(|(i, param)| (i, param.display_label(i)))
+                .collect::<Vec<_>>()
+        })
+    });
+    let parameter = RwSignal::new(None::<usize>);
+    let min = RwSignal::new(1);
+    let max = RwSignal::new(1);
+    let steps = RwSignal::new(8usize);
+    let samples = RwSignal::new(5usize);
+    let result = RwSignal::new(None::<Sweep>);
+    let running = RwSignal::new(false);
+    let error = RwSignal::new(None::<String>);
+    // Number of the last sweep started or stopped, and the worker of the
+    // sweep that runs
+    let generation = StoredValue::new(0u64);
+    let guest = StoredValue::new_local(None::<GuestWorker>);
+    let swept_values = StoredValue::new(HashMap::new());
+    // Stopping ends the worker, and with it a frame that takes too long
+    let stop = move || {
+        generation.update_value(|id| *id += 1);
+        running.set(false);
+        guest.set_value(None);
+    };
+
+    // Sweep the whole range of the picked parameter by default
+    let pick = move |index: Option<usize>| {
+        parameter.set(index);
+        let defaults = index.and_then(|index| {
+            schema.with_untracked(|schema| SweepOptions::new(schema, index).ok())
+        });
+        if let Some(defaults) = defaults {
+            min.set(defaults.min);
+            max.set(defaults.max);
+        }
+    };
+    Effect::new(move |_| {
+        let first = candidates.with(|candidates| candidates.first().map(|(i, _)| *i));
+        stop();
+        result.set(None);
+        pick(first);
+    });
+
+    let run = move |_| {
+        let Some(index) = parameter.get_untracked() else {
+            return;
+        };
+        let options = SweepOptions {
+            parameter: index,
+            min: min.get_untracked(),
+            max: max.get_untracked(),
+            steps: steps.get_untracked(),
+            samples: samples.get_untracked(),
+            time: 0.0,
+            fuel_limit: fuel_limit.get_untracked(),
+            viewport: viewport.get_value(),
+        };
+        if let Err(err) = schema.with_untracked(|schema| options.validate(schema)) {
+            error.set(Some(err));
+            return;
+        }
+        stop();
+        let run = SweepRun {
+            id: generation.get_value(),
+            current: generation,
+            guest,
+            module,
+            values: swept_values,
+            options,
+            result,
+            running,
+            error,
+        };
+        let worker = GuestWorker::new(move |response| {
+            set_timeout(move || run.answer(response), Duration::ZERO)
+        });
+        match worker {
+            Ok(worker) => guest.set_value(Some(worker)),
+            Err(err) => {
+                error.set(Some(err));
+                return;
+            }
+        }
+        let label = schema.with_untracked(|schema| schema[index].display_label(index));
+        error.set(None);
+        result.set(Some(Sweep {
+            parameter: label,
+            points: Vec::new(),
+        }));
+        swept_values.set_value(values.get_untracked());
+        running.set(true);
+        run.measure_next();
+    };
+    let save = move |plot: bool| {
+        let Some(sweep) = result.get_untracked() else {
+            return;
+        };
+        let file_name = format!("sweep-{}", sweep.parameter.replace(' ', "-"));
+        let saved = if plot {
+            download(
+                &format!("{}.svg", file_name),
+                "image/svg+xml",
+                sweep.plot().as_bytes(),
+            )
+        } else {
+            download(
+                &format!("{}.csv", file_name),
+                "text/csv",
+                sweep.to_csv().as_bytes(),
+            )
+        };
+        if let Err(err) = saved {
+            log::error!("Failed to save the sweep: {}", err);
+        }
+    };
+
+    view! {
+        <Show when=move || candidates.with(|candidates| !candidates.is_empty())>
+            <details class="mb-4">
+                <summary class="cursor-pointer">"Complexity sweep"</summary>
+                <div class="flex items-center gap-2 mt-2">
+                    <select on:change=move |ev| pick(event_target_value(&ev).parse().ok())>
+                        {move || {
+                            candidates
+                                .get()
+                                .into_iter()
+                                .map(|(i, label)| {
+                                    view! {
+                                        <option value=i selected=move || parameter.get() == Some(i)>
+                                            {label}
+                                        </option>
+                                    }
+                                })
+                                .collect_view()
+                        }}
+                    </select>
+                    {number_input("From", min, |v| *v >= 1)}
+                    "-"
+                    {number_input("To", max, |v| *v >= 1)}
+                    "in"
+                    {number_input("Steps", steps, |v| *v >= 2)}
+                    "steps,"
+                    {number_input("Samples", samples, |v| *v >= 1)}
+                    "samples each"
+                    <Show
+                        when=move || running.get()
+                        fallback=move || {
+                            view! {
+                                <button class="px-2 border rounded bg-white" on:click=run>
+                                    "Run"
+                                </button>
+                            }
+                        }
+                    >
+                        <button class="px-2 border rounded bg-white" on:click=move |_| stop()>
+                            "Stop"
+                        </button>
+                    </Show>
+                    <button
+                        class="px-2 border rounded bg-white disabled:opacity-50"
+                        disabled=move || running.get() || result.with(Option::is_none)
+                        on:click=move |_| save(true)
+                    >
+                        "Save plot"
+                    </button>
+                    <button
+                        class="px-2 border rounded bg-white disabled:opacity-50"
+                        disabled=move || running.get() || result.with(Option::is_none)
+                        on:click=move |_| save(false)
+                    >
+                        "Save CSV"
+                    </button>
+                </div>
+                {move || error.get().map(|err| view! { <p class="text-red-500">{err}</p> })}
+                {move || {
+                    result
+                        .with(|sweep| {
+                            sweep
+                                .as_ref()
+                                .map(|sweep| {
+                                    view! {
+                                        <p>
+                                            {format!(
+                                                "{} values measured. Time slope: {}, fuel slope: {}",
+                                                sweep.points.len(),
+                                                slope(sweep.time_fit()),
+                                                slope(sweep.fuel_fit()),
+                                            )}
+                                        </p>
+                                        <img class="bg-white" src=svg_data_url(&sweep.plot())/>
+                                    }
+                                })
+                        })
+                }}
+            </details>
+        </Show>
+    }
+}
@@ -0,0 +1,1047 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use leptos::web_sys::js_sys::{Array, Object, Reflect, Uint8Array};
+#[cfg(feature = "hydrate")]
+use leptos::web_sys::DedicatedWorkerGlobalScope;
+use leptos::web_sys::{MessageEvent, Worker, WorkerOptions, WorkerType};
+#[cfg(any(test, feature = "hydrate"))]
+use rgeometry_host::animation;
+use rgeometry_host::animation::AnimationOptions;
+#[cfg(any(test, feature = "hydrate"))]
+use rgeometry_host::sweep;
+use rgeometry_host::sweep::{SweepOptions, SweepPoint};
+use rgeometry_host::viewbox::ViewBox;
+#[cfg(any(test, feature = "hydrate"))]
+use rgeometry_host::wasm::Wasm;
+use rgeometry_host::wasm::{FrameMetrics, Metadata, Schema, Value, Viewport};
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::prelude::*;
+
+use crate::storage;
+
+/// Script that loads the client bundle in a worker and calls `worker_main`.
+const WORKER_SCRIPT: &str = "/worker.js";
+
+/// How long a frame may take, in milliseconds, before the worker rendering it
+/// is restarted. Rendering stops if the frame after the restart misses it too.
+pub const FRAME_DEADLINE: f64 = 3000.0;
+
+/// Fuel a frame may consume by default, see `Wasm::set_fuel_limit`.
+pub const DEFAULT_FUEL_LIMIT: u64 = 1_000_000_000;
+
+// Browser frames arrive a little early or late, so allow that much leeway,
+// in milliseconds, before a frame rate limit skips one
+const FRAME_SLACK: f64 = 2.0;
+
+/// Playback state and parameters, sent to a worker after it opened a module.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct GuestState {
+    pub time: f64,
+    pub paused: bool,
+    pub values: Vec<(usize, Value)>,
+}
+
+/// Messages from the page to the worker. They are posted as JSON, with their
+/// binary payloads transferred alongside, see `Request::take_buffers`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Request {
+    /// Instantiate a module in place of the open one. The hash is passed back
+    /// in `Response::Opened`. With a fuel limit, the module is metered and
+    /// frames that exceed it are stopped.
+    Open {
+        module: Vec<u8>,
+        hash: String,
+        fuel_limit: Option<u64>,
+    },
+    Restore(GuestState),
+    SetParameter(usize, Value),
+    ResetParameters,
+    SetTime(f64),
+    SetPaused(bool),
+    /// Render a frame at the current time, or first jump to `time`
+    Render {
+        viewport: Viewport,
+        camera: Option<ViewBox>,
+        time: Option<f64>,
+    },
+    /// Render frame `index` of an exported animation, see
+    /// `animation::render_frame`. Frames are asked for in order.
+    RenderAnimation {
+        options: AnimationOptions,
+        index: usize,
+        viewport: Viewport,
+    },
+    /// Measure one value of a complexity sweep with fresh instances of
+    /// `module`, see `sweep::measure`. No module has to be open.
+    Measure {
+        module: Vec<u8>,
+        values: Vec<(usize, Value)>,
+        options: SweepOptions,
+        value: i32,
+    },
+}
+
+impl Request {
+    // The module and the contents of file parameters, in message order
+    fn buffers_mut(&mut self) -> Vec<&mut Vec<u8>> {
+        fn files(values: &mut [(usize, Value)]) -> Vec<&mut Vec<u8>> {
+            values
+                .iter_mut()
+                .filter_map(|(_, value)| match value {
+                    Value::Bytes(bytes) => Some(bytes),
+                    _ => None,
+                })
+                .collect()
+        }
+        match self {
+            Request::Open { module, .. } => vec![module],
+            Request::Restore(state) => files(&mut state.values),
+            Request::SetParameter(_, Value::Bytes(bytes)) => vec![bytes],
+            Request::Measure { module, values, .. } => {
+                let mut buffers = vec![module];
+                buffers.extend(files(values));
+                buffers
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Move the binary payloads out, so they can be transferred to the worker
+    /// instead of encoded as JSON arrays of numbers.
+    pub fn take_buffers(&mut self) -> Vec<Vec<u8>> {
+        self.buffers_mut().into_iter().map(std::mem::take).collect()
+    }
+
+    /// Put back the payloads `take_buffers` moved out.
+    #[cfg(any(test, feature = "hydrate"))]
+    pub fn put_buffers(&mut self, buffers: Vec<Vec<u8>>) -> Result<(), String> {
+        let slots = self.buffers_mut();
+        if slots.len() != buffers.len() {
+            return Err(format!(
+                "Bad request: expected {} buffers, got {}",
+                slots.len(),
+                buffers.len()
+            ));
+        }
+        for (slot, buffer) in slots.into_iter().zip(buffers) {
+            *slot = buffer;
+        }
+        Ok(())
+    }
+}
+
+/// Messages from the worker to the page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Response {
+    /// The worker is listening for requests
+    Ready,
+    /// The module with the content hash `hash` was instantiated
+    Opened {
+        hash: String,
+        schema: Schema,
+        meta: Metadata,
+        /// See `Wasm::warnings`
+        warnings: Vec<String>,
+    },
+    /// A value the module didn't accept
+    Rejected(usize, String),
+    /// A frame consumed all of the given fuel and was stopped. The module
+    /// has failed and keeps showing its last frame, which is sent next.
+    OutOfFuel(u64),
+    Frame {
+        svg: String,
+        /// The module's time, or the time the frame was asked for, see
+        /// `GuestWorker::request_frame`
+        time: f64,
+        metrics: FrameMetrics,
+        /// Whether the next frame may differ, see `Wasm::is_animated`
+        animated: bool,
+    },
+    /// Frame `index` of an exported animation
+    AnimationFrame(usize, String),
+    /// A value of a complexity sweep was measured
+    Measured(SweepPoint),
+    /// No frames are asked for after an error, until a module opens
+    Error(String),
+}
+
+/// The module instance of a worker, driven by requests.
+#[cfg(any(test, feature = "hydrate"))]
+#[derive(Debug, Default)]
+pub struct Guest {
+    wasm: Option<Wasm>,
+    // Fuel limit of the open module
+    fuel_limit: Option<u64>,
+    // Whether requests were refused for lack of a module, which is only
+    // reported once
+    refused: bool,
+}
+
+#[cfg(any(test, feature = "hydrate"))]
+impl Guest {
+    pub fn handle(&mut self, request: Request) -> Vec<Response> {
+        if let Request::Open {
+            module,
+            hash,
+            fuel_limit,
+        } = request
+        {
+            return match Wasm::with_fuel_limit(&module, fuel_limit) {
+                Ok(wasm) => {
+                    let opened = Response::Opened {
+                        hash,
+                        schema: wasm.schema().clone(),
+                        meta: wasm.meta().clone(),
+                        warnings: wasm.warnings().to_vec(),
+                    };
+                    self.wasm = Some(wasm);
+                    self.fuel_limit = fuel_limit;
+                    self.refused = false;
+                    vec![opened]
+                }
+                Err(err) => {
+                    self.wasm = None;
+                    self.refused = true;
+                    vec![Response::Error(format!("Failed to load module: {}", err))]
+                }
+            };
+        }
+        if let Request::Measure {
+            module,
+            values,
+            options,
+            value,
+        } = request
+        {
+            let values = values.into_iter().collect();
+            return vec![match sweep::measure(&module, &values, &options, value) {
+                Ok(point) => Response::Measured(point),
+                Err(err) => Response::Error(err),
+            }];
+        }
+        let Some(wasm) = self.wasm.as_mut() else {
+            if std::mem::replace(&mut self.refused, true) {
+                return Vec::new();
+            }
+            return vec![Response::Error("No module is open".to_string())];
+        };
+        let set = |wasm: &mut Wasm, index: usize, value: Value| {
+            wasm.set_parameter(index, value)
+                .err()
+                .map(|err| Response::Rejected(index, err))
+        };
+        match request {
+            Request::Open { .. } | Request::Measure { .. } => unreachable!(),
+            Request::Restore(state) => {
+                wasm.set_paused(state.paused);
+                wasm.set_time(state.time);
+                wasm.reset_parameters();
+                state
+                    .values
+                    .into_iter()
+                    .filter_map(|(index, value)| set(wasm, index, value))
+                    .collect()
+            }
+            Request::SetParameter(index, value) => set(wasm, index, value).into_iter().collect(),
+            Request::ResetParameters => {
+                wasm.reset_parameters();
+                Vec::new()
+            }
+            Request::SetTime(time) => {
+                wasm.set_time(time);
+                Vec::new()
+            }
+            Request::SetPaused(paused) => {
+                wasm.set_paused(paused);
+                Vec::new()
+            }
+            Request::Render {
+                viewport,
+                camera,
+                time,
+            } => {
+                wasm.set_viewport(viewport);
+                wasm.set_camera(camera);
+                if let Some(time) = time {
+                    wasm.set_time(time);
+                }
+                // A module that failed keeps showing its last frame. Why it
+                // failed is reported before that frame.
+                let failed = wasm.has_failed();
+                let svg = wasm.render();
+                let mut responses = Vec::new();
+                if !failed && wasm.has_failed() {
+                    responses.push(match self.fuel_limit.filter(|_| wasm.is_out_of_fuel()) {
+                        Some(limit) => Response::OutOfFuel(limit),
+                        None => Response::Error("The module failed to render a frame".to_string()),
+                    });
+                }
+                responses.push(Response::Frame {
+                    svg,
+                    time: wasm.time(),
+                    metrics: wasm.metrics(),
+                    animated: wasm.is_animated(),
+                });
+                responses
+            }
+            Request::RenderAnimation {
+                options,
+                index,
+                viewport,
+            } => {
+                wasm.set_viewport(viewport);
+                vec![match animation::render_frame(wasm, &options, index) {
+                    Ok(svg) => Response::AnimationFrame(index, svg),
+                    Err(err) => Response::Error(err),
+                }]
+            }
+        }
+    }
+}
+
+#[cfg(feature = "hydrate")]
+fn post(scope: &DedicatedWorkerGlobalScope, response: &Response) {
+    match serde_json::to_string(response) {
+        Ok(json) => _ = scope.post_message(&JsValue::from_str(&json)),
+        Err(err) => log::error!("Failed to encode response: {}", err),
+    }
+}
+
+// Read a request posted by `send_to`
+#[cfg(feature = "hydrate")]
+fn receive(data: &JsValue) -> Result<Request, String> {
+    let json = Reflect::get(data, &"request".into())
+        .ok()
+        .and_then(|json| json.as_string())
+        .ok_or_else(|| "Bad request: no JSON".to_string())?;
+    let mut request: Request =
+        serde_json::from_str(&json).map_err(|e| format!("Bad request: {}", e))?;
+    let buffers =
+        Reflect::get(data, &"buffers".into()).map_err(|_| "Bad request: no buffers".to_string())?;
+    request.put_buffers(
+        Array::from(&buffers)
+            .iter()
+            .map(|buffer| Uint8Array::new(&buffer).to_vec())
+            .collect(),
+    )?;
+    Ok(request)
+}
+
+/// Entry point of the module worker: answer requests from the page until the
+/// worker is terminated.
+#[cfg(feature = "hydrate")]
+#[wasm_bindgen]
+pub fn worker_main() {
+    _ = console_log::init_with_level(log::Level::Debug);
+    console_error_panic_hook::set_once();
+    let scope: DedicatedWorkerGlobalScope = leptos::web_sys::js_sys::global().unchecked_into();
+    let mut guest = Guest::default();
+    let on_message = {
+        let scope = scope.clone();
+        Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            match receive(&event.data()) {
+                Ok(request) => {
+                    for response in guest.handle(request) {
+                        post(&scope, &response);
+                    }
+                }
+                Err(err) => post(&scope, &Response::Error(err)),
+            }
+        })
+    };
+    scope.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    on_message.forget();
+    post(&scope, &Response::Ready);
+}
+
+// State shared between a `GuestWorker` and its message handler
+#[derive(Default)]
+struct Connection {
+    ready: bool,
+    // Requests sent before the worker was ready
+    queue: Vec<Request>,
+    // When the frame being rendered was requested, and the time it was asked
+    // to show
+    pending_since: Option<f64>,
+    pending_time: Option<f64>,
+    // Swallow the Opened response of a restarted worker
+    restarting: bool,
+    time: f64,
+    // Whether the last frame may change on its own
+    animated: bool,
+    // The module being opened and the module that opened, with their content
+    // hashes. Only a module that opened is reopened by a restarted worker.
+    opening: Option<(Vec<u8>, String)>,
+    module: Option<(Vec<u8>, String)>,
+    // Whether frames are asked for, from when a module opened until an error
+    rendering: bool,
+    // Whether the worker rendered a frame since it started
+    rendered: bool,
+}
+
+// Decides when to ask for a frame: when something the frame depends on
+// changed or the module is animated, but no more often than the frame rate
+// limit allows
+#[derive(Debug, Default)]
+struct Schedule {
+    // Set by requests that change what the module renders
+    dirty: bool,
+    // Viewport and camera of the last requested frame
+    last: Option<(Viewport, Option<ViewBox>)>,
+    last_at: Option<f64>,
+    // Shortest time between frames in milliseconds, 0 without a limit
+    interval: f64,
+}
+
+impl Schedule {
+    fn due(
+        &mut self,
+        now: f64,
+        viewport: Viewport,
+        camera: Option<ViewBox>,
+        animated: bool,
+    ) -> bool {
+        let limited = self
+            .last_at
+            .is_some_and(|at| now - at < self.interval - FRAME_SLACK);
+        let changed = self.dirty || self.last != Some((viewport, camera));
+        if limited || !(changed || animated) {
+            return false;
+        }
+        self.dirty = false;
+        self.last = Some((viewport, camera));
+        self.last_at = Some(now);
+        true
+    }
+}
+
+/// Page side of the module worker. Keeps the open module, its values and
+/// playback state, so a worker that misses `FRAME_DEADLINE` can be replaced
+/// by a fresh one in the same state.
+pub struct GuestWorker {
+    worker: Worker,
+    connection: Rc<RefCell<Connection>>,
+    on_response: Rc<dyn Fn(Response)>,
+    _on_message: OnMessage,
+    fuel_limit: Option<u64>,
+    values: HashMap<usize, Value>,
+    paused: bool,
+    schedule: Schedule,
+    // Values of the last frame that missed the deadline, and whether the one
+    // after the restart missed it too, which stops rendering until a request
+    // changes what the module renders
+    missed: Option<HashMap<usize, Value>>,
+    stalled: bool,
+}
+
+// Handler of the messages from a worker
+type OnMessage = Closure<dyn FnMut(MessageEvent)>;
+
+// Post a request as a structured-clone object: the request as JSON, and its
+// binary payloads as buffers that are transferred rather than copied
+fn send_to(worker: &Worker, mut request: Request) {
+    let buffers = request.take_buffers();
+    let json = match serde_json::to_string(&request) {
+        Ok(json) => json,
+        Err(err) => {
+            log::error!("Failed to encode request: {}", err);
+            return;
+        }
+    };
+    let arrays: Array = buffers
+        .iter()
+        .map(|buffer| Uint8Array::from(buffer.as_slice()))
+        .collect();
+    let transfer: Array = arrays
+        .iter()
+        .map(|array| array.unchecked_into::<Uint8Array>().buffer())
+        .collect();
+    let message = Object::new();
+    _ = Reflect::set(&message, &"request".into(), &json.into());
+    _ = Reflect::set(&message, &"buffers".into(), &arrays);
+    if let Err(err) = worker.post_message_with_transfer(&message, &transfer) {
+        log::error!("Failed to message the worker: {:?}", err);
+    }
+}
+
+// Start a worker whose responses go to `on_response`
+fn spawn(
+    connection: Rc<RefCell<Connection>>,
+    on_response: Rc<dyn Fn(Response)>,
+) -> Result<(Worker, OnMessage), String> {
+    let options = WorkerOptions::new();
+    options.set_type(WorkerType::Module);
+    let worker = Worker::new_with_options(WORKER_SCRIPT, &options)
+        .map_err(|e| format!("Failed to start the worker: {:?}", e))?;
+    let on_message = {
+        let worker = worker.clone();
+        Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            let Some(mut response) = event
+                .data()
+                .as_string()
+                .and_then(|json| serde_json::from_str::<Response>(&json).ok())
+            else {
+                log::error!("Unexpected message from the worker");
+                return;
+            };
+            let forward = {
+                let mut connection = connection.borrow_mut();
+                match &mut response {
+                    Response::Ready => {
+                        connection.ready = true;
+                        for request in connection.queue.drain(..) {
+                            send_to(&worker, request);
+                        }
+                        false
+                    }
+                    Response::Opened { hash, .. } => {
+                        if connection
+                            .opening
+                            .as_ref()
+                            .is_some_and(|(_, opening)| opening == hash)
+                        {
+                            connection.module = connection.opening.take();
+                        }
+                        connection.rendering = true;
+                        !std::mem::take(&mut connection.restarting)
+                    }
+                    Response::Frame { time, animated, .. } => {
+                        connection.pending_since = None;
+                        if let Some(pending_time) = connection.pending_time.take() {
+                            *time = pending_time;
+                        }
+                        connection.time = *time;
+                        connection.animated = *animated;
+                        connection.rendered = true;
+                        true
+                    }
+                    // The module fails the same way on every frame, so stop
+                    // asking for them. A module that failed to open is gone.
+                    failed @ (Response::Error(_) | Response::OutOfFuel(_)) => {
+                        connection.pending_since = None;
+                        connection.pending_time = None;
+                        connection.animated = false;
+                        connection.rendering = false;
+                        if matches!(failed, Response::Error(_)) {
+                            connection.opening = None;
+                        }
+                        true
+                    }
+                    Response::Rejected(..)
+                    | Response::AnimationFrame(..)
+                    | Response::Measured(_) => true,
+                }
+            };
+            if forward {
+                on_response(response);
+            }
+        })
+    };
+    worker.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+    Ok((worker, on_message))
+}
+
+impl GuestWorker {
+    pub fn new(on_response: impl Fn(Response) + 'static) -> Result<Self, String> {
+        let connection = Rc::new(RefCell::new(Connection::default()));
+        let on_response: Rc<dyn Fn(Response)> = Rc::new(on_response);
+        let (worker, on_message) = spawn(connection.clone(), on_response.clone())?;
+        Ok(GuestWorker {
+            worker,
+            connection,
+            on_response,
+            _on_message: on_message,
+            fuel_limit: Some(DEFAULT_FUEL_LIMIT),
+            values: HashMap::new(),
+            paused: false,
+            schedule: Schedule::default(),
+            missed: None,
+            stalled: false,
+        })
+    }
+
+    fn send(&mut self, request: Request) {
+        if !matches!(request, Request::Render { .. }) {
+            self.schedule.dirty = true;
+            self.stalled = false;
+        }
+        let mut connection = self.connection.borrow_mut();
+        if connection.ready {
+            send_to(&self.worker, request);
+        } else {
+            connection.queue.push(request);
+        }
+    }
+
+    /// Open a module. The worker answers with `Response::Opened`, after which
+    /// the caller restores the module's state, or with `Response::Error`.
+    pub fn open(&mut self, bytes: &[u8]) {
+        let hash = storage::content_hash(bytes);
+        {
+            let mut connection = self.connection.borrow_mut();
+            connection.opening = Some((bytes.to_vec(), hash.clone()));
+            connection.module = None;
+            connection.rendering = false;
+            connection.time = 0.0;
+        }
+        self.send(Request::Open {
+            module: bytes.to_vec(),
+            hash,
+            fuel_limit: self.fuel_limit,
+        });
+        self.values.clear();
+        self.paused = false;
+        self.missed = None;
+    }
+
+    pub fn restore(&mut self, state: GuestState) {
+        self.values = state.values.iter().cloned().collect();
+        self.paused = state.paused;
+        self.connection.borrow_mut().time = state.time;
+        self.send(Request::Restore(state));
+    }
+
+    pub fn set_parameter(&mut self, index: usize, value: Value) {
+        self.values.insert(index, value.clone());
+        self.send(Request::SetParameter(index, value));
+    }
+
+    pub fn reset_parameters(&mut self) {
+        self.values.clear();
+        self.send(Request::ResetParameters);
+    }
+
+    pub fn set_time(&mut self, time: f64) {
+        self.connection.borrow_mut().time = time;
+        self.send(Request::SetTime(time));
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.send(Request::SetPaused(paused));
+    }
+
+    /// Render at most `fps` frames per second, or on every browser frame if
+    /// None.
+    pub fn set_frame_rate(&mut self, fps: Option<f64>) {
+        self.schedule.interval = fps.map_or(0.0, |fps| 1000.0 / fps);
+    }
+
+    /// Stop frames that consume more than `limit` fuel, or never if None.
+    /// The open module is reopened in the same state, which also revives it
+    /// if it ran out of fuel.
+    pub fn set_fuel_limit(&mut self, limit: Option<u64>) {
+        self.fuel_limit = limit;
+        let opened = {
+            let connection = self.connection.borrow();
+            connection.opening.is_some() || connection.module.is_some()
+        };
+        if opened {
+            if let Err(err) = self.restart() {
+                log::error!("{}", err);
+            }
+        }
+    }
+
+    /// Ask for frame `index` of an exported animation. The worker answers
+    /// with `Response::AnimationFrame`, or `Response::Error` if the frame
+    /// failed. Unlike `request_frame`, there is no deadline.
+    pub fn render_animation_frame(
+        &mut self,
+        options: AnimationOptions,
+        index: usize,
+        viewport: Viewport,
+    ) {
+        self.send(Request::RenderAnimation {
+            options,
+            index,
+            viewport,
+        });
+    }
+
+    /// Measure one value of a complexity sweep of `module`, with the other
+    /// parameters set to `values`. The worker answers with
+    /// `Response::Measured`, or `Response::Error` if a frame failed.
+    pub fn measure(
+        &mut self,
+        module: &[u8],
+        values: &HashMap<usize, Value>,
+        options: SweepOptions,
+        value: i32,
+    ) {
+        self.send(Request::Measure {
+            module: module.to_vec(),
+            values: values.clone().into_iter().collect(),
+            options,
+            value,
+        });
+    }
+
+    /// Time of the last rendered frame.
+    pub fn time(&self) -> f64 {
+        self.connection.borrow().time
+    }
+
+    /// Whether a module opened, and no other is being opened.
+    pub fn is_open(&self) -> bool {
+        self.connection.borrow().module.is_some()
+    }
+
+    /// Ask for a frame if the module's values, time, the viewport or camera
+    /// changed since the last one, or the module is animated. Nothing is
+    /// asked while a frame is being rendered or sooner than the frame rate
+    /// allows. The frame shows `time` if given, for views that share a
+    /// clock, and reports it as its time, and the module's own time
+    /// otherwise. A frame that took longer
+    /// than `FRAME_DEADLINE` is given up on, and the worker restarted. If the
+    /// first frame after that misses it with the same values, no frames are
+    /// asked for until the values, time or playback change.
+    pub fn request_frame(
+        &mut self,
+        now: f64,
+        viewport: Viewport,
+        camera: Option<ViewBox>,
+        time: Option<f64>,
+    ) {
+        let (pending_since, rendering, rendered) = {
+            let connection = self.connection.borrow();
+            (
+                connection.pending_since,
+                connection.rendering,
+                connection.rendered,
+            )
+        };
+        if self.stalled || !rendering {
+            return;
+        }
+        match pending_since {
+            Some(since) if now - since > FRAME_DEADLINE => {
+                let repeated = !rendered && self.missed.as_ref() == Some(&self.values);
+                if let Err(err) = self.restart() {
+                    log::error!("{}", err);
+                }
+                let message = if repeated {
+                    self.stalled = true;
+                    format!(
+                        "A frame exceeded the deadline of {}s again, so rendering stopped. Change a value to try again.",
+                        FRAME_DEADLINE / 1000.0
+                    )
+                } else {
+                    self.missed = Some(self.values.clone());
+                    format!(
+                        "A frame exceeded the deadline of {}s, the module was restarted",
+                        FRAME_DEADLINE / 1000.0
+                    )
+                };
+                (self.on_response)(Response::Error(message));
+            }
+            Some(_) => {}
+            None => {
+                let animated = self.connection.borrow().animated;
+                if self.schedule.due(now, viewport, camera, animated) {
+                    {
+                        let mut connection = self.connection.borrow_mut();
+                        connection.pending_since = Some(now);
+                        connection.pending_time = time;
+                    }
+                    self.send(Request::Render {
+                        viewport,
+                        camera,
+                        time,
+                    });
+                }
+            }
+        }
+    }
+
+    // Replace the worker with a fresh one in the same state. A module that
+    // is still being opened is opened again, and answered as usual.
+    fn restart(&mut self) -> Result<(), String> {
+        self.worker.terminate();
+        let (time, opening, module) = {
+            let connection = self.connection.borrow();
+            (
+                connection.time,
+                connection.opening.clone(),
+                connection.module.clone(),
+            )
+        };
+        let connection = Rc::new(RefCell::new(Connection {
+            restarting: opening.is_none(),
+            time,
+            opening: opening.clone(),
+            module: module.clone(),
+            ..Connection::default()
+        }));
+        let (worker, on_message) = spawn(connection.clone(), self.on_response.clone())?;
+        self.worker = worker;
+        self.connection = connection;
+        self._on_message = on_message;
+        if let Some((module, hash)) = opening {
+            self.send(Request::Open {
+                module,
+                hash,
+                fuel_limit: self.fuel_limit,
+            });
+        } else if let Some((module, hash)) = module {
+            self.send(Request::Open {
+                module,
+                hash,
+                fuel_limit: self.fuel_limit,
+            });
+            self.send(Request::Restore(GuestState {
+                time,
+                paused: self.paused,
+                values: self.values.clone().into_iter().collect(),
+            }));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for GuestWorker {
+    fn drop(&mut self) {
+        self.worker.terminate();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Renders the value of the range_i32 as a digit
+    const MODULE: &str = r#"
+        (module
+            (@custom "rgeometry.schema" "[{\"type\":\"range_i32\",\"name\":\"n\",\"min\":0,\"max\":9,\"default\":3}]")
+            (import "env" "render" (func $render (param i32)))
+            (memory (export "memory") 1)
+            (func (export "request_animation_frame") (param i32)
+                (i32.store8 (i32.const 16) (i32.add (i32.const 48) (local.get 0)))
+                (call $render (i32.const 16)))
+        )"#;
+
+    fn render(guest: &mut Guest) -> String {
+        let request = Request::Render {
+            viewport: Viewport::default(),
+            camera: None,
+            time: None,
+        };
+        match guest.handle(request).pop() {
+            Some(Response::Frame { svg, .. }) => svg,
+            other => panic!("Expected a frame, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn guest_requests() {
+        let mut guest = Guest::default();
+        assert!(matches!(
+            guest.handle(Request::SetTime(1.0))[..],
+            [Response::Error(_)]
+        ));
+        // Reported once
+        assert!(guest.handle(Request::SetTime(1.0)).is_empty());
+        let module = wat::parse_str(MODULE).unwrap();
+        let hash = "0123".to_string();
+        let opened = guest.handle(Request::Open {
+            module,
+            hash,
+            fuel_limit: None,
+        });
+        assert!(matches!(
+            &opened[..],
+            [Response::Opened { hash, schema, .. }] if hash == "0123" && schema.len() == 1
+        ));
+        assert_eq!(render(&mut guest), "3");
+
+        let state = GuestState {
+            time: 2.0,
+            paused: true,
+            values: vec![(0, Value::I32(7)), (0, Value::I32(20))],
+        };
+        let rejected = guest.handle(Request::Restore(state));
+        assert!(matches!(rejected[..], [Response::Rejected(0, _)]));
+        assert_eq!(render(&mut guest), "7");
+        assert!(guest.handle(Request::ResetParameters).is_empty());
+        assert_eq!(render(&mut guest), "3");
+        match guest.handle(Request::SetParameter(0, Value::I32(5)))[..] {
+            [] => assert_eq!(render(&mut guest), "5"),
+            ref other => panic!("{:?}", other),
+        }
+        assert!(matches!(
+            guest.handle(Request::Open {
+                module: b"not wasm".to_vec(),
+                hash: String::new(),
+                fuel_limit: None,
+            })[..],
+            [Response::Error(_)]
+        ));
+        assert!(guest.handle(Request::SetTime(1.0)).is_empty());
+    }
+
+    #[test]
+    fn exports_and_sweeps() {
+        let module = wat::parse_str(MODULE).unwrap();
+        let mut guest = Guest::default();
+        // Sweeps bring their own module
+        let measure = |value| Request::Measure {
+            module: module.clone(),
+            values: Vec::new(),
+            options: SweepOptions {
+                parameter: 0,
+                min: 1,
+                max: 9,
+                steps: 2,
+                samples: 2,
+                time: 0.0,
+                fuel_limit: None,
+                viewport: Viewport::default(),
+            },
+            value,
+        };
+        assert!(matches!(
+            &guest.handle(measure(4))[..],
+            [Response::Measured(point)] if point.value == 4 && point.times.len() == 2
+        ));
+        assert!(matches!(
+            guest.handle(measure(20))[..],
+            [Response::Error(_)]
+        ));
+
+        guest.handle(Request::Open {
+            module: module.clone(),
+            hash: String::new(),
+            fuel_limit: None,
+        });
+        guest.handle(Request::SetParameter(0, Value::I32(6)));
+        let frame = guest.handle(Request::RenderAnimation {
+            options: AnimationOptions::default(),
+            index: 1,
+            viewport: Viewport::default(),
+        });
+        assert!(matches!(&frame[..], [Response::AnimationFrame(1, svg)] if svg == "6"));
+    }
+
+    #[test]
+    fn out_of_fuel() {
+        // Spins for as many iterations as the range_i32 says
+        let module = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"range_i32\",\"min\":0,\"max\":1000000,\"default\":0}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "<svg/>")
+                (func (export "request_animation_frame") (param i32)
+                    (block
+                        (loop
+                            (br_if 1 (i32.eqz (local.get 0)))
+                            (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                            (br 0)))
+                    (call $render (i32.const 16)))
+            )"#;
+        let mut guest = Guest::default();
+        guest.handle(Request::Open {
+            module: wat::parse_str(module).unwrap(),
+            hash: String::new(),
+            fuel_limit: Some(10_000),
+        });
+        assert_eq!(render(&mut guest), "<svg/>");
+        guest.handle(Request::SetParameter(0, Value::I32(1_000_000)));
+        let request = || Request::Render {
+            viewport: Viewport::default(),
+            camera: None,
+            time: None,
+        };
+        assert!(matches!(
+            guest.handle(request())[..],
+            [Response::OutOfFuel(10_000), Response::Frame { .. }]
+        ));
+        // Reported once
+        assert!(matches!(
+            guest.handle(request())[..],
+            [Response::Frame { .. }]
+        ));
+    }
+
+    #[test]
+    fn frame_schedule() {
+        let viewport = Viewport::default();
+        let mut schedule = Schedule::default();
+        // The first frame, then only when something changed
+        assert!(schedule.due(0.0, viewport, None, false));
+        assert!(!schedule.due(16.0, viewport, None, false));
+        schedule.dirty = true;
+        assert!(schedule.due(32.0, viewport, None, false));
+        let resized = Viewport {
+            width: 100.0,
+            ..viewport
+        };
+        assert!(schedule.due(48.0, resized, None, false));
+        assert!(schedule.due(64.0, resized, None, true));
+
+        // At most 20 frames a second, allowing for late browser frames
+        schedule.interval = 50.0;
+        assert!(!schedule.due(100.0, resized, None, true));
+        assert!(schedule.due(113.0, resized, None, true));
+        assert!(!schedule.due(130.0, viewport, None, false));
+        assert!(schedule.due(163.0, viewport, None, false));
+    }
+
+    #[test]
+    fn messages_round_trip() {
+        let messages = [
+            Request::Open {
+                module: vec![0, 97, 115, 109],
+                hash: "0123".to_string(),
+                fuel_limit: Some(1),
+            },
+            Request::Restore(GuestState {
+                time: 1.5,
+                paused: false,
+                values: vec![
+                    (0, Value::PointSet(vec![[1.0, 2.0]])),
+                    (1, Value::Bytes(vec![0, 255])),
+                    (2, Value::Bytes(vec![1])),
+                ],
+            }),
+            Request::Render {
+                viewport: Viewport::default(),
+                camera: Some(ViewBox {
+                    x: 0.0,
+                    y: 1.0,
+                    width: 2.0,
+                    height: 3.0,
+                }),
+                time: Some(2.5),
+            },
+            Request::Measure {
+                module: vec![0, 97, 115, 109],
+                values: vec![(1, Value::Bytes(vec![2]))],
+                options: SweepOptions {
+                    parameter: 0,
+                    min: 1,
+                    max: 9,
+                    steps: 2,
+                    samples: 1,
+                    time: 0.0,
+                    fuel_limit: None,
+                    viewport: Viewport::default(),
+                },
+                value: 3,
+            },
+        ];
+        for (message, count) in messages.into_iter().zip([1, 2, 0, 2]) {
+            // Payloads travel beside the JSON
+            let mut sent = message.clone();
+            let buffers = sent.take_buffers();
+            assert_eq!(buffers.len(), count);
+            let json = serde_json::to_string(&sent).unwrap();
+            let mut received = serde_json::from_str::<Request>(&json).unwrap();
+            received.put_buffers(buffers).unwrap();
+            assert_eq!(received, message);
+        }
+        assert!(Request::ResetParameters.put_buffers(vec![vec![1]]).is_err());
+    }
+}
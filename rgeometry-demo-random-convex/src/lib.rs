@@ -1,31 +1,62 @@
-use rand::rngs::SmallRng;
-use rand::SeedableRng;
+use rand::Rng;
 use rgeometry::data::polygon::PolygonConvex;
-use rgeometry_demo::{define_schema, render, setup_panic_hook};
+use rgeometry_demo::{
+    css_color, define_meta, define_schema, render, rng_from_seed, setup_panic_hook,
+};
 
 define_schema!(
-    r#"
+    r##"
 [
   {
-    "type": "time"
+    "type": "seed",
+    "reroll_every": 1.0,
+    "name": "seed",
+    "label": "Seed"
+  },
+  {
+    "type": "viewport"
+  },
+  {
+    "type": "color",
+    "default": "#ff0000",
+    "name": "fill",
+    "label": "Fill"
   }
-]"#
+]"##
+);
+
+define_meta!(
+    r##"
+{
+  "title": "Random convex polygon",
+  "description": "Generates a new random convex polygon every second, lock the seed to keep one"
+}"##
 );
 
 #[no_mangle]
-pub extern "C" fn request_animation_frame(time: f64) {
+pub extern "C" fn request_animation_frame(
+    seed: i64,
+    width: f32,
+    height: f32,
+    _device_pixel_ratio: f32,
+    fill: i32,
+) {
     setup_panic_hook();
-    // Generate a new polygon every second
-    let seed = (time as i32) / 1;
-    let mut rng = SmallRng::seed_from_u64(seed as u64);
+    let mut rng = rng_from_seed(seed);
 
     // Generate a random convex polygon with 5-10 vertices
-    let n_vertices = 5 + (seed % 6);
-    let polygon = PolygonConvex::<i8>::random(n_vertices as usize, &mut rng);
+    let n_vertices = rng.gen_range(5..=10);
+    let polygon = PolygonConvex::<i8>::random(n_vertices, &mut rng);
 
-    // Convert polygon points to SVG path
+    // Convert polygon points to SVG path, scaling the i8 coordinates to fill
+    // the viewport and centering the polygon
+    let (width, height) = (width as f64, height as f64);
+    let scale = width.min(height) / 256.0;
     let points = polygon.iter_boundary().map(|p| {
-        (*p.x_coord() as f64 + 500.0, *p.y_coord() as f64 + 50.0) // Center the polygon in the 100x100 viewport
+        (
+            *p.x_coord() as f64 * scale + width / 2.0,
+            *p.y_coord() as f64 * scale + height / 2.0,
+        )
     });
 
     // Create SVG path
@@ -41,14 +72,14 @@ pub extern "C" fn request_animation_frame(time: f64) {
 
     let polygon_path = svg::node::element::Path::new()
         .set("d", path_data)
-        .set("fill", "red")
+        .set("fill", css_color(fill))
         .set("stroke", "black")
         .set("stroke-width", "1");
 
     let document = svg::Document::new()
-        .set("width", "1000")
-        .set("height", "100")
-        .set("viewBox", "0 0 1000 100")
+        .set("width", width)
+        .set("height", height)
+        .set("viewBox", (0, 0, width, height))
         .add(polygon_path);
 
     render(document.to_string())
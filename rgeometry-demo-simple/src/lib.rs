@@ -1,4 +1,4 @@
-use rgeometry_demo::{define_schema, render, setup_panic_hook};
+use rgeometry_demo::{define_meta, define_schema, render, set_viewport, setup_panic_hook};
 
 define_schema!(
     r#"
@@ -9,6 +9,19 @@ define_schema!(
 ]"#
 );
 
+define_meta!(
+    r#"
+{
+  "title": "Simple",
+  "description": "A circle showing the elapsed time"
+}"#
+);
+
+#[no_mangle]
+pub extern "C" fn init(width: f32, height: f32) {
+    set_viewport(width, height);
+}
+
 #[no_mangle]
 pub extern "C" fn request_animation_frame(time: f64) {
     setup_panic_hook();
@@ -1,50 +1,185 @@
+use rand::rngs::SmallRng;
+use rand::SeedableRng;
 use std::panic;
+use std::sync::atomic::{AtomicU32, Ordering};
 
+/// Embeds the JSON parameter schema in the `rgeometry.schema` custom section,
+/// where the host can read it without instantiating the module.
 #[macro_export]
 macro_rules! define_schema {
     ($schema:expr) => {
-        const _SCHEMA_STR: &str = concat!($schema, "\0");
-        #[no_mangle]
-        pub static SCHEMA: [u8; _SCHEMA_STR.len()] = match _SCHEMA_STR.as_bytes().first_chunk() {
+        const _SCHEMA_STR: &str = $schema;
+        #[used]
+        #[link_section = "rgeometry.schema"]
+        static _SCHEMA: [u8; _SCHEMA_STR.len()] = match _SCHEMA_STR.as_bytes().first_chunk() {
             Some(chunk) => *chunk,
             None => unreachable!(),
         };
     };
 }
 
+/// Embeds JSON metadata (`title`, `description`, `author` and `license`, all
+/// optional) in the `rgeometry.meta` custom section.
+#[macro_export]
+macro_rules! define_meta {
+    ($meta:expr) => {
+        const _META_STR: &str = $meta;
+        #[used]
+        #[link_section = "rgeometry.meta"]
+        static _META: [u8; _META_STR.len()] = match _META_STR.as_bytes().first_chunk() {
+            Some(chunk) => *chunk,
+            None => unreachable!(),
+        };
+    };
+}
+
+/// Exports the `alloc` and `dealloc` functions the host uses to pass
+/// variable-sized parameters, such as point sets, into guest memory. The host
+/// keeps a buffer until its parameter changes, so modules must not modify
+/// them.
+#[macro_export]
+macro_rules! export_allocator {
+    () => {
+        #[no_mangle]
+        pub extern "C" fn alloc(size: i32) -> i32 {
+            $crate::alloc_buffer(size as usize) as i32
+        }
+
+        #[no_mangle]
+        pub extern "C" fn dealloc(ptr: i32, size: i32) {
+            unsafe { $crate::dealloc_buffer(ptr as *mut u8, size as usize) }
+        }
+    };
+}
+
+const BUFFER_ALIGN: usize = 8;
+
+/// Allocate `size` bytes for the host to write a parameter into. Returns null
+/// if the memory can't be allocated, which the host reports as an error.
+pub fn alloc_buffer(size: usize) -> *mut u8 {
+    if size == 0 {
+        return BUFFER_ALIGN as *mut u8;
+    }
+    match std::alloc::Layout::from_size_align(size, BUFFER_ALIGN) {
+        Ok(layout) => unsafe { std::alloc::alloc(layout) },
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// Free a buffer returned by `alloc_buffer`.
+///
+/// # Safety
+/// `ptr` must come from `alloc_buffer(size)` and not have been freed yet.
+pub unsafe fn dealloc_buffer(ptr: *mut u8, size: usize) {
+    if size == 0 {
+        return;
+    }
+    let layout = std::alloc::Layout::from_size_align(size, BUFFER_ALIGN).unwrap();
+    std::alloc::dealloc(ptr, layout)
+}
+
+/// View a `point_set` parameter, passed as a pointer and a point count.
+///
+/// # Safety
+/// `ptr` and `len` must be the arguments the host passed for a `point_set`
+/// entry, and the slice must not outlive the current frame.
+pub unsafe fn point_set<'a>(ptr: *const [f32; 2], len: usize) -> &'a [[f32; 2]] {
+    if len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(ptr, len)
+}
+
+/// View a `file` parameter, passed as a pointer and a length in bytes.
+///
+/// # Safety
+/// `ptr` and `len` must be the arguments the host passed for a `file` or
+/// `text` entry, and the slice must not outlive the current frame.
+pub unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> &'a [u8] {
+    if len == 0 {
+        return &[];
+    }
+    std::slice::from_raw_parts(ptr, len)
+}
+
+/// View a `text` parameter. The host always passes valid UTF-8.
+///
+/// # Safety
+/// Same as `bytes`.
+pub unsafe fn text<'a>(ptr: *const u8, len: usize) -> &'a str {
+    std::str::from_utf8(bytes(ptr, len)).unwrap_or_default()
+}
+
 extern "C" {
     #[link_name = "render"]
     fn c_render(s: *const std::ffi::c_char);
+    #[link_name = "static_frame"]
+    fn c_static_frame();
+}
+
+/// Tells the host that the frame being rendered doesn't change with time, so
+/// it isn't rendered again until a parameter or the viewport changes. Call it
+/// from `request_animation_frame`, e.g. once an animation has finished.
+pub fn static_frame() {
+    unsafe { c_static_frame() }
 }
 
 pub fn render(s: impl Into<Vec<u8>>) {
     unsafe {
         match std::ffi::CString::new(s) {
             Ok(s) => c_render(s.as_ptr()),
-            Err(_) => c_render(
-                std::ffi::CStr::from_bytes_with_nul_unchecked(
-                    b"Error: String contains interior null bytes\0",
-                )
-                .as_ptr(),
-            ),
+            Err(_) => c_render(c"Error: String contains interior null bytes".as_ptr()),
         }
     }
 }
 
+// Viewer size as f32 bits, updated through `set_viewport`.
+// Starts at the host's default viewport.
+static VIEWPORT_WIDTH: AtomicU32 = AtomicU32::new(800f32.to_bits());
+static VIEWPORT_HEIGHT: AtomicU32 = AtomicU32::new(600f32.to_bits());
+
+/// Records the viewer size. Call this from the module's `init(width, height)`
+/// export, which the host runs after instantiation and on every resize.
+pub fn set_viewport(width: f32, height: f32) {
+    VIEWPORT_WIDTH.store(width.to_bits(), Ordering::Relaxed);
+    VIEWPORT_HEIGHT.store(height.to_bits(), Ordering::Relaxed);
+}
+
+/// The last size passed to `set_viewport`, or 800x600 if it was never called.
+pub fn viewport() -> (f32, f32) {
+    (
+        f32::from_bits(VIEWPORT_WIDTH.load(Ordering::Relaxed)),
+        f32::from_bits(VIEWPORT_HEIGHT.load(Ordering::Relaxed)),
+    )
+}
+
+/// Format a color parameter (packed as `0xRRGGBBAA`) for use in SVG
+/// attributes such as `fill` and `stroke`.
+pub fn css_color(rgba: i32) -> String {
+    format!("#{:08x}", rgba as u32)
+}
+
+/// Random number generator for a `seed` parameter. The same seed always
+/// yields the same sequence, so a figure can be reproduced from its seed.
+pub fn rng_from_seed(seed: i64) -> SmallRng {
+    SmallRng::seed_from_u64(seed as u64)
+}
+
 pub fn setup_panic_hook() {
     panic::set_hook(Box::new(|panic_info| {
+        let (width, height) = viewport();
         let msg = format!("Panic: {}", panic_info);
         let text = svg::node::element::Text::new(msg)
-            .set("x", "50")
-            .set("y", "50")
+            .set("x", width / 2.0)
+            .set("y", height / 2.0)
             .set("text-anchor", "middle")
             .set("dominant-baseline", "middle")
             .set("fill", "red");
 
         let document = svg::Document::new()
-            .set("width", "1000")
-            .set("height", "1000")
-            .set("viewBox", "0 0 1000 1000")
+            .set("width", width)
+            .set("height", height)
+            .set("viewBox", (0, 0, width, height))
             .add(text);
 
         render(document.to_string());
@@ -0,0 +1,580 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+use crate::export::{parse, png_encoder, rasterize, rgba, FrameInfo};
+use crate::wasm::Wasm;
+use gif::{AnyExtension, DisposalMethod, Repeat};
+use resvg::tiny_skia::{Color, IntSize};
+use serde::{Deserialize, Serialize};
+use svg2pdf::usvg;
+
+/// Largest width or height of an animation, in pixels.
+pub const MAX_ANIMATION_SIZE: u32 = 4096;
+
+/// Largest number of frames in an animation.
+pub const MAX_FRAMES: usize = 3600;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationFormat {
+    Gif,
+    Apng,
+}
+
+impl AnimationFormat {
+    pub const ALL: [AnimationFormat; 2] = [AnimationFormat::Gif, AnimationFormat::Apng];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "GIF",
+            AnimationFormat::Apng => "APNG",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::Apng => "png",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "image/gif",
+            AnimationFormat::Apng => "image/apng",
+        }
+    }
+
+    /// Highest frame rate that plays at its speed. GIF delays are whole
+    /// hundredths of a second, and browsers stretch delays below 2/100 s.
+    pub fn max_fps(self) -> f64 {
+        match self {
+            AnimationFormat::Gif => 50.0,
+            AnimationFormat::Apng => 100.0,
+        }
+    }
+}
+
+/// Which frames of an animation are rendered, and how big it is.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct AnimationOptions {
+    /// Time of the first frame, in seconds
+    pub start: f64,
+    /// Time the animation ends at. No frame is rendered at `end` itself, so
+    /// looping animations don't show the same frame twice.
+    pub end: f64,
+    pub fps: f64,
+    /// Width in pixels. The height follows from the first frame. Frames too
+    /// tall for `MAX_ANIMATION_SIZE` are scaled down as a whole.
+    pub width: u32,
+    /// How often the animation plays, None for forever
+    pub loops: Option<u16>,
+}
+
+impl Default for AnimationOptions {
+    fn default() -> Self {
+        AnimationOptions {
+            start: 0.0,
+            end: 5.0,
+            fps: 25.0,
+            width: 600,
+            loops: None,
+        }
+    }
+}
+
+impl AnimationOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.start >= 0.0 && self.end > self.start) {
+            return Err("The end must come after the start, which can't be negative".to_string());
+        }
+        if !(self.fps > 0.0 && self.fps <= 100.0) {
+            return Err("The frame rate must be above 0 and at most 100".to_string());
+        }
+        if !(1..=MAX_ANIMATION_SIZE).contains(&self.width) {
+            return Err(format!(
+                "The width must be between 1 and {}",
+                MAX_ANIMATION_SIZE
+            ));
+        }
+        if self.loops == Some(0) {
+            return Err("The animation must play at least once".to_string());
+        }
+        let frames = self.frame_count();
+        if frames > MAX_FRAMES {
+            return Err(format!(
+                "{} frames exceeds the limit of {}",
+                frames, MAX_FRAMES
+            ));
+        }
+        Ok(())
+    }
+
+    /// `validate`, and check that `format` can play the frame rate.
+    pub fn validate_for(&self, format: AnimationFormat) -> Result<(), String> {
+        self.validate()?;
+        if self.fps > format.max_fps() {
+            return Err(format!(
+                "{} animations play at most {} frames per second",
+                format.name(),
+                format.max_fps()
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn frame_count(&self) -> usize {
+        ((self.end - self.start) * self.fps).round().max(1.0) as usize
+    }
+
+    /// Times the frames are rendered at.
+    pub fn times(&self) -> impl Iterator<Item = f64> + '_ {
+        (0..self.frame_count()).map(|i| self.start + i as f64 / self.fps)
+    }
+}
+
+/// Render frame `index` of an animation. Frames must be rendered in order,
+/// starting with the first, for delta times to be right.
+pub fn render_frame(
+    wasm: &mut Wasm,
+    options: &AnimationOptions,
+    index: usize,
+) -> Result<String, String> {
+    let time = options.start + index as f64 / options.fps;
+    let delta_time = if index == 0 { 0.0 } else { 1.0 / options.fps };
+    let svg = wasm.render_at(time, delta_time);
+    if wasm.is_out_of_fuel() {
+        Err(format!("The frame at {}s ran out of fuel", time))
+    } else if wasm.has_failed() {
+        Err(format!("Failed to render the frame at {}s", time))
+    } else {
+        Ok(svg)
+    }
+}
+
+/// Render the frames of an animation at fixed times. For the frames to be
+/// reproducible, `wasm` should be a fresh instance.
+pub fn render_frames(wasm: &mut Wasm, options: &AnimationOptions) -> Result<Vec<String>, String> {
+    options.validate()?;
+    (0..options.frame_count())
+        .map(|index| render_frame(wasm, options, index))
+        .collect()
+}
+
+// Scale a frame to the width of the animation, or less if that makes it
+// taller than the limit, keeping its aspect ratio
+fn animation_size(tree: &usvg::Tree, width: u32) -> Result<IntSize, String> {
+    let (frame_width, frame_height) = (tree.size.width(), tree.size.height());
+    let scale = (width as f32 / frame_width).min(MAX_ANIMATION_SIZE as f32 / frame_height);
+    IntSize::from_wh(
+        (frame_width * scale).round().max(1.0) as u32,
+        (frame_height * scale).round().max(1.0) as u32,
+    )
+    .ok_or_else(|| "Invalid animation size".to_string())
+}
+
+// Where the APNG writer puts the animation. The writer holds on to its
+// output until it is finished, so the encoder keeps a handle to the bytes.
+#[derive(Clone, Default)]
+struct Output(Rc<RefCell<Vec<u8>>>);
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// An animation being encoded, with the frames added so far
+enum Stream {
+    Gif(gif::Encoder<Vec<u8>>),
+    Apng(png::Writer<Output>, Output),
+}
+
+// The frame rate as an APNG frame delay of 1/fps seconds, if it is a whole
+// number
+fn whole_fps(fps: f64) -> Option<u16> {
+    (fps.fract() == 0.0 && fps <= u16::MAX as f64).then_some(fps as u16)
+}
+
+// Write the header of an animation of `size`
+fn start(
+    format: AnimationFormat,
+    options: &AnimationOptions,
+    info: &FrameInfo,
+    size: IntSize,
+) -> Result<Stream, String> {
+    match format {
+        AnimationFormat::Gif => {
+            let mut encoder =
+                gif::Encoder::new(Vec::new(), size.width() as u16, size.height() as u16, &[])
+                    .map_err(|e| e.to_string())?;
+            encoder
+                .set_repeat(match options.loops {
+                    None => Repeat::Infinite,
+                    Some(plays) => Repeat::Finite(plays - 1),
+                })
+                .map_err(|e| e.to_string())?;
+            // A comment extension with the frame info
+            encoder
+                .write_raw_extension(AnyExtension(0xFE), &[info.description().as_bytes()])
+                .map_err(|e| e.to_string())?;
+            Ok(Stream::Gif(encoder))
+        }
+        AnimationFormat::Apng => {
+            let output = Output::default();
+            let mut encoder = png_encoder(output.clone(), size, info)?;
+            let plays = options.loops.map_or(0, u32::from);
+            encoder
+                .set_animated(options.frame_count() as u32, plays)
+                .map_err(|e| e.to_string())?;
+            // Other frame rates get a delay per frame, see `add_frame`
+            if let Some(fps) = whole_fps(options.fps) {
+                encoder.set_frame_delay(1, fps).map_err(|e| e.to_string())?;
+            }
+            let writer = encoder.write_header().map_err(|e| e.to_string())?;
+            Ok(Stream::Apng(writer, output))
+        }
+    }
+}
+
+/// Builds an animation one frame at a time, so that callers can spread the
+/// work out and report progress. Frames are encoded as they are added, so
+/// only the encoded animation is kept. GIFs have no partial transparency, so
+/// their frames are drawn on white.
+pub struct AnimationEncoder {
+    format: AnimationFormat,
+    options: AnimationOptions,
+    info: FrameInfo,
+    // Started by the first frame, at the size all frames are drawn at
+    stream: Option<(IntSize, Stream)>,
+    // Number of frames added
+    frames: usize,
+}
+
+impl AnimationEncoder {
+    pub fn new(
+        format: AnimationFormat,
+        options: &AnimationOptions,
+        info: &FrameInfo,
+    ) -> Result<Self, String> {
+        options.validate_for(format)?;
+        Ok(AnimationEncoder {
+            format,
+            options: *options,
+            info: info.clone(),
+            stream: None,
+            frames: 0,
+        })
+    }
+
+    /// Rasterize and encode the next frame. The first one decides the size
+    /// of the animation.
+    pub fn add_frame(&mut self, svg: &str) -> Result<(), String> {
+        let total = self.options.frame_count();
+        if self.frames == total {
+            return Err(format!("The animation has all of its {} frames", total));
+        }
+        let tree = parse(svg)?;
+        let (size, stream) = match &mut self.stream {
+            Some((size, stream)) => (*size, stream),
+            None => {
+                let size = animation_size(&tree, self.options.width)?;
+                let stream = start(self.format, &self.options, &self.info, size)?;
+                (size, &mut self.stream.insert((size, stream)).1)
+            }
+        };
+        // A frame lasts until the rounded time of the next one, in units of
+        // 1/scale seconds, so the rounding errors don't add up over the
+        // animation
+        let (index, fps) = (self.frames, self.options.fps);
+        let end = |index: usize, scale: f64| (index as f64 * scale / fps).round();
+        let delay = |scale: f64| (end(index + 1, scale) - end(index, scale)).min(u16::MAX as f64);
+        match stream {
+            Stream::Gif(encoder) => {
+                let mut data = rgba(&rasterize(&tree, size, Some(Color::WHITE))?);
+                let mut frame = gif::Frame::from_rgba_speed(
+                    size.width() as u16,
+                    size.height() as u16,
+                    &mut data,
+                    10,
+                );
+                frame.dispose = DisposalMethod::Background;
+                // GIF delays are in hundredths of a second
+                frame.delay = (delay(100.0) as u16).max(1);
+                encoder.write_frame(&frame).map_err(|e| e.to_string())?;
+            }
+            Stream::Apng(writer, _) => {
+                if whole_fps(fps).is_none() {
+                    writer
+                        .set_frame_delay(delay(1000.0) as u16, 1000)
+                        .map_err(|e| e.to_string())?;
+                }
+                writer
+                    .write_image_data(&rgba(&rasterize(&tree, size, None)?))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Finish the animation, once all of its frames are added.
+    pub fn finish(self) -> Result<Vec<u8>, String> {
+        let total = self.options.frame_count();
+        let Some((_, stream)) = self.stream.filter(|_| self.frames == total) else {
+            return Err(format!(
+                "The animation has {} of its {} frames",
+                self.frames, total
+            ));
+        };
+        match stream {
+            Stream::Gif(encoder) => encoder.into_inner().map_err(|e| e.to_string()),
+            Stream::Apng(writer, output) => {
+                writer.finish().map_err(|e| e.to_string())?;
+                Ok(output.0.take())
+            }
+        }
+    }
+}
+
+/// Encode rendered frames as an animation.
+pub fn encode(
+    frames: &[String],
+    format: AnimationFormat,
+    options: &AnimationOptions,
+    info: &FrameInfo,
+) -> Result<Vec<u8>, String> {
+    let mut encoder = AnimationEncoder::new(format, options, info)?;
+    for svg in frames {
+        encoder.add_frame(svg)?;
+    }
+    encoder.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A 10x10 frame with a red square whose x position is `x`
+    fn frame(x: u32) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><rect x="{}" width="2" height="10" fill="red"/></svg>"#,
+            x
+        )
+    }
+
+    fn options() -> AnimationOptions {
+        AnimationOptions {
+            start: 1.0,
+            end: 2.0,
+            fps: 4.0,
+            width: 20,
+            loops: Some(2),
+        }
+    }
+
+    #[test]
+    fn frame_times() {
+        assert_eq!(
+            options().times().collect::<Vec<_>>(),
+            [1.0, 1.25, 1.5, 1.75]
+        );
+        assert!(options().validate().is_ok());
+        let invalid = [
+            AnimationOptions {
+                end: 1.0,
+                ..options()
+            },
+            AnimationOptions {
+                fps: 0.0,
+                ..options()
+            },
+            AnimationOptions {
+                width: 0,
+                ..options()
+            },
+            AnimationOptions {
+                loops: Some(0),
+                ..options()
+            },
+            AnimationOptions {
+                end: 1000.0,
+                ..options()
+            },
+        ];
+        for options in invalid {
+            assert!(options.validate().is_err(), "{:?}", options);
+        }
+
+        let fast = AnimationOptions {
+            fps: 60.0,
+            ..options()
+        };
+        assert!(fast.validate_for(AnimationFormat::Apng).is_ok());
+        assert!(fast.validate_for(AnimationFormat::Gif).is_err());
+    }
+
+    #[test]
+    fn gif_frames() {
+        let frames: Vec<String> = (0..4).map(|i| frame(i * 2)).collect();
+        let bytes = encode(
+            &frames,
+            AnimationFormat::Gif,
+            &options(),
+            &FrameInfo::default(),
+        )
+        .unwrap();
+        let mut options = gif::DecodeOptions::new();
+        options.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = options.read_info(bytes.as_slice()).unwrap();
+        assert_eq!((decoder.width(), decoder.height()), (20, 20));
+        let mut count = 0;
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            assert_eq!(frame.delay, 25);
+            // The square moved 4 pixels to the right with every frame
+            let red = frame.buffer[..20 * 4]
+                .chunks(4)
+                .position(|pixel| pixel[0] > 200 && pixel[1] < 50)
+                .unwrap();
+            assert_eq!(red, count * 4);
+            count += 1;
+        }
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn gif_delays_add_up() {
+        // A third of a second at 30 fps can't be split into equal delays
+        let options = AnimationOptions {
+            start: 0.0,
+            end: 1.0 / 3.0,
+            fps: 30.0,
+            ..options()
+        };
+        let frames: Vec<String> = (0..options.frame_count()).map(|_| frame(0)).collect();
+        let bytes = encode(
+            &frames,
+            AnimationFormat::Gif,
+            &options,
+            &FrameInfo::default(),
+        )
+        .unwrap();
+        let mut decoder = gif::DecodeOptions::new()
+            .read_info(bytes.as_slice())
+            .unwrap();
+        let mut delays = Vec::new();
+        while let Some(frame) = decoder.read_next_frame().unwrap() {
+            delays.push(frame.delay);
+        }
+        assert_eq!(delays, [3, 4, 3, 3, 4, 3, 3, 4, 3, 3]);
+    }
+
+    #[test]
+    fn tall_frames_keep_aspect_ratio() {
+        let tall = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 1000"><rect width="10" height="1000" fill="red"/></svg>"#;
+        let options = AnimationOptions {
+            end: 1.25,
+            width: 100,
+            ..options()
+        };
+        let bytes = encode(
+            &[tall.to_string()],
+            AnimationFormat::Apng,
+            &options,
+            &FrameInfo::default(),
+        )
+        .unwrap();
+        let reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        assert_eq!(
+            (reader.info().width, reader.info().height),
+            (41, MAX_ANIMATION_SIZE)
+        );
+    }
+
+    #[test]
+    fn apng_frames() {
+        let frames: Vec<String> = (0..4).map(|i| frame(i * 2)).collect();
+        let bytes = encode(
+            &frames,
+            AnimationFormat::Apng,
+            &options(),
+            &FrameInfo::default(),
+        )
+        .unwrap();
+        let decoder = png::Decoder::new(bytes.as_slice());
+        let reader = decoder.read_info().unwrap();
+        let control = reader.info().animation_control.unwrap();
+        assert_eq!((control.num_frames, control.num_plays), (4, 2));
+        assert_eq!((reader.info().width, reader.info().height), (20, 20));
+        assert_eq!(apng_delays(&bytes), [(1, 4); 4]);
+
+        // Every frame is announced up front
+        let mut encoder =
+            AnimationEncoder::new(AnimationFormat::Apng, &options(), &FrameInfo::default())
+                .unwrap();
+        encoder.add_frame(&frames[0]).unwrap();
+        assert!(encoder.finish().is_err());
+    }
+
+    // Delays of the frames of an APNG, as fractions of a second
+    fn apng_delays(bytes: &[u8]) -> Vec<(u16, u16)> {
+        let mut reader = png::Decoder::new(bytes).read_info().unwrap();
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let mut delays = Vec::new();
+        while reader.next_frame(&mut buffer).is_ok() {
+            let control = reader.info().frame_control.unwrap();
+            delays.push((control.delay_num, control.delay_den));
+        }
+        delays
+    }
+
+    #[test]
+    fn apng_delays_add_up() {
+        // 7.5 fps has no whole delay in milliseconds
+        let options = AnimationOptions {
+            start: 0.0,
+            end: 0.4,
+            fps: 7.5,
+            ..options()
+        };
+        let frames: Vec<String> = (0..options.frame_count()).map(|_| frame(0)).collect();
+        let bytes = encode(
+            &frames,
+            AnimationFormat::Apng,
+            &options,
+            &FrameInfo::default(),
+        )
+        .unwrap();
+        assert_eq!(apng_delays(&bytes), [(133, 1000), (134, 1000), (133, 1000)]);
+    }
+
+    #[test]
+    fn deterministic_frames() {
+        // Renders the time in tenths of a second as a single digit
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"time\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param f64)
+                    (i32.store8 (i32.const 16)
+                        (i32.add (i32.const 48)
+                            (i32.trunc_f64_u (f64.nearest (f64.mul (local.get 0) (f64.const 10))))))
+                    (call $render (i32.const 16)))
+            )"#;
+        let bytes = wat::parse_str(wat).unwrap();
+        let options = AnimationOptions {
+            start: 0.2,
+            end: 0.6,
+            fps: 10.0,
+            ..AnimationOptions::default()
+        };
+        let render = || render_frames(&mut Wasm::new(&bytes).unwrap(), &options).unwrap();
+        assert_eq!(render(), ["2", "3", "4", "5"]);
+        assert_eq!(render(), render());
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::viewbox::ViewBox;
+use svg::node::element::tag::Type;
+use svg::node::Attributes;
+use svg::parser::Event;
+
+/// Color of the elements only the left frame draws.
+pub const ONLY_LEFT_COLOR: &str = "#d62728";
+
+/// Color of the elements only the right frame draws.
+pub const ONLY_RIGHT_COLOR: &str = "#1f77b4";
+
+/// Opacity of the elements both frames draw.
+pub const SHARED_OPACITY: f32 = 0.3;
+
+// Elements that only group others, whose children are compared one by one
+const CONTAINERS: [&str; 4] = ["svg", "g", "a", "switch"];
+
+// Elements that are only drawn where they are referenced
+const DEFINITIONS: [&str; 10] = [
+    "defs",
+    "style",
+    "marker",
+    "clipPath",
+    "mask",
+    "linearGradient",
+    "radialGradient",
+    "pattern",
+    "symbol",
+    "filter",
+];
+
+// Elements that aren't drawn at all
+const IGNORED: [&str; 3] = ["title", "desc", "metadata"];
+
+/// Two frames drawn on top of each other, and how many of their elements
+/// they have in common.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Overlay {
+    pub svg: String,
+    pub shared: usize,
+    pub only_left: usize,
+    pub only_right: usize,
+}
+
+// A drawn element: the open tags of the groups around it, and its source
+// with its content. Elements are the same if both are equal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Item {
+    groups: Vec<String>,
+    source: String,
+}
+
+#[derive(Debug, Default)]
+struct Frame {
+    // Attributes of the root <svg> element
+    root: Vec<(String, String)>,
+    definitions: Vec<String>,
+    items: Vec<Item>,
+}
+
+// Write a tag with its attributes sorted, so equal elements read the same
+fn write_tag(out: &mut String, name: &str, kind: Type, attributes: &Attributes) {
+    if kind == Type::End {
+        _ = write!(out, "</{}>", name);
+        return;
+    }
+    let mut attributes: Vec<_> = attributes.iter().collect();
+    attributes.sort_by(|a, b| a.0.cmp(b.0));
+    out.push('<');
+    out.push_str(name);
+    for (key, value) in attributes {
+        write_attribute(out, key, value);
+    }
+    out.push_str(if kind == Type::Empty { "/>" } else { ">" });
+}
+
+// Attribute values are kept as written, so quote them with whichever quote
+// they don't contain
+fn write_attribute(out: &mut String, key: &str, value: &str) {
+    if value.contains('"') {
+        _ = write!(out, " {}='{}'", key, value);
+    } else {
+        _ = write!(out, " {}=\"{}\"", key, value);
+    }
+}
+
+// What is being copied: an element and how many of its tags are open
+enum Capture {
+    Item(String, usize),
+    Definition(String, usize),
+    Ignored(usize),
+}
+
+fn parse(svg: &str) -> Result<Frame, String> {
+    let mut frame = Frame::default();
+    let mut root = true;
+    // Open tags of the groups around the current element
+    let mut groups: Vec<String> = Vec::new();
+    let mut capture: Option<Capture> = None;
+    for event in svg::read(svg).map_err(|e| e.to_string())? {
+        match event {
+            Event::Tag(name, kind, attributes) => {
+                if let Some(current) = &mut capture {
+                    let (source, depth) = match current {
+                        Capture::Item(source, depth) | Capture::Definition(source, depth) => {
+                            (Some(source), depth)
+                        }
+                        Capture::Ignored(depth) => (None, depth),
+                    };
+                    if let Some(source) = source {
+                        write_tag(source, name, kind, &attributes);
+                    }
+                    match kind {
+                        Type::Start => *depth += 1,
+                        Type::End => *depth -= 1,
+                        Type::Empty => {}
+                    }
+                    if *depth == 0 {
+                        match capture.take() {
+                            Some(Capture::Item(source, _)) => frame.items.push(Item {
+                                groups: groups.clone(),
+                                source,
+                            }),
+                            Some(Capture::Definition(source, _)) => frame.definitions.push(source),
+                            _ => {}
+                        }
+                    }
+                } else if root && name == "svg" {
+                    root = false;
+                    let mut attributes: Vec<_> = attributes
+                        .iter()
+                        .map(|(key, value)| (key.clone(), value.to_string()))
+                        .collect();
+                    attributes.sort();
+                    frame.root = attributes;
+                    if kind == Type::Empty {
+                        break;
+                    }
+                } else if CONTAINERS.contains(&name) {
+                    match kind {
+                        Type::Start => {
+                            let mut tag = String::new();
+                            write_tag(&mut tag, name, kind, &attributes);
+                            groups.push(tag);
+                        }
+                        Type::End => _ = groups.pop(),
+                        Type::Empty => {}
+                    }
+                } else {
+                    let mut source = String::new();
+                    write_tag(&mut source, name, kind, &attributes);
+                    let depth = usize::from(kind == Type::Start);
+                    let current = if IGNORED.contains(&name) {
+                        Capture::Ignored(depth)
+                    } else if DEFINITIONS.contains(&name) {
+                        Capture::Definition(source, depth)
+                    } else {
+                        Capture::Item(source, depth)
+                    };
+                    match current {
+                        Capture::Item(source, 0) => frame.items.push(Item {
+                            groups: groups.clone(),
+                            source,
+                        }),
+                        Capture::Definition(source, 0) => frame.definitions.push(source),
+                        Capture::Ignored(0) => {}
+                        current => capture = Some(current),
+                    }
+                }
+            }
+            Event::Text(text) => {
+                if let Some(Capture::Item(source, _) | Capture::Definition(source, _)) =
+                    &mut capture
+                {
+                    source.push_str(text);
+                }
+            }
+            Event::Error(err) => return Err(format!("Failed to parse frame: {}", err)),
+            Event::Comment(_) | Event::Declaration(_) | Event::Instruction(_) => {}
+        }
+    }
+    if root {
+        return Err("Frame has no <svg> element".to_string());
+    }
+    Ok(frame)
+}
+
+fn union(a: ViewBox, b: ViewBox) -> ViewBox {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    ViewBox {
+        x,
+        y,
+        width: (a.x + a.width).max(b.x + b.width) - x,
+        height: (a.y + a.height).max(b.y + b.height) - y,
+    }
+}
+
+// Write items wrapped in their groups
+fn write_items<'a>(out: &mut String, items: impl Iterator<Item = &'a Item>) {
+    for item in items {
+        for group in &item.groups {
+            out.push_str(group);
+        }
+        out.push_str(&item.source);
+        for group in item.groups.iter().rev() {
+            // The name is what follows '<' up to the first space or '>'
+            let name = group[1..]
+                .split(|c: char| c.is_whitespace() || c == '>')
+                .next()
+                .unwrap_or_default();
+            _ = write!(out, "</{}>", name);
+        }
+    }
+}
+
+/// Draw two frames on top of each other, to see where they differ. Elements
+/// both frames draw, compared by their attributes, content and the groups
+/// around them, are faded to `SHARED_OPACITY`. Elements only one frame
+/// draws are recolored, in `ONLY_LEFT_COLOR` or `ONLY_RIGHT_COLOR`.
+///
+/// The result has the root attributes of the left frame, with a view box
+/// that covers both and its size grown to match. The definitions of both
+/// frames, such as gradients and markers, are kept; where their ids clash,
+/// the left frame's win.
+pub fn overlay(left: &str, right: &str) -> Result<Overlay, String> {
+    let left_frame = parse(left)?;
+    let right_frame = parse(right)?;
+    let left_box = ViewBox::from_svg(left);
+    let view_box = match (left_box, ViewBox::from_svg(right)) {
+        (Some(a), Some(b)) => Some(union(a, b)),
+        (a, b) => a.or(b),
+    };
+    // Scale the size of the left frame with its view box. Sizes that aren't
+    // plain lengths are dropped, leaving the size to the view box.
+    let size = |name: &str, extent: fn(&ViewBox) -> f64| -> Option<String> {
+        let (_, value) = left_frame.root.iter().find(|(key, _)| key == name)?;
+        let length: f64 = value.trim().trim_end_matches("px").parse().ok()?;
+        let scale = extent(&view_box?) / extent(&left_box?);
+        Some((length * scale).to_string())
+    };
+
+    // Pair up equal elements, each at most once
+    let mut unmatched: HashMap<&Item, usize> = HashMap::new();
+    for item in &right_frame.items {
+        *unmatched.entry(item).or_default() += 1;
+    }
+    let mut shared = Vec::new();
+    let mut only_left = Vec::new();
+    for item in &left_frame.items {
+        match unmatched.get_mut(item) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                shared.push(item);
+            }
+            _ => only_left.push(item),
+        }
+    }
+    let only_right: Vec<&Item> = right_frame
+        .items
+        .iter()
+        .filter(|item| match unmatched.get_mut(item) {
+            Some(count) if *count > 0 => {
+                *count -= 1;
+                true
+            }
+            _ => false,
+        })
+        .collect();
+
+    let mut svg = String::from("<svg");
+    let mut has_namespace = false;
+    for (key, value) in &left_frame.root {
+        match key.as_str() {
+            "viewBox" | "width" | "height" if view_box.is_some() => continue,
+            "xmlns" => has_namespace = true,
+            _ => {}
+        }
+        write_attribute(&mut svg, key, value);
+    }
+    if !has_namespace {
+        write_attribute(&mut svg, "xmlns", "http://www.w3.org/2000/svg");
+    }
+    if let Some(view_box) = view_box {
+        let width = size("width", |view_box| view_box.width);
+        let height = size("height", |view_box| view_box.height);
+        for (key, value) in [("width", width), ("height", height)] {
+            if let Some(value) = value {
+                write_attribute(&mut svg, key, &value);
+            }
+        }
+        write_attribute(&mut svg, "viewBox", &view_box.to_string());
+    }
+    svg.push('>');
+    for definition in left_frame
+        .definitions
+        .iter()
+        .chain(&right_frame.definitions)
+    {
+        svg.push_str(definition);
+    }
+    // Recolor by flooding the shapes, so fills, strokes and text all change.
+    // The filter region must not depend on the bounding box, which is empty
+    // for horizontal and vertical lines.
+    let region = view_box.map_or(
+        r#"x="-10%" y="-10%" width="120%" height="120%""#.to_string(),
+        |b| {
+            format!(
+                r#"x="{}" y="{}" width="{}" height="{}""#,
+                b.x, b.y, b.width, b.height
+            )
+        },
+    );
+    svg.push_str("<defs>");
+    for (id, color) in [
+        ("only-left", ONLY_LEFT_COLOR),
+        ("only-right", ONLY_RIGHT_COLOR),
+    ] {
+        _ = write!(
+            svg,
+            r#"<filter id="rgeometry-{}" filterUnits="userSpaceOnUse" {}><feFlood flood-color="{}" flood-opacity="0.7"/><feComposite in2="SourceGraphic" operator="in"/></filter>"#,
+            id, region, color
+        );
+    }
+    svg.push_str("</defs>");
+    _ = write!(svg, r#"<g opacity="{}">"#, SHARED_OPACITY);
+    write_items(&mut svg, shared.iter().copied());
+    svg.push_str(r#"</g><g filter="url(#rgeometry-only-left)">"#);
+    write_items(&mut svg, only_left.iter().copied());
+    svg.push_str(r#"</g><g filter="url(#rgeometry-only-right)">"#);
+    write_items(&mut svg, only_right.iter().copied());
+    svg.push_str("</g></svg>");
+
+    Ok(Overlay {
+        svg,
+        shared: shared.len(),
+        only_left: only_left.len(),
+        only_right: only_right.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(body: &str) -> String {
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="0 0 100 50">{}</svg>"#,
+            body
+        )
+    }
+
+    #[test]
+    fn shared_and_different_elements() {
+        let left = frame(
+            r#"<circle cx="1" cy="2" r="3"/><g transform="scale(2)"><text x="1">a &amp; b</text><line x1="0" y1="0" x2="1" y2="0"/></g>"#,
+        );
+        // Same circle with the attributes in another order, same text, a
+        // moved line and a new rectangle
+        let right = frame(
+            r#"<circle r="3" cy="2" cx="1"/><g transform="scale(2)"><text x="1">a &amp; b</text><line x1="0" y1="1" x2="1" y2="1"/></g><rect width="1" height="1"/>"#,
+        );
+        let drawn = overlay(&left, &right).unwrap();
+        assert_eq!((drawn.shared, drawn.only_left, drawn.only_right), (2, 1, 2));
+        assert!(drawn.svg.contains(
+            r#"<g filter="url(#rgeometry-only-left)"><g transform="scale(2)"><line x1="0" x2="1" y1="0" y2="0"/></g></g>"#
+        ));
+        assert!(drawn
+            .svg
+            .contains(r#"<g transform="scale(2)"><text x="1">a &amp; b</text></g>"#));
+        // The result is a well-formed document
+        assert!(drawn
+            .svg
+            .contains(r#"width="100" height="50" viewBox="0 0 100 50""#));
+        assert!(svg::read(&drawn.svg)
+            .unwrap()
+            .all(|event| !matches!(event, Event::Error(_))));
+    }
+
+    #[test]
+    fn repeated_elements_and_view_boxes() {
+        let dot = r#"<circle r="1"/>"#;
+        let left = frame(&dot.repeat(3));
+        let right = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-10 0 50 100"><defs><marker id="m"/></defs><circle r="1"/><title>Dots</title></svg>"#;
+        let drawn = overlay(&left, right).unwrap();
+        // Each element pairs up once
+        assert_eq!((drawn.shared, drawn.only_left, drawn.only_right), (1, 2, 0));
+        assert_eq!(
+            ViewBox::from_svg(&drawn.svg),
+            Some(ViewBox {
+                x: -10.0,
+                y: 0.0,
+                width: 110.0,
+                height: 100.0
+            })
+        );
+        // The left frame's scale is kept
+        assert!(drawn.svg.contains(r#"width="110" height="100""#));
+        let relative = left.replace(r#"width="100""#, r#"width="100%""#);
+        let drawn = overlay(&relative, right).unwrap();
+        let root = &drawn.svg[crate::viewbox::root_tag(&drawn.svg).unwrap()];
+        assert!(!root.contains("width=") && root.contains(r#"height="100""#));
+        assert!(drawn.svg.contains(r#"<defs><marker id="m"/></defs>"#));
+        assert!(!drawn.svg.contains("Dots"));
+        assert!(overlay("<g/>", &left).is_err());
+    }
+}
@@ -0,0 +1,476 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::OnceLock;
+
+use crate::tikz::{to_tikz, Tikz, TIKZ_WIDTH};
+use crate::viewbox::root_tag;
+use crate::wasm::{Metadata, Schema, Value};
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, TextStr};
+use resvg::tiny_skia::{Color, IntSize, Pixmap, Transform};
+use serde::Serialize;
+use svg2pdf::usvg::{self, fontdb, TreeParsing, TreePostProc};
+
+/// Largest width or height of an exported PNG, in pixels.
+pub const MAX_PNG_SIZE: u32 = 16384;
+
+/// What exported files record about the frame they show.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct FrameInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    pub time: f64,
+    /// Value of every parameter the user can set, by name or label
+    pub parameters: BTreeMap<String, String>,
+}
+
+impl FrameInfo {
+    pub fn capture(
+        meta: &Metadata,
+        schema: &Schema,
+        values: &HashMap<usize, Value>,
+        time: f64,
+    ) -> Self {
+        let parameters = schema
+            .iter()
+            .enumerate()
+            .filter_map(|(index, param)| {
+                let value = values
+                    .get(&index)
+                    .cloned()
+                    .or_else(|| param.ty.default_value())?;
+                let key = param
+                    .name
+                    .clone()
+                    .unwrap_or_else(|| param.display_label(index));
+                Some((key, param.ty.format_value(&value)?))
+            })
+            .collect();
+        FrameInfo {
+            title: meta.title.clone(),
+            time,
+            parameters,
+        }
+    }
+
+    // One "key = value" line for the time and each parameter
+    pub(crate) fn description(&self) -> String {
+        std::iter::once(format!("time = {}", self.time))
+            .chain(
+                self.parameters
+                    .iter()
+                    .map(|(key, value)| format!("{} = {}", key, value)),
+            )
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// File name for an export, made from the title.
+    pub fn file_name(&self, extension: &str) -> String {
+        let stem: String = self
+            .title
+            .as_deref()
+            .unwrap_or("frame")
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() {
+                    c.to_ascii_lowercase()
+                } else {
+                    '-'
+                }
+            })
+            .collect();
+        let stem = stem
+            .split('-')
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>();
+        if stem.is_empty() {
+            format!("frame.{}", extension)
+        } else {
+            format!("{}.{}", stem.join("-"), extension)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Svg,
+    Png,
+    Pdf,
+    Tikz,
+}
+
+impl Format {
+    pub const ALL: [Format; 4] = [Format::Svg, Format::Png, Format::Pdf, Format::Tikz];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Svg => "SVG",
+            Format::Png => "PNG",
+            Format::Pdf => "PDF",
+            Format::Tikz => "TikZ",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Svg => "svg",
+            Format::Png => "png",
+            Format::Pdf => "pdf",
+            Format::Tikz => "tex",
+        }
+    }
+
+    pub fn mime(self) -> &'static str {
+        match self {
+            Format::Svg => "image/svg+xml",
+            Format::Png => "image/png",
+            Format::Pdf => "application/pdf",
+            Format::Tikz => "application/x-tex",
+        }
+    }
+}
+
+fn escape_xml(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Convert a rendered frame to `format`. PNGs are rasterized at `scale`
+/// pixels per user unit. Elements TikZ exports can't show are logged as
+/// warnings.
+pub fn export(svg: &str, format: Format, scale: f32, info: &FrameInfo) -> Result<Vec<u8>, String> {
+    match format {
+        Format::Svg => to_svg(svg, info).map(String::into_bytes),
+        Format::Png => to_png(svg, scale, info),
+        Format::Pdf => to_pdf(svg, info),
+        Format::Tikz => {
+            let tikz = tikz(svg, info)?;
+            for item in &tikz.unsupported {
+                log::warn!("TikZ export skipped or approximated {}", item);
+            }
+            Ok(tikz.source.into_bytes())
+        }
+    }
+}
+
+/// Convert a rendered frame to a `tikzpicture`, preceded by comments with
+/// the frame info.
+pub fn tikz(svg: &str, info: &FrameInfo) -> Result<Tikz, String> {
+    let mut tikz = to_tikz(svg, TIKZ_WIDTH)?;
+    // Every line is commented out, including those that a lone carriage
+    // return starts
+    let header: String = info
+        .title
+        .iter()
+        .cloned()
+        .chain(std::iter::once(info.description()))
+        .flat_map(|text| {
+            text.split(['\r', '\n'])
+                .filter(|line| !line.is_empty())
+                .map(|line| format!("% {}\n", line))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    tikz.source.insert_str(0, &header);
+    Ok(tikz)
+}
+
+// Add a <title> and the frame info as JSON <metadata> to the root element
+fn to_svg(svg: &str, info: &FrameInfo) -> Result<String, String> {
+    let tag = root_tag(svg).ok_or("Frame has no <svg> element")?;
+    if tag.end == svg.len() || svg[..tag.end].ends_with('/') {
+        return Err("Frame is empty".to_string());
+    }
+    let json = serde_json::to_string(info).map_err(|e| e.to_string())?;
+    let title = info
+        .title
+        .as_deref()
+        .map(|title| format!("<title>{}</title>", escape_xml(title)))
+        .unwrap_or_default();
+    Ok(format!(
+        "{}>{}<metadata>{}</metadata>{}",
+        &svg[..tag.end],
+        title,
+        escape_xml(&json),
+        &svg[tag.end + 1..]
+    ))
+}
+
+// A public domain font for text in no font the system has, and for all text
+// in the browser, where no system fonts can be loaded
+const FALLBACK_FONT: &[u8] = include_bytes!("../fonts/Tuffy.ttf");
+
+// Fonts that text is drawn with, loaded once
+fn fonts() -> &'static fontdb::Database {
+    static FONTS: OnceLock<fontdb::Database> = OnceLock::new();
+    FONTS.get_or_init(|| {
+        let mut fonts = fontdb::Database::new();
+        #[cfg(not(target_arch = "wasm32"))]
+        fonts.load_system_fonts();
+        fonts.load_font_data(FALLBACK_FONT.to_vec());
+        // Every lookup ends with the serif family, so text is only dropped
+        // if that is missing too
+        let missing = |fonts: &fontdb::Database, family| {
+            let query = fontdb::Query {
+                families: &[family],
+                ..fontdb::Query::default()
+            };
+            fonts.query(&query).is_none()
+        };
+        if missing(&fonts, fontdb::Family::Serif) {
+            fonts.set_serif_family("Tuffy");
+        }
+        if missing(&fonts, fontdb::Family::SansSerif) {
+            fonts.set_sans_serif_family("Tuffy");
+        }
+        if missing(&fonts, fontdb::Family::Monospace) {
+            fonts.set_monospace_family("Tuffy");
+        }
+        fonts
+    })
+}
+
+pub(crate) fn parse(svg: &str) -> Result<usvg::Tree, String> {
+    let mut tree = usvg::Tree::from_str(svg, &usvg::Options::default())
+        .map_err(|e| format!("Failed to parse frame: {}", e))?;
+    // Computes the bounding boxes that rendering relies on, and turns text
+    // into paths
+    tree.postprocess(usvg::PostProcessingSteps::default(), fonts());
+    Ok(tree)
+}
+
+// Render a frame scaled to fill a canvas of the given size, on top of
+// `background` or on a transparent canvas
+pub(crate) fn rasterize(
+    tree: &usvg::Tree,
+    size: IntSize,
+    background: Option<Color>,
+) -> Result<Pixmap, String> {
+    let mut pixmap = Pixmap::new(size.width(), size.height()).ok_or("Invalid image size")?;
+    if let Some(background) = background {
+        pixmap.fill(background);
+    }
+    let transform = Transform::from_scale(
+        size.width() as f32 / tree.size.width(),
+        size.height() as f32 / tree.size.height(),
+    );
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+    Ok(pixmap)
+}
+
+// Straight (not premultiplied) RGBA bytes of a canvas
+pub(crate) fn rgba(pixmap: &Pixmap) -> Vec<u8> {
+    pixmap
+        .pixels()
+        .iter()
+        .flat_map(|pixel| {
+            let color = pixel.demultiply();
+            [color.red(), color.green(), color.blue(), color.alpha()]
+        })
+        .collect()
+}
+
+// An 8-bit RGBA PNG encoder with the frame info in text chunks. tiny-skia
+// can't write text chunks, so PNGs are encoded with png directly.
+pub(crate) fn png_encoder<'a, W: std::io::Write>(
+    out: W,
+    size: IntSize,
+    info: &FrameInfo,
+) -> Result<png::Encoder<'a, W>, String> {
+    let mut encoder = png::Encoder::new(out, size.width(), size.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let json = serde_json::to_string(info).map_err(|e| e.to_string())?;
+    let mut chunks = vec![("Software", "rgeometry".to_string())];
+    chunks.extend(info.title.clone().map(|title| ("Title", title)));
+    chunks.push(("Description", info.description()));
+    chunks.push(("Comment", json));
+    for (keyword, text) in chunks {
+        encoder
+            .add_itxt_chunk(keyword.to_string(), text)
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(encoder)
+}
+
+fn to_png(svg: &str, scale: f32, info: &FrameInfo) -> Result<Vec<u8>, String> {
+    let tree = parse(svg)?;
+    let size = tree
+        .size
+        .to_int_size()
+        .scale_by(scale)
+        .ok_or("Invalid scale")?;
+    if size.width() > MAX_PNG_SIZE || size.height() > MAX_PNG_SIZE {
+        return Err(format!(
+            "{}x{} pixels exceeds the limit of {}",
+            size.width(),
+            size.height(),
+            MAX_PNG_SIZE
+        ));
+    }
+    let pixmap = rasterize(&tree, size, None)?;
+    let mut bytes = Vec::new();
+    let mut writer = png_encoder(&mut bytes, size, info)?
+        .write_header()
+        .map_err(|e| e.to_string())?;
+    writer
+        .write_image_data(&rgba(&pixmap))
+        .map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(bytes)
+}
+
+// A single page the size of the frame, one point per user unit
+fn to_pdf(svg: &str, info: &FrameInfo) -> Result<Vec<u8>, String> {
+    let tree = parse(svg)?;
+    let (width, height) = (tree.size.width(), tree.size.height());
+    let catalog_id = Ref::new(1);
+    let page_tree_id = Ref::new(2);
+    let page_id = Ref::new(3);
+    let content_id = Ref::new(4);
+    let info_id = Ref::new(5);
+    let svg_id = Ref::new(6);
+    let svg_name = Name(b"S1");
+
+    let mut pdf = Pdf::new();
+    pdf.catalog(catalog_id).pages(page_tree_id);
+    pdf.pages(page_tree_id).kids([page_id]).count(1);
+    let mut page = pdf.page(page_id);
+    page.media_box(Rect::new(0.0, 0.0, width, height));
+    page.parent(page_tree_id);
+    page.contents(content_id);
+    page.resources().x_objects().pair(svg_name, svg_id);
+    page.finish();
+
+    svg2pdf::convert_tree_into(&tree, svg2pdf::Options::default(), &mut pdf, svg_id);
+    let mut content = Content::new();
+    content
+        .transform([width, 0.0, 0.0, height, 0.0, 0.0])
+        .x_object(svg_name);
+    pdf.stream(content_id, &content.finish());
+
+    let description = info.description();
+    let mut document_info = pdf.document_info(info_id);
+    if let Some(title) = &info.title {
+        document_info.title(TextStr(title));
+    }
+    document_info
+        .subject(TextStr(&description))
+        .creator(TextStr("rgeometry"))
+        .producer(TextStr("svg2pdf"));
+    document_info.finish();
+    Ok(pdf.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SVG: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10"><rect width="10" height="10" fill="red"/></svg>"#;
+
+    fn info() -> FrameInfo {
+        FrameInfo {
+            title: Some("Convex <hull>".to_string()),
+            time: 1.5,
+            parameters: BTreeMap::from([("n".to_string(), "10".to_string())]),
+        }
+    }
+
+    #[test]
+    fn svg_metadata() {
+        let svg = String::from_utf8(export(SVG, Format::Svg, 1.0, &info()).unwrap()).unwrap();
+        assert!(svg.starts_with(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 20 10"><title>Convex &lt;hull&gt;</title><metadata>{&quot;title&quot;"#
+        ));
+        assert!(svg.ends_with(r#"</metadata><rect width="10" height="10" fill="red"/></svg>"#));
+        // Still a valid document
+        assert!(parse(&svg).is_ok());
+        assert!(export("<svg/>", Format::Svg, 1.0, &info()).is_err());
+    }
+
+    #[test]
+    fn png_size_and_pixels() {
+        let png = export(SVG, Format::Png, 2.0, &info()).unwrap();
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        assert_eq!((reader.info().width, reader.info().height), (40, 20));
+        assert!(reader
+            .info()
+            .utf8_text
+            .iter()
+            .any(|chunk| chunk.keyword == "Description"
+                && chunk.get_text().unwrap() == "time = 1.5\nn = 10"));
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        // Red on the left, transparent on the right
+        assert_eq!(data[..4], [255, 0, 0, 255]);
+        assert_eq!(data[data.len() - 4..], [0, 0, 0, 0]);
+
+        assert!(export(SVG, Format::Png, 1000.0, &info()).is_err());
+    }
+
+    #[test]
+    fn text_is_drawn() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 40 20"><text x="2" y="16" font-size="16">Hi</text></svg>"#;
+        let png = export(svg, Format::Png, 1.0, &info()).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut data = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        let inked = data.chunks(4).filter(|pixel| pixel[3] > 0).count();
+        assert!(inked > 20, "only {} pixels drawn", inked);
+
+        // The glyphs end up as paths in the PDF
+        let pdf = export(svg, Format::Pdf, 1.0, &info()).unwrap();
+        let empty = export(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 40 20"><text x="2" y="16" font-size="16"></text></svg>"#,
+            Format::Pdf,
+            1.0,
+            &info(),
+        )
+        .unwrap();
+        assert!(pdf.len() > empty.len());
+    }
+
+    #[test]
+    fn pdf_document() {
+        let pdf = export(SVG, Format::Pdf, 1.0, &info()).unwrap();
+        let text = String::from_utf8_lossy(&pdf);
+        assert!(text.starts_with("%PDF-"));
+        assert!(text.contains("/MediaBox [0 0 20 10]"));
+        assert!(text.contains("/Title (Convex <hull>)"));
+    }
+
+    #[test]
+    fn tikz_picture() {
+        let tex = String::from_utf8(export(SVG, Format::Tikz, 1.0, &info()).unwrap()).unwrap();
+        assert!(tex.starts_with("% Convex <hull>\n% time = 1.5\n% n = 10\n\\begin{tikzpicture}\n"));
+        assert!(tex.contains(r"\path[fill=rg0] (0,5) -- (5,5) -- (5,0) -- (0,0) -- cycle;"));
+        assert!(tex.ends_with("\\end{tikzpicture}\n"));
+
+        let info = FrameInfo {
+            title: Some("Two\nlines\r\\end".to_string()),
+            ..info()
+        };
+        let tex = String::from_utf8(export(SVG, Format::Tikz, 1.0, &info).unwrap()).unwrap();
+        assert!(tex.starts_with("% Two\n% lines\n% \\end\n% time = 1.5\n"));
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(info().file_name("pdf"), "convex-hull.pdf");
+        assert_eq!(FrameInfo::default().file_name("png"), "frame.png");
+        let untitled = FrameInfo {
+            title: Some("???".to_string()),
+            ..FrameInfo::default()
+        };
+        assert_eq!(untitled.file_name("svg"), "frame.svg");
+    }
+}
@@ -0,0 +1,241 @@
+use crate::wasm::{Preset, Schema, SchemaType, Value};
+
+/// Largest number of cells a grid renders.
+pub const MAX_CELLS: usize = 100;
+
+// Values an axis gets by default, at most
+const DEFAULT_STEPS: usize = 5;
+
+/// Values of one parameter along a side of the grid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GridAxis {
+    /// Schema index of the parameter
+    pub parameter: usize,
+    pub values: Vec<Value>,
+}
+
+impl GridAxis {
+    /// Evenly spaced values across a range, both values of a bool, every
+    /// option of an enum, or a few seeds from the default on. Other kinds of
+    /// parameters can't be put on an axis.
+    pub fn new(schema: &Schema, parameter: usize) -> Result<Self, String> {
+        let spaced = |min: f64, max: f64| {
+            (0..DEFAULT_STEPS)
+                .map(move |i| min + (max - min) * i as f64 / (DEFAULT_STEPS - 1) as f64)
+        };
+        let mut values: Vec<Value> = match schema.get(parameter).map(|param| &param.ty) {
+            Some(SchemaType::RangeF32 { min, max, .. }) => spaced(*min as f64, *max as f64)
+                .map(|v| Value::F32(v as f32))
+                .collect(),
+            Some(SchemaType::RangeF64 { min, max, .. }) => {
+                spaced(*min, *max).map(Value::F64).collect()
+            }
+            Some(SchemaType::RangeI32 { min, max, .. }) => spaced(*min as f64, *max as f64)
+                .map(|v| Value::I32(v.round() as i32))
+                .collect(),
+            Some(SchemaType::RangeI64 { min, max, .. }) => spaced(*min as f64, *max as f64)
+                .map(|v| Value::I64(v.round() as i64))
+                .collect(),
+            Some(SchemaType::Bool { .. }) => vec![Value::I32(0), Value::I32(1)],
+            Some(SchemaType::Enum { options, .. }) => {
+                (0..options.len() as i32).map(Value::I32).collect()
+            }
+            Some(SchemaType::Seed { default, .. }) => (0..DEFAULT_STEPS as i64)
+                .map(|i| Value::I64(default.wrapping_add(i)))
+                .collect(),
+            _ => return Err(format!("Parameter {} can't be put on a grid", parameter)),
+        };
+        values.dedup();
+        Ok(GridAxis { parameter, values })
+    }
+
+    /// Whether the parameter at `index` can be put on an axis.
+    pub fn accepts(schema: &Schema, index: usize) -> bool {
+        GridAxis::new(schema, index).is_ok()
+    }
+
+    /// Values separated by commas, written as in a URL query.
+    pub fn parse(schema: &Schema, parameter: usize, text: &str) -> Result<Self, String> {
+        let param = schema
+            .get(parameter)
+            .ok_or_else(|| format!("No parameter at index {}", parameter))?;
+        let values = text
+            .split(',')
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                param.ty.parse_value(value).ok_or_else(|| {
+                    format!(
+                        "Invalid value {:?} for {}",
+                        value,
+                        param.display_label(parameter)
+                    )
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if values.is_empty() {
+            return Err(format!("No values for {}", param.display_label(parameter)));
+        }
+        Ok(GridAxis { parameter, values })
+    }
+
+    /// Inverse of `parse`.
+    pub fn format(&self, schema: &Schema) -> String {
+        let Some(param) = schema.get(self.parameter) else {
+            return String::new();
+        };
+        self.values
+            .iter()
+            .filter_map(|value| param.ty.format_value(value))
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// The cartesian product of the values of one or two parameters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Grid {
+    pub columns: GridAxis,
+    pub rows: Option<GridAxis>,
+}
+
+impl Grid {
+    pub fn validate(&self, schema: &Schema) -> Result<(), String> {
+        for axis in std::iter::once(&self.columns).chain(&self.rows) {
+            if !GridAxis::accepts(schema, axis.parameter) {
+                return Err(format!(
+                    "Parameter {} can't be put on a grid",
+                    axis.parameter
+                ));
+            }
+        }
+        if self.rows.as_ref().map(|rows| rows.parameter) == Some(self.columns.parameter) {
+            return Err("The rows and columns need different parameters".to_string());
+        }
+        if self.len() > MAX_CELLS {
+            return Err(format!(
+                "The grid has {} cells, at most {} are rendered",
+                self.len(),
+                MAX_CELLS
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn width(&self) -> usize {
+        self.columns.values.len()
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.as_ref().map_or(1, |rows| rows.values.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Values of the cell at `index`, counting row by row.
+    pub fn cell(&self, index: usize) -> Vec<(usize, Value)> {
+        let column = &self.columns.values[index % self.width()];
+        let mut values = vec![(self.columns.parameter, column.clone())];
+        if let Some(rows) = &self.rows {
+            values.push((rows.parameter, rows.values[index / self.width()].clone()));
+        }
+        values
+    }
+
+    /// "name = value" for each parameter of a cell.
+    pub fn label(schema: &Schema, cell: &[(usize, Value)]) -> String {
+        cell.iter()
+            .filter_map(|(index, value)| {
+                let param = schema.get(*index)?;
+                let value = param.ty.format_value(value)?;
+                Some(format!("{} = {}", param.display_label(*index), value))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// The cell at `index` as a preset named by its label, so its values are
+    /// keyed by parameter name.
+    pub fn preset(&self, schema: &Schema, index: usize) -> Preset {
+        let cell = self.cell(index);
+        let label = Grid::label(schema, &cell);
+        Preset::capture(label, schema, &cell.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wasm::Wasm;
+
+    // Draws a circle of radius n, and a square instead if the bool is set
+    const SHAPES: &str = r##"
+        (module
+            (@custom "rgeometry.schema" "[{\"type\":\"range_i32\",\"name\":\"n\",\"min\":1,\"max\":9,\"default\":1},{\"type\":\"bool\",\"name\":\"square\",\"default\":false},{\"type\":\"color\",\"default\":\"#000000\"}]")
+            (import "env" "render" (func $render (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "<svg><circle r=\"0\"/></svg>\00")
+            (data (i32.const 64) "<svg><rect/></svg>\00")
+            (func (export "request_animation_frame") (param i32 i32 i32)
+                (if (local.get 1)
+                    (then (call $render (i32.const 64)))
+                    (else
+                        (i32.store8 (i32.const 32) (i32.add (i32.const 48) (local.get 0)))
+                        (call $render (i32.const 16)))))
+        )"##;
+
+    fn schema() -> Schema {
+        Wasm::new(&wat::parse_str(SHAPES).unwrap())
+            .unwrap()
+            .schema()
+            .clone()
+    }
+
+    #[test]
+    fn axes() {
+        let schema = schema();
+        let n = GridAxis::new(&schema, 0).unwrap();
+        assert_eq!(n.format(&schema), "1, 3, 5, 7, 9");
+        assert_eq!(
+            GridAxis::new(&schema, 1).unwrap().values,
+            [Value::I32(0), Value::I32(1)]
+        );
+        assert!(GridAxis::new(&schema, 2).is_err());
+        assert_eq!(
+            GridAxis::parse(&schema, 1, " true,false ,").unwrap().values,
+            [Value::I32(1), Value::I32(0)]
+        );
+        assert!(GridAxis::parse(&schema, 0, "2, 10").is_err());
+        assert!(GridAxis::parse(&schema, 0, ",").is_err());
+    }
+
+    #[test]
+    fn cells() {
+        let schema = schema();
+        let mut grid = Grid {
+            columns: GridAxis::parse(&schema, 0, "2, 4, 6").unwrap(),
+            rows: Some(GridAxis::new(&schema, 1).unwrap()),
+        };
+        assert_eq!(grid.validate(&schema), Ok(()));
+        assert_eq!((grid.width(), grid.height(), grid.len()), (3, 2, 6));
+        let cell = grid.cell(4);
+        assert_eq!(cell, [(0, Value::I32(4)), (1, Value::I32(1))]);
+        assert_eq!(Grid::label(&schema, &cell), "n = 4, square = true");
+
+        let preset = grid.preset(&schema, 4);
+        assert_eq!(preset.name, "n = 4, square = true");
+        assert_eq!(preset.resolve(&schema).unwrap(), cell);
+
+        grid.rows = Some(GridAxis::parse(&schema, 0, "1").unwrap());
+        assert!(grid.validate(&schema).is_err());
+        grid.rows = None;
+        grid.columns.values = vec![Value::I32(1); MAX_CELLS + 1];
+        assert!(grid.validate(&schema).is_err());
+    }
+}
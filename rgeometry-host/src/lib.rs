@@ -0,0 +1,11 @@
+//! Running rgeometry demo modules outside of the browser UI: loading and
+//! rendering guests, and converting their frames to other formats.
+
+pub mod animation;
+pub mod compare;
+pub mod export;
+pub mod grid;
+pub mod sweep;
+pub mod tikz;
+pub mod viewbox;
+pub mod wasm;
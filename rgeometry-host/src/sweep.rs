@@ -0,0 +1,551 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::wasm::{Schema, SchemaType, Value, Viewport, Wasm};
+use serde::{Deserialize, Serialize};
+use svg::node::element::{Circle, Group, Line, Path, Rectangle, Text};
+use svg::Document;
+
+/// Largest number of values a sweep renders.
+pub const MAX_STEPS: usize = 100;
+
+/// Largest number of timed renders per value.
+pub const MAX_SAMPLES: usize = 100;
+
+/// Which values of a `range_i32` parameter a sweep renders, and how often.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct SweepOptions {
+    /// Schema index of the swept parameter
+    pub parameter: usize,
+    pub min: i32,
+    pub max: i32,
+    /// Number of values between `min` and `max`, spaced evenly on a log scale
+    pub steps: usize,
+    /// Timed renders per value. The median is plotted.
+    pub samples: usize,
+    /// Time the frames are rendered at, in seconds
+    pub time: f64,
+    /// Fuel a frame may consume, see `Wasm::set_fuel_limit`. None for no
+    /// limit.
+    pub fuel_limit: Option<u64>,
+    /// Size the frames are rendered at, for modules that adapt to it
+    pub viewport: Viewport,
+}
+
+impl SweepOptions {
+    /// Sweep the whole range of the `range_i32` at `parameter`, or from 1 if
+    /// the range includes values below 1.
+    pub fn new(schema: &Schema, parameter: usize) -> Result<Self, String> {
+        match schema.get(parameter).map(|param| &param.ty) {
+            Some(SchemaType::RangeI32 { min, max, .. }) => Ok(SweepOptions {
+                parameter,
+                min: (*min).max(1),
+                max: *max,
+                steps: 8,
+                samples: 5,
+                time: 0.0,
+                fuel_limit: None,
+                viewport: Viewport::default(),
+            }),
+            _ => Err(format!("Parameter {} is not a range_i32", parameter)),
+        }
+    }
+
+    pub fn validate(&self, schema: &Schema) -> Result<(), String> {
+        let Some(SchemaType::RangeI32 { min, max, .. }) =
+            schema.get(self.parameter).map(|param| &param.ty)
+        else {
+            return Err(format!("Parameter {} is not a range_i32", self.parameter));
+        };
+        if self.min < 1 || self.max < self.min {
+            return Err("The sweep needs 1 <= min <= max for log axes".to_string());
+        }
+        if self.min < *min || self.max > *max {
+            return Err(format!(
+                "The sweep {}..={} leaves the parameter's range {}..={}",
+                self.min, self.max, min, max
+            ));
+        }
+        if !(2..=MAX_STEPS).contains(&self.steps) {
+            return Err(format!("The steps must be between 2 and {}", MAX_STEPS));
+        }
+        if !(1..=MAX_SAMPLES).contains(&self.samples) {
+            return Err(format!("The samples must be between 1 and {}", MAX_SAMPLES));
+        }
+        Ok(())
+    }
+
+    /// The swept values, spaced evenly on a log scale. Values that round to
+    /// the same integer are rendered once.
+    pub fn values(&self) -> Vec<i32> {
+        let (min, max) = (self.min as f64, self.max as f64);
+        let mut values: Vec<i32> = (0..self.steps)
+            .map(|i| {
+                let t = i as f64 / (self.steps - 1) as f64;
+                (min * (max / min).powf(t)).round() as i32
+            })
+            .collect();
+        values.dedup();
+        values
+    }
+}
+
+/// Measurements of one swept value.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SweepPoint {
+    pub value: i32,
+    /// Wall time of every timed render, in milliseconds
+    pub times: Vec<f64>,
+    /// Fuel one frame consumed
+    pub fuel: u64,
+}
+
+impl SweepPoint {
+    pub fn median_time(&self) -> f64 {
+        let mut times = self.times.clone();
+        times.sort_by(f64::total_cmp);
+        let mid = times.len() / 2;
+        if times.len().is_multiple_of(2) {
+            (times[mid - 1] + times[mid]) / 2.0
+        } else {
+            times[mid]
+        }
+    }
+}
+
+/// A power law `y = 10^intercept * x^slope`, fitted to points on log-log
+/// axes. The slope is the empirical exponent of the complexity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Fit {
+    pub slope: f64,
+    pub intercept: f64,
+}
+
+impl Fit {
+    /// Least squares fit of log10(y) against log10(x). Points that can't be
+    /// shown on log axes are ignored. None for fewer than two distinct x.
+    pub fn new(points: impl IntoIterator<Item = (f64, f64)>) -> Option<Fit> {
+        let logs: Vec<(f64, f64)> = points
+            .into_iter()
+            .filter(|(x, y)| *x > 0.0 && *y > 0.0)
+            .map(|(x, y)| (x.log10(), y.log10()))
+            .collect();
+        let n = logs.len() as f64;
+        let mean_x = logs.iter().map(|(x, _)| x).sum::<f64>() / n;
+        let mean_y = logs.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let sxx: f64 = logs.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        let sxy: f64 = logs.iter().map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        if logs.len() < 2 || sxx <= 0.0 {
+            return None;
+        }
+        let slope = sxy / sxx;
+        Some(Fit {
+            slope,
+            intercept: mean_y - slope * mean_x,
+        })
+    }
+
+    pub fn at(&self, x: f64) -> f64 {
+        10f64.powf(self.intercept + self.slope * x.log10())
+    }
+}
+
+/// Render cost of a module across the values of a parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sweep {
+    /// Name or label of the swept parameter
+    pub parameter: String,
+    pub points: Vec<SweepPoint>,
+}
+
+// Create an instance with `values` set, rendering into `viewport`
+fn instance(
+    bytes: &[u8],
+    values: &HashMap<usize, Value>,
+    viewport: Viewport,
+    fuel_metering: bool,
+) -> Result<Wasm, String> {
+    let mut wasm = if fuel_metering {
+        Wasm::with_fuel_metering(bytes)?
+    } else {
+        Wasm::new(bytes)?
+    };
+    wasm.set_viewport(viewport);
+    for (index, value) in values {
+        wasm.set_parameter(*index, value.clone())?;
+    }
+    Ok(wasm)
+}
+
+fn render(wasm: &mut Wasm, time: f64) -> Result<(), String> {
+    wasm.render_at(time, 0.0);
+    if wasm.is_out_of_fuel() {
+        return Err(format!("The frame at {}s ran out of fuel", time));
+    }
+    if wasm.has_failed() {
+        return Err(format!("Failed to render the frame at {}s", time));
+    }
+    Ok(())
+}
+
+/// Measure one value of a sweep, with the other parameters set to `values`.
+/// Fuel is counted by a metered instance, as metering slows the module down.
+/// It renders first, so a frame over the fuel limit fails before any frame
+/// runs unmetered. Times are taken from a separate instance after an untimed
+/// first frame, which also runs the module's `init`. Every render starts from
+/// the state the previous frame left, as in the viewer.
+pub fn measure(
+    bytes: &[u8],
+    values: &HashMap<usize, Value>,
+    options: &SweepOptions,
+    value: i32,
+) -> Result<SweepPoint, String> {
+    let mut values = values.clone();
+    values.insert(options.parameter, Value::I32(value));
+
+    let mut metered = instance(bytes, &values, options.viewport, true)?;
+    metered.set_fuel_limit(options.fuel_limit);
+    render(&mut metered, options.time)?;
+    let fuel = metered.metrics().fuel.unwrap_or_default();
+
+    let mut wasm = instance(bytes, &values, options.viewport, false)?;
+    render(&mut wasm, options.time)?;
+    let times = (0..options.samples)
+        .map(|_| {
+            render(&mut wasm, options.time)?;
+            Ok(wasm.metrics().guest_time)
+        })
+        .collect::<Result<_, String>>()?;
+    Ok(SweepPoint { value, times, fuel })
+}
+
+/// Measure every value of a sweep.
+pub fn run(
+    bytes: &[u8],
+    values: &HashMap<usize, Value>,
+    options: &SweepOptions,
+) -> Result<Sweep, String> {
+    let wasm = Wasm::new(bytes)?;
+    options.validate(wasm.schema())?;
+    let parameter = wasm.schema()[options.parameter].display_label(options.parameter);
+    let points = options
+        .values()
+        .into_iter()
+        .map(|value| measure(bytes, values, options, value))
+        .collect::<Result<_, _>>()?;
+    Ok(Sweep { parameter, points })
+}
+
+// Size of a plot, in pixels, and the margin around its axes
+const PLOT_WIDTH: f64 = 480.0;
+const PLOT_HEIGHT: f64 = 300.0;
+const MARGIN: f64 = 48.0;
+
+// Powers of ten from below `min` to above `max`
+fn decades(min: f64, max: f64) -> (i32, i32) {
+    let low = min.log10().floor() as i32;
+    let high = (max.log10().ceil() as i32).max(low + 1);
+    (low, high)
+}
+
+// A number for an axis label, like 1e-3, 0.01, 10 or 1e5
+fn power_label(exponent: i32) -> String {
+    match exponent {
+        -2..=4 => format!("{}", 10f64.powi(exponent)),
+        _ => format!("1e{}", exponent),
+    }
+}
+
+// Log-log plot of `points`, with the fitted line
+fn plot(parameter: &str, label: &str, points: &[(f64, f64)], color: &str, top: f64) -> Group {
+    let mut group = Group::new().set("transform", format!("translate(0 {})", top));
+    let fit = Fit::new(points.iter().copied());
+    let shown: Vec<(f64, f64)> = points
+        .iter()
+        .copied()
+        .filter(|(x, y)| *x > 0.0 && *y > 0.0)
+        .collect();
+    let bounds = |values: Vec<f64>| {
+        let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+        let max = values.iter().copied().fold(0.0, f64::max);
+        if min.is_finite() {
+            decades(min, max)
+        } else {
+            (0, 1)
+        }
+    };
+    let (x_low, x_high) = bounds(shown.iter().map(|(x, _)| *x).collect());
+    let (y_low, y_high) = bounds(shown.iter().map(|(_, y)| *y).collect());
+    let width = PLOT_WIDTH - 2.0 * MARGIN;
+    let height = PLOT_HEIGHT - 2.0 * MARGIN;
+    let to_x = |x: f64| MARGIN + (x.log10() - x_low as f64) / (x_high - x_low) as f64 * width;
+    let to_y = |y: f64| {
+        PLOT_HEIGHT - MARGIN - (y.log10() - y_low as f64) / (y_high - y_low) as f64 * height
+    };
+
+    group = group.add(
+        Rectangle::new()
+            .set("x", MARGIN)
+            .set("y", MARGIN)
+            .set("width", width)
+            .set("height", height)
+            .set("fill", "none")
+            .set("stroke", "#888888"),
+    );
+    for exponent in x_low..=x_high {
+        let x = to_x(10f64.powi(exponent));
+        group = group
+            .add(grid_line(x, MARGIN, x, PLOT_HEIGHT - MARGIN))
+            .add(label_at(
+                x,
+                PLOT_HEIGHT - MARGIN + 14.0,
+                "middle",
+                power_label(exponent),
+            ));
+    }
+    for exponent in y_low..=y_high {
+        let y = to_y(10f64.powi(exponent));
+        group = group
+            .add(grid_line(MARGIN, y, PLOT_WIDTH - MARGIN, y))
+            .add(label_at(
+                MARGIN - 4.0,
+                y + 4.0,
+                "end",
+                power_label(exponent),
+            ));
+    }
+    group = group.add(label_at(
+        PLOT_WIDTH / 2.0,
+        PLOT_HEIGHT - MARGIN + 30.0,
+        "middle",
+        parameter.to_string(),
+    ));
+
+    let mut title = label.to_string();
+    if let Some(fit) = fit {
+        // Over the measured values only, so the line stays inside the axes
+        let xs = shown.iter().map(|(x, _)| *x);
+        let x_min = xs.clone().fold(f64::INFINITY, f64::min);
+        let x_max = xs.fold(0.0, f64::max);
+        let mut d = String::new();
+        _ = write!(d, "M{:.2},{:.2}", to_x(x_min), to_y(fit.at(x_min)));
+        _ = write!(d, " L{:.2},{:.2}", to_x(x_max), to_y(fit.at(x_max)));
+        group = group.add(
+            Path::new()
+                .set("d", d)
+                .set("stroke", color)
+                .set("stroke-dasharray", "4 3")
+                .set("fill", "none"),
+        );
+        _ = write!(title, ", slope {:.2}", fit.slope);
+    }
+    for (x, y) in shown {
+        group = group.add(
+            Circle::new()
+                .set("cx", to_x(x))
+                .set("cy", to_y(y))
+                .set("r", 3)
+                .set("fill", color),
+        );
+    }
+    group.add(label_at(PLOT_WIDTH / 2.0, MARGIN - 10.0, "middle", title))
+}
+
+fn grid_line(x1: f64, y1: f64, x2: f64, y2: f64) -> Line {
+    Line::new()
+        .set("x1", x1)
+        .set("y1", y1)
+        .set("x2", x2)
+        .set("y2", y2)
+        .set("stroke", "#dddddd")
+}
+
+fn label_at(x: f64, y: f64, anchor: &str, text: String) -> Text {
+    Text::new(text)
+        .set("x", x)
+        .set("y", y)
+        .set("text-anchor", anchor)
+        .set("font-size", 11)
+        .set("font-family", "sans-serif")
+}
+
+impl Sweep {
+    /// Fit of the median render times.
+    pub fn time_fit(&self) -> Option<Fit> {
+        Fit::new(self.time_points())
+    }
+
+    pub fn fuel_fit(&self) -> Option<Fit> {
+        Fit::new(self.fuel_points())
+    }
+
+    fn time_points(&self) -> Vec<(f64, f64)> {
+        let points = self.points.iter();
+        points.map(|p| (p.value as f64, p.median_time())).collect()
+    }
+
+    fn fuel_points(&self) -> Vec<(f64, f64)> {
+        let points = self.points.iter();
+        points.map(|p| (p.value as f64, p.fuel as f64)).collect()
+    }
+
+    /// One row per timed render: value, sample, time in ms and fuel.
+    pub fn to_csv(&self) -> String {
+        let mut csv = format!("{},sample,time_ms,fuel\n", self.parameter.replace(',', " "));
+        for point in &self.points {
+            for (sample, time) in point.times.iter().enumerate() {
+                _ = writeln!(csv, "{},{},{},{}", point.value, sample, time, point.fuel);
+            }
+        }
+        csv
+    }
+
+    /// Log-log plots of the median time and the fuel against the swept
+    /// value, each with its fitted slope.
+    pub fn plot(&self) -> String {
+        let background = Rectangle::new()
+            .set("width", PLOT_WIDTH)
+            .set("height", 2.0 * PLOT_HEIGHT)
+            .set("fill", "white");
+        Document::new()
+            .set("width", PLOT_WIDTH)
+            .set("height", 2.0 * PLOT_HEIGHT)
+            .set("viewBox", (0, 0, PLOT_WIDTH, 2.0 * PLOT_HEIGHT))
+            .add(background)
+            .add(plot(
+                &self.parameter,
+                "Median time (ms)",
+                &self.time_points(),
+                "#d95f02",
+                0.0,
+            ))
+            .add(plot(
+                &self.parameter,
+                "Fuel",
+                &self.fuel_points(),
+                "#1b9e77",
+                PLOT_HEIGHT,
+            ))
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Loops n^2 times for the range_i32 n, and also takes a bool
+    const QUADRATIC: &str = r#"
+        (module
+            (@custom "rgeometry.schema" "[{\"type\":\"bool\",\"default\":false},{\"type\":\"range_i32\",\"name\":\"n\",\"min\":0,\"max\":1000,\"default\":10}]")
+            (import "env" "render" (func $render (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "ok")
+            (func (export "request_animation_frame") (param i32 i32)
+                (local $i i32)
+                (local.set $i (i32.mul (local.get 1) (local.get 1)))
+                (block $done
+                    (loop $next
+                        (br_if $done (i32.eqz (local.get $i)))
+                        (local.set $i (i32.sub (local.get $i) (i32.const 1)))
+                        (br $next)))
+                (call $render (i32.const 16)))
+        )"#;
+
+    #[test]
+    fn log_spaced_values() {
+        let schema = Wasm::new(&wat::parse_str(QUADRATIC).unwrap())
+            .unwrap()
+            .schema()
+            .clone();
+        let options = SweepOptions::new(&schema, 1).unwrap();
+        assert_eq!((options.min, options.max), (1, 1000));
+        assert_eq!(
+            SweepOptions {
+                steps: 4,
+                ..options
+            }
+            .values(),
+            [1, 10, 100, 1000]
+        );
+        // Duplicates after rounding are dropped
+        assert_eq!(
+            SweepOptions {
+                min: 1,
+                max: 3,
+                steps: 5,
+                ..options
+            }
+            .values(),
+            [1, 2, 3]
+        );
+        assert!(SweepOptions::new(&schema, 0).is_err());
+        for invalid in [
+            SweepOptions { min: 0, ..options },
+            SweepOptions {
+                max: 2000,
+                ..options
+            },
+            SweepOptions {
+                steps: 1,
+                ..options
+            },
+            SweepOptions {
+                samples: 0,
+                ..options
+            },
+        ] {
+            assert!(invalid.validate(&schema).is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn fitted_slopes() {
+        let fit = Fit::new([(1.0, 3.0), (10.0, 300.0), (100.0, 30000.0), (0.0, 5.0)]).unwrap();
+        assert!((fit.slope - 2.0).abs() < 1e-9);
+        assert!((fit.at(1000.0) - 3e6).abs() < 1e-3);
+        assert_eq!(Fit::new([(10.0, 1.0), (10.0, 2.0)]), None);
+    }
+
+    #[test]
+    fn quadratic_sweep() {
+        let bytes = wat::parse_str(QUADRATIC).unwrap();
+        let options = SweepOptions {
+            parameter: 1,
+            min: 10,
+            max: 1000,
+            steps: 3,
+            samples: 3,
+            time: 0.0,
+            fuel_limit: None,
+            viewport: Viewport::default(),
+        };
+        let values = HashMap::from([(0, Value::I32(1))]);
+        let sweep = run(&bytes, &values, &options).unwrap();
+        assert_eq!(sweep.parameter, "n");
+        assert_eq!(
+            sweep.points.iter().map(|p| p.value).collect::<Vec<_>>(),
+            [10, 100, 1000]
+        );
+        assert!(sweep.points.iter().all(|p| p.times.len() == 3));
+        // Fuel is deterministic, so its slope is close to the loop's
+        let slope = sweep.fuel_fit().unwrap().slope;
+        assert!((slope - 2.0).abs() < 0.05, "{}", slope);
+
+        let csv = sweep.to_csv();
+        assert!(csv.starts_with("n,sample,time_ms,fuel\n10,0,"));
+        assert_eq!(csv.lines().count(), 10);
+        let plot = sweep.plot();
+        assert!(plot.contains("Fuel, slope 2."));
+        assert!(crate::export::parse(&plot).is_ok());
+
+        let limited = SweepOptions {
+            fuel_limit: Some(100_000),
+            ..options
+        };
+        assert_eq!(
+            run(&bytes, &values, &limited),
+            Err("The frame at 0s ran out of fuel".to_string())
+        );
+    }
+}
@@ -0,0 +1,931 @@
+use std::fmt::Write;
+
+use crate::viewbox::ViewBox;
+use crate::wasm::parse_color;
+use svg::node::element::path::{Command, Data, Position};
+use svg::node::element::tag::Type;
+use svg::node::Attributes;
+use svg::parser::Event;
+
+/// Default width of a TikZ picture, in centimeters.
+pub const TIKZ_WIDTH: f32 = 10.0;
+
+/// TikZ code for a frame, and what could not be converted.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Tikz {
+    pub source: String,
+    /// Elements, attributes and path commands that were skipped or
+    /// approximated, each listed once.
+    pub unsupported: Vec<String>,
+}
+
+// An affine transform [a, b, c, d, e, f] as in SVG's matrix(a b c d e f)
+type Matrix = [f32; 6];
+
+const IDENTITY: Matrix = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+fn multiply([a, b, c, d, e, f]: Matrix, [g, h, i, j, k, l]: Matrix) -> Matrix {
+    [
+        a * g + c * h,
+        b * g + d * h,
+        a * i + c * j,
+        b * i + d * j,
+        a * k + c * l + e,
+        b * k + d * l + f,
+    ]
+}
+
+fn apply([a, b, c, d, e, f]: Matrix, [x, y]: [f32; 2]) -> [f32; 2] {
+    [a * x + c * y + e, b * x + d * y + f]
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Anchor {
+    Start,
+    Middle,
+    End,
+}
+
+// Presentation attributes, inherited from the enclosing elements
+#[derive(Debug, Clone)]
+struct Style {
+    fill: Option<[u8; 3]>,
+    stroke: Option<[u8; 3]>,
+    // Alpha of the fill and stroke colors, kept apart from the opacity
+    // properties so that neither overwrites the other
+    fill_alpha: f32,
+    stroke_alpha: f32,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    opacity: f32,
+    stroke_width: f32,
+    dash_array: Option<Vec<f32>>,
+    line_cap: Option<&'static str>,
+    line_join: Option<&'static str>,
+    font_size: f32,
+    text_anchor: Anchor,
+    centered: bool,
+    transform: Matrix,
+}
+
+impl Style {
+    // How opaque the fill and the stroke are, before the group opacity
+    fn fill_opacity(&self) -> f32 {
+        self.fill_alpha * self.fill_opacity
+    }
+
+    fn stroke_opacity(&self) -> f32 {
+        self.stroke_alpha * self.stroke_opacity
+    }
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            fill: Some([0, 0, 0]),
+            stroke: None,
+            fill_alpha: 1.0,
+            stroke_alpha: 1.0,
+            fill_opacity: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+            stroke_width: 1.0,
+            dash_array: None,
+            line_cap: None,
+            line_join: None,
+            font_size: 16.0,
+            text_anchor: Anchor::Start,
+            centered: false,
+            transform: IDENTITY,
+        }
+    }
+}
+
+// The colors CSS names that demos are likely to use
+const NAMED_COLORS: &[(&str, [u8; 3])] = &[
+    ("black", [0, 0, 0]),
+    ("white", [255, 255, 255]),
+    ("red", [255, 0, 0]),
+    ("green", [0, 128, 0]),
+    ("lime", [0, 255, 0]),
+    ("blue", [0, 0, 255]),
+    ("yellow", [255, 255, 0]),
+    ("cyan", [0, 255, 255]),
+    ("magenta", [255, 0, 255]),
+    ("orange", [255, 165, 0]),
+    ("purple", [128, 0, 128]),
+    ("gray", [128, 128, 128]),
+    ("grey", [128, 128, 128]),
+    ("lightgray", [211, 211, 211]),
+    ("darkgray", [169, 169, 169]),
+    ("brown", [165, 42, 42]),
+    ("pink", [255, 192, 203]),
+    ("navy", [0, 0, 128]),
+    ("teal", [0, 128, 128]),
+];
+
+// Parse a paint into a color and its alpha. None for "none".
+fn parse_paint(value: &str) -> Result<Option<([u8; 3], f32)>, ()> {
+    let value = value.trim();
+    if value == "none" || value == "transparent" {
+        return Ok(None);
+    }
+    if let Some((_, rgb)) = NAMED_COLORS
+        .iter()
+        .find(|(name, _)| value.eq_ignore_ascii_case(name))
+    {
+        return Ok(Some((*rgb, 1.0)));
+    }
+    if let Some(hex) = value.strip_prefix('#').filter(|hex| hex.len() == 3) {
+        // #rgb is short for #rrggbb
+        let long: String = hex.chars().flat_map(|c| [c, c]).collect();
+        return parse_paint(&format!("#{}", long));
+    }
+    if let Some(rgba) = parse_color(value) {
+        let [r, g, b, a] = rgba.to_be_bytes();
+        return Ok(Some(([r, g, b], a as f32 / 255.0)));
+    }
+    let function = value
+        .strip_prefix("rgba(")
+        .or_else(|| value.strip_prefix("rgb("))
+        .and_then(|rest| rest.strip_suffix(')'))
+        .ok_or(())?;
+    let numbers: Vec<f32> = function
+        .split(',')
+        .map(|n| n.trim().parse())
+        .collect::<Result<_, _>>()
+        .map_err(|_| ())?;
+    match numbers[..] {
+        [r, g, b] => Ok(Some(([r as u8, g as u8, b as u8], 1.0))),
+        [r, g, b, a] => Ok(Some(([r as u8, g as u8, b as u8], a))),
+        _ => Err(()),
+    }
+}
+
+// Parse a list of numbers separated by whitespace and/or commas
+fn numbers(value: &str) -> Option<Vec<f32>> {
+    value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|n| !n.is_empty())
+        .map(|n| n.parse().ok())
+        .collect()
+}
+
+// Parse a length, ignoring a "px" unit
+fn length(value: &str) -> Option<f32> {
+    value.trim().trim_end_matches("px").parse().ok()
+}
+
+// Format a number with at most three decimals
+fn num(value: f32) -> String {
+    let s = format!("{:.3}", value);
+    let s = s.trim_end_matches('0').trim_end_matches('.');
+    match s {
+        "-0" | "" => "0".to_string(),
+        s => s.to_string(),
+    }
+}
+
+fn escape_latex(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Elements that are converted, or carry styles for the ones that are
+const ELEMENTS: &[&str] = &[
+    "g", "path", "polygon", "polyline", "line", "circle", "ellipse", "rect", "text",
+];
+
+// Replace the predefined XML entities
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+struct Converter {
+    view_box: ViewBox,
+    // Centimeters per user unit
+    scale: f32,
+    colors: Vec<[u8; 3]>,
+    commands: Vec<String>,
+    unsupported: Vec<String>,
+    // Styles of the open elements, innermost last
+    styles: Vec<Style>,
+    // Open elements whose content is skipped, such as <defs>
+    skipping: usize,
+    // Position, style and content of the open <text> element
+    text: Option<([f32; 2], Style, String)>,
+}
+
+impl Converter {
+    fn report(&mut self, item: String) {
+        if !self.unsupported.contains(&item) {
+            self.unsupported.push(item);
+        }
+    }
+
+    fn style(&self) -> &Style {
+        self.styles.last().expect("root style")
+    }
+
+    // Picture coordinates, in centimeters with y pointing up
+    fn point(&self, style: &Style, point: [f32; 2]) -> String {
+        let [x, y] = apply(style.transform, point);
+        format!(
+            "({},{})",
+            num((x - self.view_box.x as f32) * self.scale),
+            num(((self.view_box.y + self.view_box.height) as f32 - y) * self.scale)
+        )
+    }
+
+    // Average scale of a transform, for lengths such as radii
+    fn length(&self, style: &Style, length: f32) -> f32 {
+        let [a, b, c, d, _, _] = style.transform;
+        length * (a * d - b * c).abs().sqrt() * self.scale
+    }
+
+    fn color(&mut self, rgb: [u8; 3]) -> String {
+        let index = match self.colors.iter().position(|c| *c == rgb) {
+            Some(index) => index,
+            None => {
+                self.colors.push(rgb);
+                self.colors.len() - 1
+            }
+        };
+        format!("rg{}", index)
+    }
+
+    // The style of an element, from its own attributes and its parent's style
+    fn inherit(&mut self, attributes: &Attributes) -> Style {
+        let mut style = self.style().clone();
+        let mut properties: Vec<(String, String)> = attributes
+            .iter()
+            .map(|(name, value)| (name.clone(), value.to_string()))
+            .collect();
+        // Declarations in the style attribute override presentation attributes
+        properties.sort_by_key(|(name, _)| name == "style");
+        for (name, value) in properties {
+            if name == "style" {
+                for declaration in value.split(';') {
+                    if let Some((name, value)) = declaration.split_once(':') {
+                        self.apply_property(&mut style, name.trim(), value.trim());
+                    }
+                }
+            } else {
+                self.apply_property(&mut style, &name, &value);
+            }
+        }
+        style
+    }
+
+    fn apply_property(&mut self, style: &mut Style, name: &str, value: &str) {
+        let valid = match name {
+            "fill" | "stroke" => match parse_paint(value) {
+                Ok(paint) => {
+                    let (color, alpha) = paint.map_or((None, 1.0), |(c, a)| (Some(c), a));
+                    if name == "fill" {
+                        style.fill = color;
+                        style.fill_alpha = alpha;
+                    } else {
+                        style.stroke = color;
+                        style.stroke_alpha = alpha;
+                    }
+                    true
+                }
+                Err(()) => false,
+            },
+            "fill-opacity" | "stroke-opacity" | "opacity" => match value.parse::<f32>() {
+                Ok(v) => {
+                    // Fill and stroke opacity are inherited, while the
+                    // opacity of a group applies to everything in it
+                    match name {
+                        "fill-opacity" => style.fill_opacity = v,
+                        "stroke-opacity" => style.stroke_opacity = v,
+                        _ => style.opacity *= v,
+                    }
+                    true
+                }
+                Err(_) => false,
+            },
+            "stroke-width" => length(value).map(|w| style.stroke_width = w).is_some(),
+            "font-size" => length(value).map(|s| style.font_size = s).is_some(),
+            "stroke-dasharray" if value == "none" => {
+                style.dash_array = None;
+                true
+            }
+            "stroke-dasharray" => numbers(value).map(|d| style.dash_array = Some(d)).is_some(),
+            "stroke-linecap" => {
+                style.line_cap = match value {
+                    "round" => Some("round"),
+                    "square" => Some("rect"),
+                    _ => None,
+                };
+                true
+            }
+            "stroke-linejoin" => {
+                style.line_join = match value {
+                    "round" => Some("round"),
+                    "bevel" => Some("bevel"),
+                    _ => None,
+                };
+                true
+            }
+            "text-anchor" => {
+                style.text_anchor = match value {
+                    "middle" => Anchor::Middle,
+                    "end" => Anchor::End,
+                    _ => Anchor::Start,
+                };
+                true
+            }
+            "dominant-baseline" => {
+                style.centered = matches!(value, "middle" | "central");
+                true
+            }
+            "transform" => match parse_transform(value) {
+                Some(matrix) => {
+                    style.transform = multiply(style.transform, matrix);
+                    true
+                }
+                None => false,
+            },
+            // Geometry and attributes without a visual effect
+            "x" | "y" | "x1" | "y1" | "x2" | "y2" | "cx" | "cy" | "r" | "rx" | "ry" | "width"
+            | "height" | "d" | "points" | "viewBox" | "xmlns" | "xmlns:xlink" | "version"
+            | "id" | "class" | "font-family" | "fill-rule" | "vector-effect" => true,
+            _ => {
+                self.report(format!("attribute {}", name));
+                true
+            }
+        };
+        if !valid {
+            self.report(format!("{}=\"{}\"", name, value));
+        }
+    }
+
+    // TikZ options for drawing with `style`, or None if nothing is drawn
+    fn options(&mut self, style: &Style, fill: bool) -> Option<String> {
+        let mut options = Vec::new();
+        if let Some(stroke) = style.stroke {
+            options.push(format!("draw={}", self.color(stroke)));
+            options.push(format!(
+                "line width={}cm",
+                num(self.length(style, style.stroke_width))
+            ));
+            if style.stroke_opacity() < 1.0 {
+                options.push(format!("draw opacity={}", num(style.stroke_opacity())));
+            }
+            if let Some(dashes) = &style.dash_array {
+                let pattern: Vec<String> = dashes
+                    .chunks(2)
+                    .map(|dash| {
+                        let on = self.length(style, dash[0]);
+                        let off = self.length(style, *dash.get(1).unwrap_or(&dash[0]));
+                        format!("on {}cm off {}cm", num(on), num(off))
+                    })
+                    .collect();
+                options.push(format!("dash pattern={}", pattern.join(" ")));
+            }
+            options.extend(style.line_cap.map(|cap| format!("line cap={}", cap)));
+            options.extend(style.line_join.map(|join| format!("line join={}", join)));
+        }
+        if let (Some(color), true) = (style.fill, fill) {
+            options.push(format!("fill={}", self.color(color)));
+            if style.fill_opacity() < 1.0 {
+                options.push(format!("fill opacity={}", num(style.fill_opacity())));
+            }
+        }
+        if options.is_empty() {
+            return None;
+        }
+        if style.opacity < 1.0 {
+            options.push(format!("opacity={}", num(style.opacity)));
+        }
+        Some(options.join(", "))
+    }
+
+    fn draw(&mut self, style: &Style, fill: bool, path: String) {
+        if let Some(options) = self.options(style, fill) {
+            self.commands.push(format!("\\path[{}] {};", options, path));
+        }
+    }
+
+    fn element(&mut self, name: &str, attributes: &Attributes, style: &Style) {
+        let number = |name: &str| attributes.get(name).and_then(|v| length(v)).unwrap_or(0.0);
+        match name {
+            "path" => {
+                let d = attributes
+                    .get("d")
+                    .map(|d| d.to_string())
+                    .unwrap_or_default();
+                match Data::parse(&d) {
+                    Ok(data) => {
+                        let path = self.path(style, &data);
+                        if !path.is_empty() {
+                            self.draw(style, true, path);
+                        }
+                    }
+                    Err(_) => self.report(format!("d=\"{}\"", d)),
+                }
+            }
+            "polygon" | "polyline" => {
+                let points = attributes
+                    .get("points")
+                    .and_then(|p| numbers(p))
+                    .unwrap_or_default();
+                let mut path: Vec<String> = points
+                    .chunks_exact(2)
+                    .map(|p| self.point(style, [p[0], p[1]]))
+                    .collect();
+                if path.is_empty() {
+                    return;
+                }
+                if name == "polygon" {
+                    path.push("cycle".to_string());
+                }
+                self.draw(style, name == "polygon", path.join(" -- "));
+            }
+            "line" => {
+                let from = self.point(style, [number("x1"), number("y1")]);
+                let to = self.point(style, [number("x2"), number("y2")]);
+                self.draw(style, false, format!("{} -- {}", from, to));
+            }
+            "circle" => {
+                let center = self.point(style, [number("cx"), number("cy")]);
+                let radius = num(self.length(style, number("r")));
+                self.draw(
+                    style,
+                    true,
+                    format!("{} circle[radius={}cm]", center, radius),
+                );
+            }
+            "ellipse" => {
+                let center = self.point(style, [number("cx"), number("cy")]);
+                let rx = num(self.length(style, number("rx")));
+                let ry = num(self.length(style, number("ry")));
+                self.draw(
+                    style,
+                    true,
+                    format!("{} ellipse[x radius={}cm, y radius={}cm]", center, rx, ry),
+                );
+            }
+            "rect" => {
+                if attributes.contains_key("rx") || attributes.contains_key("ry") {
+                    self.report("rounded <rect>".to_string());
+                }
+                let [x, y, w, h] = [number("x"), number("y"), number("width"), number("height")];
+                let corners = [[x, y], [x + w, y], [x + w, y + h], [x, y + h]];
+                let mut path: Vec<String> = corners.iter().map(|p| self.point(style, *p)).collect();
+                path.push("cycle".to_string());
+                self.draw(style, true, path.join(" -- "));
+            }
+            // <g> only carries styles for its children
+            _ => {}
+        }
+    }
+
+    // TikZ path operations for SVG path data
+    fn path(&mut self, style: &Style, data: &Data) -> String {
+        let mut path = String::new();
+        let mut current = [0.0f32; 2];
+        let mut start = [0.0f32; 2];
+        // Second control point of the previous cubic or the control point of
+        // the previous quadratic curve, for the smooth variants
+        let mut last_cubic: Option<[f32; 2]> = None;
+        let mut last_quadratic: Option<[f32; 2]> = None;
+        let cubic = |path: &mut String, c1: [f32; 2], c2: [f32; 2], end: [f32; 2]| {
+            _ = write!(
+                path,
+                " .. controls {} and {} .. {}",
+                self.point(style, c1),
+                self.point(style, c2),
+                self.point(style, end)
+            );
+        };
+        let mut unsupported = Vec::new();
+        for command in data.iter() {
+            let (position, parameters) = match command {
+                Command::Move(p, params)
+                | Command::Line(p, params)
+                | Command::HorizontalLine(p, params)
+                | Command::VerticalLine(p, params)
+                | Command::QuadraticCurve(p, params)
+                | Command::SmoothQuadraticCurve(p, params)
+                | Command::CubicCurve(p, params)
+                | Command::SmoothCubicCurve(p, params)
+                | Command::EllipticalArc(p, params) => (*p, params.to_vec()),
+                Command::Close => {
+                    path.push_str(" -- cycle");
+                    current = start;
+                    last_cubic = None;
+                    last_quadratic = None;
+                    continue;
+                }
+            };
+            let relative = position == Position::Relative;
+            let offset = |p: [f32; 2], current: [f32; 2]| match relative {
+                true => [current[0] + p[0], current[1] + p[1]],
+                false => p,
+            };
+            let mut cubic_control = None;
+            let mut quadratic_control = None;
+            match command {
+                Command::Move(..) => {
+                    for (i, p) in parameters.chunks_exact(2).enumerate() {
+                        current = offset([p[0], p[1]], current);
+                        if i == 0 {
+                            start = current;
+                            if !path.is_empty() {
+                                path.push(' ');
+                            }
+                            path.push_str(&self.point(style, current));
+                        } else {
+                            _ = write!(path, " -- {}", self.point(style, current));
+                        }
+                    }
+                }
+                Command::Line(..) => {
+                    for p in parameters.chunks_exact(2) {
+                        current = offset([p[0], p[1]], current);
+                        _ = write!(path, " -- {}", self.point(style, current));
+                    }
+                }
+                Command::HorizontalLine(..) | Command::VerticalLine(..) => {
+                    let horizontal = matches!(command, Command::HorizontalLine(..));
+                    for v in parameters {
+                        let axis = if horizontal { 0 } else { 1 };
+                        current[axis] = if relative { current[axis] + v } else { v };
+                        _ = write!(path, " -- {}", self.point(style, current));
+                    }
+                }
+                Command::CubicCurve(..) | Command::SmoothCubicCurve(..) => {
+                    let smooth = matches!(command, Command::SmoothCubicCurve(..));
+                    let size = if smooth { 4 } else { 6 };
+                    for p in parameters.chunks_exact(size) {
+                        let c1 = match smooth {
+                            // Reflection of the previous control point
+                            true => last_cubic.map_or(current, |[x, y]| {
+                                [2.0 * current[0] - x, 2.0 * current[1] - y]
+                            }),
+                            false => offset([p[0], p[1]], current),
+                        };
+                        let c2 = offset([p[size - 4], p[size - 3]], current);
+                        let end = offset([p[size - 2], p[size - 1]], current);
+                        cubic(&mut path, c1, c2, end);
+                        cubic_control = Some(c2);
+                        last_cubic = Some(c2);
+                        current = end;
+                    }
+                }
+                Command::QuadraticCurve(..) | Command::SmoothQuadraticCurve(..) => {
+                    let smooth = matches!(command, Command::SmoothQuadraticCurve(..));
+                    let size = if smooth { 2 } else { 4 };
+                    for p in parameters.chunks_exact(size) {
+                        let control = match smooth {
+                            true => last_quadratic.map_or(current, |[x, y]| {
+                                [2.0 * current[0] - x, 2.0 * current[1] - y]
+                            }),
+                            false => offset([p[0], p[1]], current),
+                        };
+                        let end = offset([p[size - 2], p[size - 1]], current);
+                        // Degree elevation to a cubic curve
+                        let c1 = [
+                            current[0] + 2.0 / 3.0 * (control[0] - current[0]),
+                            current[1] + 2.0 / 3.0 * (control[1] - current[1]),
+                        ];
+                        let c2 = [
+                            end[0] + 2.0 / 3.0 * (control[0] - end[0]),
+                            end[1] + 2.0 / 3.0 * (control[1] - end[1]),
+                        ];
+                        cubic(&mut path, c1, c2, end);
+                        quadratic_control = Some(control);
+                        last_quadratic = Some(control);
+                        current = end;
+                    }
+                }
+                Command::EllipticalArc(..) => {
+                    // Approximated by a straight line to the end point
+                    unsupported.push("path command A (drawn as a line)".to_string());
+                    for p in parameters.chunks_exact(7) {
+                        current = offset([p[5], p[6]], current);
+                        _ = write!(path, " -- {}", self.point(style, current));
+                    }
+                }
+                Command::Close => unreachable!(),
+            }
+            last_cubic = cubic_control;
+            last_quadratic = quadratic_control;
+        }
+        for item in unsupported {
+            self.report(item);
+        }
+        path
+    }
+
+    fn tag(&mut self, name: &str, kind: Type, attributes: &Attributes) {
+        if self.skipping > 0 {
+            match kind {
+                Type::Start => self.skipping += 1,
+                Type::End => self.skipping -= 1,
+                Type::Empty => {}
+            }
+            return;
+        }
+        match (name, kind) {
+            ("text", Type::Start) => {
+                let style = self.inherit(attributes);
+                let number =
+                    |name: &str| attributes.get(name).and_then(|v| length(v)).unwrap_or(0.0);
+                self.text = Some(([number("x"), number("y")], style.clone(), String::new()));
+                self.styles.push(style);
+            }
+            ("text", Type::End) => {
+                self.styles.pop();
+                if let Some((position, style, content)) = self.text.take() {
+                    self.text_node(position, &style, content.trim());
+                }
+            }
+            ("tspan", Type::Start) if self.text.is_some() => {
+                self.report("<tspan> (merged into its <text>)".to_string());
+                let style = self.inherit(attributes);
+                self.styles.push(style);
+            }
+            (_, Type::End) => {
+                self.styles.pop();
+            }
+            ("title" | "desc" | "metadata", Type::Start) => self.skipping = 1,
+            // Skipped with their content, such as <defs> and <image>
+            (name, _) if !ELEMENTS.contains(&name) => {
+                self.report(format!("<{}>", name));
+                if kind == Type::Start {
+                    self.skipping = 1;
+                }
+            }
+            (_, Type::Start) => {
+                let style = self.inherit(attributes);
+                self.element(name, attributes, &style);
+                self.styles.push(style);
+            }
+            (_, Type::Empty) => {
+                let style = self.inherit(attributes);
+                self.element(name, attributes, &style);
+            }
+        }
+    }
+
+    fn text_node(&mut self, position: [f32; 2], style: &Style, content: &str) {
+        let Some(color) = style.fill else {
+            return;
+        };
+        if content.is_empty() {
+            return;
+        }
+        let anchor = match (style.text_anchor, style.centered) {
+            (Anchor::Start, false) => "base west",
+            (Anchor::Middle, false) => "base",
+            (Anchor::End, false) => "base east",
+            (Anchor::Start, true) => "west",
+            (Anchor::Middle, true) => "center",
+            (Anchor::End, true) => "east",
+        };
+        // 1cm is 28.45pt
+        let size = self.length(style, style.font_size) * 28.45;
+        let mut options = vec![
+            format!("anchor={}", anchor),
+            "inner sep=0".to_string(),
+            format!("text={}", self.color(color)),
+            format!(
+                "font=\\fontsize{{{}pt}}{{{}pt}}\\selectfont",
+                num(size),
+                num(size * 1.2)
+            ),
+        ];
+        let opacity = style.fill_opacity() * style.opacity;
+        if opacity < 1.0 {
+            options.push(format!("text opacity={}", num(opacity)));
+        }
+        let at = self.point(style, position);
+        self.commands.push(format!(
+            "\\node[{}] at {} {{{}}};",
+            options.join(", "),
+            at,
+            escape_latex(content)
+        ));
+    }
+}
+
+// Parse a transform list such as "translate(10 20) scale(2)"
+fn parse_transform(value: &str) -> Option<Matrix> {
+    let mut matrix = IDENTITY;
+    for part in value.split(')').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, args) = part.split_once('(')?;
+        let args = numbers(args)?;
+        let next = match (name.trim().trim_start_matches(','), &args[..]) {
+            ("matrix", &[a, b, c, d, e, f]) => [a, b, c, d, e, f],
+            ("translate", &[x]) => [1.0, 0.0, 0.0, 1.0, x, 0.0],
+            ("translate", &[x, y]) => [1.0, 0.0, 0.0, 1.0, x, y],
+            ("scale", &[s]) => [s, 0.0, 0.0, s, 0.0, 0.0],
+            ("scale", &[x, y]) => [x, 0.0, 0.0, y, 0.0, 0.0],
+            ("rotate", &[angle, ref center @ ..]) if center.is_empty() || center.len() == 2 => {
+                let (sin, cos) = angle.to_radians().sin_cos();
+                let rotation = [cos, sin, -sin, cos, 0.0, 0.0];
+                match center {
+                    [x, y] => multiply(
+                        [1.0, 0.0, 0.0, 1.0, *x, *y],
+                        multiply(rotation, [1.0, 0.0, 0.0, 1.0, -x, -y]),
+                    ),
+                    _ => rotation,
+                }
+            }
+            _ => return None,
+        };
+        matrix = multiply(matrix, next);
+    }
+    Some(matrix)
+}
+
+/// Convert a frame to a `tikzpicture` that is `width` centimeters wide.
+/// Coordinates are mapped from the frame's view box, with y flipped to
+/// point up. Unsupported elements are skipped and listed in the result.
+pub fn to_tikz(svg: &str, width: f32) -> Result<Tikz, String> {
+    let view_box = ViewBox::from_svg(svg).ok_or("Frame has no view box")?;
+    let mut converter = Converter {
+        view_box,
+        scale: width / view_box.width as f32,
+        colors: Vec::new(),
+        commands: Vec::new(),
+        unsupported: Vec::new(),
+        styles: vec![Style::default()],
+        skipping: 0,
+        text: None,
+    };
+    let mut root = true;
+    for event in svg::read(svg).map_err(|e| e.to_string())? {
+        match event {
+            Event::Tag("svg", kind, attributes) if root => {
+                root = false;
+                if kind == Type::Start {
+                    let style = converter.inherit(&attributes);
+                    converter.styles.push(style);
+                }
+            }
+            Event::Tag(name, kind, attributes) => converter.tag(name, kind, &attributes),
+            Event::Text(text) => {
+                if let (Some((_, _, content)), 0) = (&mut converter.text, converter.skipping) {
+                    content.push_str(&unescape_xml(text));
+                }
+            }
+            Event::Error(err) => return Err(format!("Failed to parse frame: {}", err)),
+            Event::Comment(_) | Event::Declaration(_) | Event::Instruction(_) => {}
+        }
+    }
+
+    let mut source = String::from("\\begin{tikzpicture}\n");
+    for (i, [r, g, b]) in converter.colors.iter().enumerate() {
+        _ = writeln!(
+            source,
+            "\\definecolor{{rg{}}}{{RGB}}{{{},{},{}}}",
+            i, r, g, b
+        );
+    }
+    _ = writeln!(
+        source,
+        "\\useasboundingbox (0,0) rectangle ({},{});",
+        num(width),
+        num(view_box.height as f32 * converter.scale)
+    );
+    for command in &converter.commands {
+        source.push_str(command);
+        source.push('\n');
+    }
+    source.push_str("\\end{tikzpicture}\n");
+    Ok(Tikz {
+        source,
+        unsupported: converter.unsupported,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(body: &str) -> Tikz {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="100" height="50" viewBox="0 0 100 50">{}</svg>"#,
+            body
+        );
+        to_tikz(&svg, 10.0).unwrap()
+    }
+
+    // The drawing commands, without the preamble
+    fn commands(tikz: &Tikz) -> Vec<&str> {
+        tikz.source
+            .lines()
+            .filter(|line| line.starts_with("\\path") || line.starts_with("\\node"))
+            .collect()
+    }
+
+    #[test]
+    fn shapes() {
+        let tikz = convert(
+            r##"<circle cx="50" cy="25" r="10" fill="red"/><line x1="0" y1="0" x2="100" y2="50" stroke="#00f" stroke-width="2"/><polygon points="0,0 10,0 10,10" fill="none" stroke="black"/><rect x="10" y="10" width="20" height="10" fill="#00ff0080"/>"##,
+        );
+        assert_eq!(
+            commands(&tikz),
+            [
+                r"\path[fill=rg0] (5,2.5) circle[radius=1cm];",
+                r"\path[draw=rg1, line width=0.2cm] (0,5) -- (10,0);",
+                r"\path[draw=rg2, line width=0.1cm] (0,5) -- (1,5) -- (1,4) -- cycle;",
+                r"\path[fill=rg3, fill opacity=0.502] (1,4) -- (3,4) -- (3,3) -- (1,3) -- cycle;",
+            ]
+        );
+        assert!(tikz.source.contains(r"\definecolor{rg0}{RGB}{255,0,0}"));
+        assert!(tikz.source.contains(r"\definecolor{rg1}{RGB}{0,0,255}"));
+        assert!(tikz
+            .source
+            .contains(r"\useasboundingbox (0,0) rectangle (10,5);"));
+        assert!(tikz.unsupported.is_empty(), "{:?}", tikz.unsupported);
+    }
+
+    #[test]
+    fn paths() {
+        let tikz = convert(
+            r#"<path d="M10,10 l10,0 V20 H10 z m50,0 Q70,0 80,10 C80,20 90,20 90,10 A5 5 0 0 1 100 10" fill="none" stroke="black"/>"#,
+        );
+        assert_eq!(
+            commands(&tikz),
+            [concat!(
+                r"\path[draw=rg0, line width=0.1cm] (1,4) -- (2,4) -- (2,3) -- (1,3) -- cycle",
+                r" (6,4) .. controls (6.667,4.667) and (7.333,4.667) .. (8,4)",
+                r" .. controls (8,3) and (9,3) .. (9,4) -- (10,4);"
+            )]
+        );
+        assert_eq!(tikz.unsupported, ["path command A (drawn as a line)"]);
+    }
+
+    #[test]
+    fn text_and_styles() {
+        let tikz = convert(
+            r#"<g transform="translate(10 5)" style="fill: white; opacity: 0.5"><text x="40" y="20" text-anchor="middle" dominant-baseline="middle" font-size="10">$1 &amp; 2</text></g>"#,
+        );
+        assert_eq!(
+            commands(&tikz),
+            [
+                r"\node[anchor=center, inner sep=0, text=rg0, font=\fontsize{28.45pt}{34.14pt}\selectfont, text opacity=0.5] at (5,2.5) {\$1 \& 2};"
+            ]
+        );
+    }
+
+    #[test]
+    fn color_alpha_and_opacity() {
+        // Attributes come in a different order every time
+        for _ in 0..20 {
+            let tikz = convert(
+                r##"<g fill-opacity="0.2" stroke-opacity="0.5"><rect width="10" height="10" fill="#ff000080" fill-opacity="0.5" stroke="rgba(0,0,255,0.5)"/></g>"##,
+            );
+            assert_eq!(
+                commands(&tikz),
+                [
+                    r"\path[draw=rg0, line width=0.1cm, draw opacity=0.25, fill=rg1, fill opacity=0.251] (0,5) -- (1,5) -- (1,4) -- (0,4) -- cycle;"
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_elements() {
+        let tikz = convert(
+            r#"<defs><linearGradient id="g"/></defs><image href="a.png"/><circle r="1" filter="url(#f)"/><rect width="1" height="1" rx="1"/>"#,
+        );
+        assert_eq!(
+            tikz.unsupported,
+            ["<defs>", "<image>", "attribute filter", "rounded <rect>"]
+        );
+        assert!(to_tikz("<svg>", 10.0).is_err());
+    }
+
+    #[test]
+    fn transforms() {
+        let rotate = parse_transform("rotate(90, 1, 1)").unwrap();
+        let [x, y] = apply(rotate, [2.0, 1.0]);
+        assert!((x - 1.0).abs() < 1e-6 && (y - 2.0).abs() < 1e-6);
+        let combined = parse_transform("translate(1,2) scale(2)").unwrap();
+        assert_eq!(apply(combined, [1.0, 1.0]), [3.0, 4.0]);
+        assert_eq!(parse_transform("skewX(10)"), None);
+    }
+}
@@ -0,0 +1,235 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+use std::str::FromStr;
+use svg::node::element::tag::Type;
+use svg::parser::Event;
+
+/// The user coordinate system of an SVG document, as given by the `viewBox`
+/// attribute of its root element. Kept in f64 so that deep zooms still
+/// resolve single pixels far from the origin.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ViewBox {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl ViewBox {
+    /// Read the view box of the root `<svg>` element. Documents without a
+    /// `viewBox` attribute fall back to their `width` and `height`.
+    pub fn from_svg(svg: &str) -> Option<Self> {
+        for event in svg::read(svg).ok()? {
+            match event {
+                Event::Tag("svg", Type::Start | Type::Empty, attributes) => {
+                    if let Some(view_box) = attributes.get("viewBox") {
+                        return view_box.parse().ok();
+                    }
+                    let length = |name| -> Option<f64> {
+                        let value = attributes.get(name)?;
+                        value.trim().trim_end_matches("px").parse().ok()
+                    };
+                    return Some(ViewBox {
+                        x: 0.0,
+                        y: 0.0,
+                        width: length("width")?,
+                        height: length("height")?,
+                    });
+                }
+                Event::Error(_) => return None,
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// Map a point from client coordinates (CSS pixels) to user coordinates.
+    /// `rect` is the `[left, top, width, height]` of the element showing the
+    /// view box with the default `xMidYMid meet` aspect ratio.
+    pub fn client_to_user(&self, rect: [f64; 4], [x, y]: [f64; 2]) -> [f64; 2] {
+        let [left, top, width, height] = rect;
+        let scale = (width / self.width).min(height / self.height);
+        let offset_x = left + (width - self.width * scale) / 2.0;
+        let offset_y = top + (height - self.height * scale) / 2.0;
+        [
+            self.x + (x - offset_x) / scale,
+            self.y + (y - offset_y) / scale,
+        ]
+    }
+
+    /// Scale the view box by `factor` around `point`, which stays in place.
+    /// Factors below 1 zoom in.
+    pub fn zoom_at(&self, [x, y]: [f64; 2], factor: f64) -> Self {
+        ViewBox {
+            x: x + (self.x - x) * factor,
+            y: y + (self.y - y) * factor,
+            width: self.width * factor,
+            height: self.height * factor,
+        }
+    }
+
+    /// Move the view box by `[dx, dy]` user units.
+    pub fn translate(&self, [dx, dy]: [f64; 2]) -> Self {
+        ViewBox {
+            x: self.x + dx,
+            y: self.y + dy,
+            ..*self
+        }
+    }
+
+    /// Replace the view box of the root `<svg>` element of `svg`, adding the
+    /// attribute if it is missing.
+    pub fn apply_to(&self, svg: &str) -> String {
+        let Some(Range { start, end }) = root_tag(svg) else {
+            return svg.to_string();
+        };
+        let tag = &svg[start..end];
+        // Span of the current attribute value, without quotes
+        let value = tag.find("viewBox").and_then(|i| {
+            let rest = tag[i + 7..].trim_start().strip_prefix('=')?.trim_start();
+            let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+            let value_start = end - rest.len() + 1;
+            let value_len = svg[value_start..end].find(quote)?;
+            Some(value_start..value_start + value_len)
+        });
+        match value {
+            Some(range) => format!("{}{}{}", &svg[..range.start], self, &svg[range.end..]),
+            None => format!(
+                "{} viewBox=\"{}\"{}",
+                &svg[..start + 4],
+                self,
+                &svg[start + 4..]
+            ),
+        }
+    }
+}
+
+/// Byte range of the start tag of the root `<svg>` element, from its `<` up
+/// to but excluding its `>`.
+pub fn root_tag(svg: &str) -> Option<Range<usize>> {
+    let start = svg.match_indices("<svg").map(|(i, _)| i).find(|&i| {
+        svg[i + 4..]
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_whitespace() || c == '>' || c == '/')
+    })?;
+    let end = svg[start..].find('>').map_or(svg.len(), |i| start + i);
+    Some(start..end)
+}
+
+impl FromStr for ViewBox {
+    type Err = String;
+
+    /// Parse `min-x min-y width height`, separated by whitespace and/or commas.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let numbers = s
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|n| !n.is_empty())
+            .map(|n| n.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Invalid viewBox {:?}: {}", s, e))?;
+        match numbers[..] {
+            [x, y, width, height] if width > 0.0 && height > 0.0 => Ok(ViewBox {
+                x,
+                y,
+                width,
+                height,
+            }),
+            _ => Err(format!("Invalid viewBox {:?}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ViewBox {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {} {}", self.x, self.y, self.width, self.height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_view_box_attribute() {
+        let svg = r#"<svg xmlns="http://www.w3.org/2000/svg" width="200" height="100" viewBox="-10, 0 100 50"><circle r="1"/></svg>"#;
+        assert_eq!(
+            ViewBox::from_svg(svg),
+            Some(ViewBox {
+                x: -10.0,
+                y: 0.0,
+                width: 100.0,
+                height: 50.0
+            })
+        );
+    }
+
+    #[test]
+    fn fall_back_to_size() {
+        let svg = r#"<svg width="200px" height="100"></svg>"#;
+        assert_eq!(
+            ViewBox::from_svg(svg),
+            Some(ViewBox {
+                x: 0.0,
+                y: 0.0,
+                width: 200.0,
+                height: 100.0
+            })
+        );
+        assert_eq!(ViewBox::from_svg("Panic!"), None);
+    }
+
+    #[test]
+    fn zoom_keeps_point_in_place() {
+        let view_box: ViewBox = "0 0 100 50".parse().unwrap();
+        let zoomed = view_box.zoom_at([20.0, 10.0], 0.5);
+        assert_eq!(zoomed, "10 5 50 25".parse().unwrap());
+        let rect = [0.0, 0.0, 200.0, 100.0];
+        assert_eq!(view_box.client_to_user(rect, [40.0, 20.0]), [20.0, 10.0]);
+        assert_eq!(zoomed.client_to_user(rect, [40.0, 20.0]), [20.0, 10.0]);
+        assert_eq!(
+            zoomed.translate([-10.0, 5.0]),
+            "0 10 50 25".parse().unwrap()
+        );
+    }
+
+    #[test]
+    fn deep_zoom_resolves_pixels() {
+        // Zoomed in 1e5 times on a point far from the origin
+        let view_box: ViewBox = "0 0 10000 10000".parse().unwrap();
+        let zoomed = view_box.zoom_at([9999.0, 9999.0], 1e-5);
+        let rect = [0.0, 0.0, 1000.0, 1000.0];
+        let [x0, _] = zoomed.client_to_user(rect, [500.0, 500.0]);
+        let [x1, _] = zoomed.client_to_user(rect, [501.0, 500.0]);
+        assert!((x1 - x0 - 1e-4).abs() < 1e-9);
+    }
+
+    #[test]
+    fn apply_to_svg() {
+        let view_box: ViewBox = "1 2 3 4".parse().unwrap();
+        assert_eq!(
+            view_box.apply_to(r#"<?xml version="1.0"?><svg viewBox='0 0 10 10'><svg/></svg>"#),
+            r#"<?xml version="1.0"?><svg viewBox='1 2 3 4'><svg/></svg>"#
+        );
+        assert_eq!(
+            view_box.apply_to(r#"<svg width="10" height="10"><g/></svg>"#),
+            r#"<svg viewBox="1 2 3 4" width="10" height="10"><g/></svg>"#
+        );
+        assert_eq!(
+            view_box.apply_to(r#"<svgx/><svg viewBox = "0 0 1 1">"#),
+            r#"<svgx/><svg viewBox = "1 2 3 4">"#
+        );
+        assert_eq!(view_box.apply_to("Panic!"), "Panic!");
+    }
+
+    #[test]
+    fn client_to_user_letterboxed() {
+        // A square view box shown in a 200x100 element is centered horizontally
+        let view_box: ViewBox = "0 0 10 10".parse().unwrap();
+        let rect = [100.0, 50.0, 200.0, 100.0];
+        assert_eq!(view_box.client_to_user(rect, [150.0, 50.0]), [0.0, 0.0]);
+        assert_eq!(view_box.client_to_user(rect, [200.0, 100.0]), [5.0, 5.0]);
+        assert_eq!(view_box.client_to_user(rect, [250.0, 150.0]), [10.0, 10.0]);
+    }
+}
@@ -0,0 +1,3128 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::CStr;
+use std::fmt::Display;
+use std::str;
+use wasmi::core::{TrapCode, F32, F64};
+use wasmi::{self, core::ValType, Config, Engine, Extern, FuncType, Instance, Module, Store};
+use wasmi::{Linker, Val};
+use wasmparser::{Parser, Payload};
+use web_time::Instant;
+
+use crate::viewbox::ViewBox;
+
+/// Name of the custom section that holds the JSON parameter schema.
+pub const SCHEMA_SECTION: &str = "rgeometry.schema";
+
+/// Name of the custom section that holds the JSON module metadata.
+pub const META_SECTION: &str = "rgeometry.meta";
+
+/// JSON schema for render parameters. Each parameter can be one of:
+/// ```json
+/// {
+///   "type": "time"                     // Represents time, typically for animations
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "frame"                    // Number of frames rendered before this one (i64)
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "delta_time"               // Seconds since the previous frame (f64), 0 at first
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "viewport"                 // Size of the viewer, passed as three f32
+/// }                                    // values: width, height, device pixel ratio
+/// ```
+/// ```json
+/// {
+///   "type": "camera"                   // Region the viewer is zoomed in on, passed as
+/// }                                    // four f32 values: x, y, width, height in the
+///                                      // frame's user units. All 0 when not zoomed
+/// ```
+/// ```json
+/// {
+///   "type": "range_f32",
+///   "min": 0.0,                        // Minimum value (float)
+///   "max": 100.0,                      // Maximum value (float)
+///   "default": 50.0                    // Default value (float)
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "range_i32",
+///   "min": 0,                          // Minimum value (integer)
+///   "max": 100,                        // Maximum value (integer)
+///   "default": 50                      // Default value (integer)
+/// }
+/// ```
+/// `range_f64` and `range_i64` take the same fields and are passed as f64
+/// and i64 respectively. All range types accept the optional fields of
+/// `RangeOptions`.
+/// ```json
+/// {
+///   "type": "bool",
+///   "default": true                    // Passed as an i32, 0 or 1
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "enum",
+///   "options": ["graham", "melkman"],  // Labels of the choices
+///   "default": 0                       // Index of the default choice, passed as i32
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "color",
+///   "default": "#ff0000"               // #rrggbb or #rrggbbaa, passed as an i32
+/// }                                    // packed as 0xRRGGBBAA
+/// ```
+/// ```json
+/// {
+///   "type": "seed",                    // Seed for a random number generator, passed
+///   "default": 42,                     // as an i64. Defaults to 0
+///   "reroll_every": 1.0                // Optional, pick a new seed every so many
+/// }                                    // seconds unless the user locks it
+/// ```
+/// ```json
+/// {
+///   "type": "point2d",
+///   "default": [50.0, 50.0],           // Initial position, passed as two f32 values
+///   "bounds": [0.0, 0.0, 100.0, 100.0] // Optional [min_x, min_y, max_x, max_y]
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "point_set",
+///   "default": [[10.0, 10.0], [90.0, 50.0]], // Optional initial points
+///   "bounds": [0.0, 0.0, 100.0, 100.0] // Optional [min_x, min_y, max_x, max_y]
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "text",
+///   "default": "POLYGON ((0 0, 10 0, 0 10, 0 0))" // Optional initial text
+/// }
+/// ```
+/// ```json
+/// {
+///   "type": "file",
+///   "extensions": ["csv", "txt"]       // Optional filter for the file picker
+/// }
+/// ```
+///
+/// Point sets, text and files are copied into linear memory and passed as two
+/// i32 values: a pointer and a length. Point sets are consecutive
+/// little-endian (x, y) f32 pairs and their length is the number of points.
+/// Text is UTF-8 and file contents are passed as is, both with their length
+/// in bytes. The buffer is allocated with the module's `alloc(size) -> ptr`
+/// export and passed again to later frames, until the value changes. It is
+/// then released with `dealloc(ptr, size)` before the next frame, as are all
+/// buffers when an allocation fails.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum SchemaType {
+    Time,
+    Frame,
+    DeltaTime,
+    Viewport,
+    Camera,
+    RangeF32 {
+        min: f32,
+        max: f32,
+        default: f32,
+        #[serde(flatten)]
+        options: RangeOptions,
+    },
+    RangeI32 {
+        min: i32,
+        max: i32,
+        default: i32,
+        #[serde(flatten)]
+        options: RangeOptions,
+    },
+    RangeF64 {
+        min: f64,
+        max: f64,
+        default: f64,
+        #[serde(flatten)]
+        options: RangeOptions,
+    },
+    RangeI64 {
+        min: i64,
+        max: i64,
+        default: i64,
+        #[serde(flatten)]
+        options: RangeOptions,
+    },
+    Bool {
+        default: bool,
+    },
+    Enum {
+        options: Vec<String>,
+        default: u32,
+    },
+    Color {
+        default: String,
+    },
+    Seed {
+        #[serde(default)]
+        default: i64,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        reroll_every: Option<f64>,
+    },
+    Point2d {
+        default: [f32; 2],
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bounds: Option<[f32; 4]>,
+    },
+    PointSet {
+        #[serde(default)]
+        default: Vec<[f32; 2]>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        bounds: Option<[f32; 4]>,
+    },
+    Text {
+        #[serde(default)]
+        default: String,
+    },
+    File {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        extensions: Vec<String>,
+    },
+}
+
+/// Optional fields of the range types:
+/// ```json
+/// {
+///   "step": 0.5,                       // Granularity of the slider
+///   "scale": "log",                    // "linear" (default) or "log", which needs min > 0
+///   "unit": "px",                      // Shown after the value
+///   "precision": 1                     // Number of decimals shown for floats
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RangeOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<f64>,
+    #[serde(default, skip_serializing_if = "Scale::is_linear")]
+    pub scale: Scale,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub precision: Option<u32>,
+}
+
+impl RangeOptions {
+    fn validate(&self, min: f64, errors: &mut Vec<(&'static str, String)>) {
+        if let Some(step) = self.step {
+            if !step.is_finite() || step <= 0.0 {
+                errors.push(("step", format!("must be positive, got {}", step)));
+            }
+        }
+        if self.scale == Scale::Log && min <= 0.0 {
+            errors.push(("scale", format!("log scale needs min > 0, got {}", min)));
+        }
+        if self.precision.is_some_and(|precision| precision > 20) {
+            errors.push(("precision", "must be at most 20".to_string()));
+        }
+    }
+}
+
+/// How slider positions map to values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Scale {
+    #[default]
+    Linear,
+    Log,
+}
+
+impl Scale {
+    fn is_linear(&self) -> bool {
+        *self == Scale::Linear
+    }
+
+    /// Position of `value` on a slider from `min` to `max`, between 0 and 1.
+    pub fn position(self, min: f64, max: f64, value: f64) -> f64 {
+        let (min, max, value) = match self {
+            Scale::Linear => (min, max, value),
+            Scale::Log => (min.ln(), max.ln(), value.ln()),
+        };
+        if max > min {
+            ((value - min) / (max - min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+
+    /// Inverse of `position`.
+    pub fn value(self, min: f64, max: f64, position: f64) -> f64 {
+        // The ends are exact, log sliders would miss them by rounding
+        if position <= 0.0 {
+            return min;
+        } else if position >= 1.0 {
+            return max;
+        }
+        match self {
+            Scale::Linear => min + (max - min) * position,
+            Scale::Log => (min.ln() + (max.ln() - min.ln()) * position).exp(),
+        }
+        .clamp(min, max)
+    }
+}
+
+impl SchemaType {
+    fn val_types(&self) -> &'static [ValType] {
+        match self {
+            SchemaType::Time => &[ValType::F64], // Time is typically passed as f64
+            SchemaType::Frame => &[ValType::I64],
+            SchemaType::DeltaTime => &[ValType::F64],
+            SchemaType::Viewport => &[ValType::F32, ValType::F32, ValType::F32],
+            SchemaType::Camera => &[ValType::F32; 4],
+            SchemaType::RangeF32 { .. } => &[ValType::F32],
+            SchemaType::RangeI32 { .. } => &[ValType::I32],
+            SchemaType::RangeF64 { .. } => &[ValType::F64],
+            SchemaType::RangeI64 { .. } => &[ValType::I64],
+            SchemaType::Bool { .. } => &[ValType::I32],
+            SchemaType::Enum { .. } => &[ValType::I32],
+            SchemaType::Color { .. } => &[ValType::I32],
+            SchemaType::Seed { .. } => &[ValType::I64],
+            SchemaType::Point2d { .. } => &[ValType::F32, ValType::F32],
+            SchemaType::PointSet { .. } => &[ValType::I32, ValType::I32],
+            SchemaType::Text { .. } => &[ValType::I32, ValType::I32],
+            SchemaType::File { .. } => &[ValType::I32, ValType::I32],
+        }
+    }
+
+    // Whether the value is copied into guest memory rather than passed as
+    // plain arguments
+    fn uses_guest_memory(&self) -> bool {
+        matches!(
+            self,
+            SchemaType::PointSet { .. } | SchemaType::Text { .. } | SchemaType::File { .. }
+        )
+    }
+
+    /// Parse a value written as in a URL query: numbers, `true`/`false`, enum
+    /// option names or indices, `#rrggbb[aa]` colors, `x,y` points and
+    /// `x,y;x,y` point sets. Text is taken as is. Returns None for values
+    /// that are malformed or don't pass `check_value`, and for files.
+    pub fn parse_value(&self, s: &str) -> Option<Value> {
+        fn point(s: &str) -> Option<[f32; 2]> {
+            let (x, y) = s.split_once(',')?;
+            Some([x.trim().parse().ok()?, y.trim().parse().ok()?])
+        }
+        let value = match self {
+            SchemaType::RangeF32 { .. } => Value::F32(s.parse().ok()?),
+            SchemaType::RangeI32 { .. } => Value::I32(s.parse().ok()?),
+            SchemaType::RangeF64 { .. } => Value::F64(s.parse().ok()?),
+            SchemaType::RangeI64 { .. } => Value::I64(s.parse().ok()?),
+            SchemaType::Bool { .. } => match s {
+                "true" | "1" => Value::I32(1),
+                "false" | "0" => Value::I32(0),
+                _ => return None,
+            },
+            SchemaType::Enum { options, .. } => match options.iter().position(|o| o == s) {
+                Some(i) => Value::I32(i as i32),
+                None => Value::I32(s.parse().ok()?),
+            },
+            SchemaType::Color { .. } => Value::I32(parse_color(s)? as i32),
+            SchemaType::Seed { .. } => Value::I64(s.parse().ok()?),
+            SchemaType::Point2d { .. } => Value::Point2d(point(s)?),
+            SchemaType::PointSet { .. } => Value::PointSet(
+                s.split(';')
+                    .filter(|p| !p.is_empty())
+                    .map(point)
+                    .collect::<Option<_>>()?,
+            ),
+            SchemaType::Text { .. } => Value::Text(s.to_string()),
+            SchemaType::Time
+            | SchemaType::Frame
+            | SchemaType::DeltaTime
+            | SchemaType::Viewport
+            | SchemaType::Camera
+            | SchemaType::File { .. } => return None,
+        };
+        self.check_value(&value).ok()?;
+        Some(value)
+    }
+
+    /// Like `parse_value`, but also takes JSON booleans and numbers.
+    pub fn parse_json_value(&self, json: &serde_json::Value) -> Option<Value> {
+        match json {
+            serde_json::Value::String(s) => self.parse_value(s),
+            other => self.parse_value(&other.to_string()),
+        }
+    }
+
+    /// Inverse of `parse_value`.
+    pub fn format_value(&self, value: &Value) -> Option<String> {
+        match (self, value) {
+            (SchemaType::Bool { .. }, Value::I32(v)) => Some((*v != 0).to_string()),
+            (SchemaType::Enum { options, .. }, Value::I32(v)) => {
+                options.get(usize::try_from(*v).ok()?).cloned()
+            }
+            (SchemaType::Color { .. }, Value::I32(v)) => Some(format_color(*v as u32)),
+            (SchemaType::File { .. }, _) => None,
+            (_, Value::F32(v)) => Some(v.to_string()),
+            (_, Value::I32(v)) => Some(v.to_string()),
+            (_, Value::F64(v)) => Some(v.to_string()),
+            (_, Value::I64(v)) => Some(v.to_string()),
+            (_, Value::Point2d([x, y])) => Some(format!("{},{}", x, y)),
+            (_, Value::PointSet(points)) => Some(
+                points
+                    .iter()
+                    .map(|[x, y]| format!("{},{}", x, y))
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            ),
+            (_, Value::Text(text)) => Some(text.clone()),
+            (_, Value::Bytes(_)) => None,
+        }
+    }
+
+    // Value passed to the module until the user picks another one. None for
+    // parameters that are driven by the host, such as time.
+    pub fn default_value(&self) -> Option<Value> {
+        match self {
+            SchemaType::Time
+            | SchemaType::Frame
+            | SchemaType::DeltaTime
+            | SchemaType::Viewport
+            | SchemaType::Camera => None,
+            SchemaType::RangeF32 { default, .. } => Some(Value::F32(*default)),
+            SchemaType::RangeI32 { default, .. } => Some(Value::I32(*default)),
+            SchemaType::RangeF64 { default, .. } => Some(Value::F64(*default)),
+            SchemaType::RangeI64 { default, .. } => Some(Value::I64(*default)),
+            SchemaType::Bool { default } => Some(Value::I32(*default as i32)),
+            SchemaType::Enum { default, .. } => Some(Value::I32(*default as i32)),
+            SchemaType::Color { default } => Some(Value::I32(parse_color(default)? as i32)),
+            SchemaType::Seed { default, .. } => Some(Value::I64(*default)),
+            SchemaType::Point2d { default, .. } => Some(Value::Point2d(*default)),
+            SchemaType::PointSet { default, .. } => Some(Value::PointSet(default.clone())),
+            SchemaType::Text { default } => Some(Value::Text(default.clone())),
+            SchemaType::File { .. } => Some(Value::Bytes(Vec::new())),
+        }
+    }
+
+    // Check that a user-supplied value is acceptable for this parameter.
+    fn check_value(&self, value: &Value) -> Result<(), String> {
+        let ok = match (self, value) {
+            (SchemaType::RangeF32 { min, max, .. }, Value::F32(v)) => (*min..=*max).contains(v),
+            (SchemaType::RangeI32 { min, max, .. }, Value::I32(v)) => (*min..=*max).contains(v),
+            (SchemaType::RangeF64 { min, max, .. }, Value::F64(v)) => (*min..=*max).contains(v),
+            (SchemaType::RangeI64 { min, max, .. }, Value::I64(v)) => (*min..=*max).contains(v),
+            (SchemaType::Bool { .. }, Value::I32(v)) => *v == 0 || *v == 1,
+            (SchemaType::Enum { options, .. }, Value::I32(v)) => {
+                usize::try_from(*v).is_ok_and(|v| v < options.len())
+            }
+            (SchemaType::Color { .. }, Value::I32(_)) => true,
+            (SchemaType::Seed { .. }, Value::I64(_)) => true,
+            (SchemaType::Point2d { bounds, .. }, Value::Point2d(point)) => {
+                point.iter().all(|c| c.is_finite())
+                    && bounds.is_none_or(|bounds| in_bounds(*point, bounds))
+            }
+            (SchemaType::PointSet { bounds, .. }, Value::PointSet(points)) => {
+                points.iter().all(|point| {
+                    point.iter().all(|c| c.is_finite())
+                        && bounds.is_none_or(|bounds| in_bounds(*point, bounds))
+                })
+            }
+            (SchemaType::Text { .. }, Value::Text(text)) => text.len() <= MAX_BUFFER_BYTES,
+            (SchemaType::File { .. }, Value::Bytes(bytes)) => bytes.len() <= MAX_BUFFER_BYTES,
+            _ => false,
+        };
+        if ok {
+            Ok(())
+        } else if let Value::Text(_) | Value::Bytes(_) = value {
+            // Don't dump whole files into the error message
+            Err(format!(
+                "Value is not valid for {:?} (limit is {} bytes)",
+                self, MAX_BUFFER_BYTES
+            ))
+        } else {
+            Err(format!("{:?} is not a valid value for {:?}", value, self))
+        }
+    }
+}
+
+/// A schema entry: a parameter type plus optional fields that identify and
+/// present it. All of them may be added to any entry:
+/// ```json
+/// {
+///   "type": "enum",
+///   "options": ["graham", "melkman"],
+///   "default": 0,
+///   "name": "algorithm",               // Stable identifier, used in URLs
+///   "label": "Algorithm",              // Shown next to the control
+///   "description": "Convex hull algorithm",
+///   "group": "Hull",                   // Controls are grouped by this title
+///   "visible_if": {                    // Hide the control unless the named
+///     "name": "show_hull",             // parameter has the given value
+///     "equals": true
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Parameter {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible_if: Option<Condition>,
+    #[serde(flatten)]
+    pub ty: SchemaType,
+}
+
+impl From<SchemaType> for Parameter {
+    fn from(ty: SchemaType) -> Self {
+        Parameter {
+            name: None,
+            label: None,
+            description: None,
+            group: None,
+            visible_if: None,
+            ty,
+        }
+    }
+}
+
+impl Parameter {
+    /// Text shown next to the control: the label, else the name, else the
+    /// position in the schema.
+    pub fn display_label(&self, index: usize) -> String {
+        self.label
+            .clone()
+            .or_else(|| self.name.clone())
+            .unwrap_or_else(|| format!("Parameter {}", index))
+    }
+}
+
+/// Condition on the value of another, named parameter. `equals` is written
+/// like the value in a URL query (see `SchemaType::parse_value`), but JSON
+/// booleans and numbers are accepted as well.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Condition {
+    pub name: String,
+    pub equals: serde_json::Value,
+}
+
+impl Condition {
+    fn expected(&self, target: &SchemaType) -> Option<Value> {
+        target.parse_json_value(&self.equals)
+    }
+}
+
+/// A named set of parameter values. Keys are parameter names, or positions
+/// for unnamed entries, and values are written as in `Condition::equals`:
+/// ```json
+/// {
+///   "name": "Collinear points",
+///   "values": { "points": "0,0;1,1;2,2", "show_hull": true, "3": 0.5 }
+/// }
+/// ```
+/// Entries left out keep their default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Preset {
+    pub name: String,
+    #[serde(default)]
+    pub values: BTreeMap<String, serde_json::Value>,
+}
+
+impl Preset {
+    /// Record the given values. File contents are left out.
+    pub fn capture(name: String, schema: &Schema, values: &HashMap<usize, Value>) -> Self {
+        let values = values
+            .iter()
+            .filter_map(|(index, value)| {
+                let key = parameter_key(schema, *index)?;
+                let text = schema[*index].ty.format_value(value)?;
+                Some((key, serde_json::Value::String(text)))
+            })
+            .collect();
+        Preset { name, values }
+    }
+
+    /// The values of the preset by schema index. Fails on keys that don't
+    /// name an entry and on values that don't suit it.
+    pub fn resolve(&self, schema: &Schema) -> Result<Vec<(usize, Value)>, String> {
+        self.values
+            .iter()
+            .map(|(key, json)| {
+                let index = parameter_by_key(schema, key)
+                    .ok_or_else(|| format!("no parameter is named {:?}", key))?;
+                let value = schema[index]
+                    .ty
+                    .parse_json_value(json)
+                    .ok_or_else(|| format!("{} is not a valid value for {:?}", json, key))?;
+                Ok((index, value))
+            })
+            .collect()
+    }
+
+    /// Like `resolve`, but skip keys and values that don't suit the schema,
+    /// e.g. to carry values over to another build of a module.
+    pub fn resolve_known(&self, schema: &Schema) -> Vec<(usize, Value)> {
+        self.values
+            .iter()
+            .filter_map(|(key, json)| {
+                let single = Preset {
+                    name: String::new(),
+                    values: BTreeMap::from([(key.clone(), json.clone())]),
+                };
+                single.resolve(schema).ok()?.pop()
+            })
+            .collect()
+    }
+}
+
+/// Check that every preset resolves against the schema.
+pub fn validate_presets(presets: &[Preset], schema: &Schema) -> Result<(), String> {
+    let errors: Vec<String> = presets
+        .iter()
+        .filter_map(|preset| {
+            let err = preset.resolve(schema).err()?;
+            Some(format!("{:?}: {}", preset.name, err))
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Invalid preset {}", errors.join("; ")))
+    }
+}
+
+pub type Schema = Vec<Parameter>;
+
+/// Position of the entry called `name`.
+pub fn parameter_index(schema: &Schema, name: &str) -> Option<usize> {
+    schema
+        .iter()
+        .position(|param| param.name.as_deref() == Some(name))
+}
+
+/// What identifies entry `index` across versions of a module: its name, or
+/// its position if it has none.
+pub fn parameter_key(schema: &Schema, index: usize) -> Option<String> {
+    let param = schema.get(index)?;
+    Some(param.name.clone().unwrap_or_else(|| index.to_string()))
+}
+
+/// Position of the entry a `parameter_key` identifies.
+pub fn parameter_by_key(schema: &Schema, key: &str) -> Option<usize> {
+    parameter_index(schema, key).or_else(|| key.parse().ok().filter(|index| *index < schema.len()))
+}
+
+/// Whether the control for entry `index` should be shown, given the values
+/// the user picked so far. Entries whose condition refers to a hidden entry
+/// are hidden as well.
+pub fn is_visible(schema: &Schema, index: usize, values: &HashMap<usize, Value>) -> bool {
+    let mut index = index;
+    // Bounded by the schema length in case of cycles
+    for _ in 0..schema.len() {
+        let Some(condition) = &schema[index].visible_if else {
+            return true;
+        };
+        let Some(target) = parameter_index(schema, &condition.name) else {
+            return true;
+        };
+        let ty = &schema[target].ty;
+        let value = values.get(&target).cloned().or_else(|| ty.default_value());
+        if value.is_none() || value != condition.expected(ty) {
+            return false;
+        }
+        index = target;
+    }
+    true
+}
+
+/// Value of a user-controlled parameter.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Value {
+    F32(f32),
+    I32(i32),
+    F64(f64),
+    I64(i64),
+    Point2d([f32; 2]),
+    PointSet(Vec<[f32; 2]>),
+    Text(String),
+    Bytes(Vec<u8>),
+}
+
+impl Value {
+    // Arguments passed to request_animation_frame for this value. Values that
+    // live in guest memory are passed as the (ptr, len) of their buffer instead.
+    fn to_vals(&self) -> Vec<Val> {
+        match self {
+            Value::F32(v) => vec![Val::F32(F32::from_float(*v))],
+            Value::I32(v) => vec![Val::I32(*v)],
+            Value::F64(v) => vec![Val::F64(F64::from_float(*v))],
+            Value::I64(v) => vec![Val::I64(*v)],
+            Value::Point2d([x, y]) => {
+                vec![Val::F32(F32::from_float(*x)), Val::F32(F32::from_float(*y))]
+            }
+            Value::PointSet(_) | Value::Text(_) | Value::Bytes(_) => Vec::new(),
+        }
+    }
+
+    // Contents of the guest memory buffer and the length passed alongside
+    // its pointer
+    fn guest_bytes(&self) -> Option<(Vec<u8>, i32)> {
+        match self {
+            Value::PointSet(points) => {
+                let bytes = points.iter().flatten().flat_map(|c| c.to_le_bytes());
+                Some((bytes.collect(), points.len() as i32))
+            }
+            Value::Text(text) => Some((text.as_bytes().to_vec(), text.len() as i32)),
+            Value::Bytes(bytes) => Some((bytes.clone(), bytes.len() as i32)),
+            _ => None,
+        }
+    }
+}
+
+// A parameter copied into guest memory. Buffers are kept across frames and
+// only replaced when the parameter changes.
+#[derive(Debug, Clone, Copy)]
+struct GuestBuffer {
+    index: usize,
+    ptr: i32,
+    size: i32,
+    len: i32,
+}
+
+fn in_bounds([x, y]: [f32; 2], [min_x, min_y, max_x, max_y]: [f32; 4]) -> bool {
+    (min_x..=max_x).contains(&x) && (min_y..=max_y).contains(&y)
+}
+
+/// Parse a `#rrggbb` or `#rrggbbaa` color into a packed `0xRRGGBBAA` value.
+/// Colors without an alpha component are opaque.
+pub fn parse_color(color: &str) -> Option<u32> {
+    let hex = color.strip_prefix('#')?;
+    if !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    match hex.len() {
+        6 => Some(value << 8 | 0xff),
+        8 => Some(value),
+        _ => None,
+    }
+}
+
+/// Format a packed `0xRRGGBBAA` value as `#rrggbbaa`.
+pub fn format_color(rgba: u32) -> String {
+    format!("#{:08x}", rgba)
+}
+
+/// Upper bound on the number of schema entries a module may declare.
+pub const MAX_SCHEMA_ENTRIES: usize = 64;
+
+/// Upper bound on the size of a text or file parameter.
+pub const MAX_BUFFER_BYTES: usize = 16 << 20;
+
+impl SchemaType {
+    // Semantic checks that serde can't express. Each error names the
+    // offending field.
+    fn validate(&self) -> Vec<(&'static str, String)> {
+        let mut errors = Vec::new();
+        match *self {
+            SchemaType::Time
+            | SchemaType::Frame
+            | SchemaType::DeltaTime
+            | SchemaType::Viewport
+            | SchemaType::Camera
+            | SchemaType::Bool { .. } => {}
+            SchemaType::Text { ref default } => {
+                if default.len() > MAX_BUFFER_BYTES {
+                    errors.push((
+                        "default",
+                        format!("must be at most {} bytes", MAX_BUFFER_BYTES),
+                    ));
+                }
+            }
+            SchemaType::File { ref extensions } => {
+                for extension in extensions {
+                    if extension.is_empty() || !extension.chars().all(char::is_alphanumeric) {
+                        errors.push((
+                            "extensions",
+                            format!(
+                                "{:?} must be alphanumeric, without a leading dot",
+                                extension
+                            ),
+                        ));
+                    }
+                }
+            }
+            SchemaType::RangeF32 {
+                min,
+                max,
+                default,
+                ref options,
+            } => {
+                validate_float_range(min, max, default, &mut errors);
+                options.validate(min.into(), &mut errors);
+            }
+            SchemaType::RangeI32 {
+                min,
+                max,
+                default,
+                ref options,
+            } => {
+                validate_range(min, max, default, &mut errors);
+                options.validate(min.into(), &mut errors);
+            }
+            SchemaType::RangeF64 {
+                min,
+                max,
+                default,
+                ref options,
+            } => {
+                validate_float_range(min, max, default, &mut errors);
+                options.validate(min, &mut errors);
+            }
+            SchemaType::RangeI64 {
+                min,
+                max,
+                default,
+                ref options,
+            } => {
+                validate_range(min, max, default, &mut errors);
+                options.validate(min as f64, &mut errors);
+            }
+            SchemaType::Enum {
+                ref options,
+                default,
+            } => {
+                if options.is_empty() {
+                    errors.push(("options", "must not be empty".to_string()));
+                } else if default as usize >= options.len() {
+                    errors.push((
+                        "default",
+                        format!(
+                            "index {} is out of bounds for {} options",
+                            default,
+                            options.len()
+                        ),
+                    ));
+                }
+                for (i, option) in options.iter().enumerate() {
+                    if options[..i].contains(option) {
+                        errors.push(("options", format!("duplicate option {:?}", option)));
+                    }
+                }
+            }
+            SchemaType::Color { ref default } => {
+                if parse_color(default).is_none() {
+                    errors.push((
+                        "default",
+                        format!("{:?} is not a #rrggbb or #rrggbbaa color", default),
+                    ));
+                }
+            }
+            SchemaType::Seed { reroll_every, .. } => {
+                if let Some(seconds) = reroll_every.filter(|s| !s.is_finite() || *s <= 0.0) {
+                    errors.push(("reroll_every", format!("must be positive, got {}", seconds)));
+                }
+            }
+            SchemaType::PointSet {
+                ref default,
+                bounds,
+            } => {
+                let bounds = bounds.filter(|bounds| validate_bounds(*bounds, &mut errors));
+                for (i, point) in default.iter().enumerate() {
+                    if !point.iter().all(|c| c.is_finite()) {
+                        errors.push(("default", format!("point {} must be finite", i)));
+                    } else if bounds.is_some_and(|bounds| !in_bounds(*point, bounds)) {
+                        errors.push((
+                            "default",
+                            format!("point {} {:?} is outside the bounds", i, point),
+                        ));
+                    }
+                }
+            }
+            SchemaType::Point2d { default, bounds } => {
+                let bounds = bounds.filter(|bounds| validate_bounds(*bounds, &mut errors));
+                if !default.iter().all(|c| c.is_finite()) {
+                    errors.push(("default", format!("must be finite, got {:?}", default)));
+                } else if let Some(bounds) = bounds.filter(|bounds| !in_bounds(default, *bounds)) {
+                    errors.push((
+                        "default",
+                        format!("{:?} is outside the bounds {:?}", default, bounds),
+                    ));
+                }
+            }
+        }
+        errors
+    }
+}
+
+// Returns whether the bounds are usable for further checks
+fn validate_bounds(
+    bounds @ [min_x, min_y, max_x, max_y]: [f32; 4],
+    errors: &mut Vec<(&'static str, String)>,
+) -> bool {
+    if !bounds.iter().all(|c| c.is_finite()) {
+        errors.push(("bounds", format!("must be finite, got {:?}", bounds)));
+        false
+    } else if min_x > max_x || min_y > max_y {
+        errors.push(("bounds", format!("{:?} has min greater than max", bounds)));
+        false
+    } else {
+        true
+    }
+}
+
+fn validate_float_range<T: Into<f64> + PartialOrd + Display + Copy>(
+    min: T,
+    max: T,
+    default: T,
+    errors: &mut Vec<(&'static str, String)>,
+) {
+    for (field, value) in [("min", min), ("max", max), ("default", default)] {
+        if !value.into().is_finite() {
+            errors.push((field, format!("must be finite, got {}", value)));
+        }
+    }
+    if errors.is_empty() {
+        validate_range(min, max, default, errors);
+    }
+}
+
+fn validate_range<T: PartialOrd + Display>(
+    min: T,
+    max: T,
+    default: T,
+    errors: &mut Vec<(&'static str, String)>,
+) {
+    if min > max {
+        errors.push(("min", format!("{} is greater than max {}", min, max)));
+    } else if default < min || default > max {
+        errors.push((
+            "default",
+            format!("{} is outside the range [{}, {}]", default, min, max),
+        ));
+    }
+}
+
+/// Check every schema entry for semantic errors (inverted ranges, defaults
+/// outside their range, non-finite floats) and reject overly long schemas.
+/// All problems are reported at once, each prefixed with its entry index and
+/// field, e.g. `schema[1].default: 150 is outside the range [0, 100]`.
+pub fn validate_schema(schema: &Schema) -> Result<(), String> {
+    if schema.len() > MAX_SCHEMA_ENTRIES {
+        return Err(format!(
+            "Invalid schema: {} entries, at most {} are allowed",
+            schema.len(),
+            MAX_SCHEMA_ENTRIES
+        ));
+    }
+    let errors: Vec<String> = schema
+        .iter()
+        .enumerate()
+        .flat_map(|(i, entry)| {
+            let mut errors = entry.ty.validate();
+            errors.extend(validate_presentation(schema, i));
+            errors
+                .into_iter()
+                .map(move |(field, msg)| format!("schema[{}].{}: {}", i, field, msg))
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Invalid schema: {}", errors.join("; ")))
+    }
+}
+
+// Checks of the name and visible_if fields, which depend on the rest of the
+// schema
+fn validate_presentation(schema: &Schema, index: usize) -> Vec<(&'static str, String)> {
+    let mut errors = Vec::new();
+    let entry = &schema[index];
+    if let Some(name) = &entry.name {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            errors.push((
+                "name",
+                format!("{:?} may only contain ASCII letters, digits and '_'", name),
+            ));
+        } else if parameter_index(schema, name) != Some(index) {
+            errors.push(("name", format!("{:?} is already used", name)));
+        }
+    }
+    if let Some(condition) = &entry.visible_if {
+        match parameter_index(schema, &condition.name) {
+            None => errors.push((
+                "visible_if",
+                format!("no parameter is named {:?}", condition.name),
+            )),
+            Some(target) if target == index => {
+                errors.push(("visible_if", "must not refer to itself".to_string()))
+            }
+            Some(target) => {
+                if condition.expected(&schema[target].ty).is_none() {
+                    errors.push((
+                        "visible_if",
+                        format!(
+                            "{} is not a valid value for {:?}",
+                            condition.equals, condition.name
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Descriptive metadata stored in the `rgeometry.meta` custom section. Every
+/// field is optional:
+/// ```json
+/// {
+///   "title": "Random convex polygon",
+///   "description": "Generates a new polygon every second",
+///   "author": "Jane Doe",
+///   "license": "MIT",
+///   "presets": [{ "name": "Triangle", "values": { "n": 3 } }] // See `Preset`
+/// }
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Metadata {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub presets: Vec<Preset>,
+}
+
+/// Information read from a module's custom sections. Parsing a module this
+/// way never instantiates or runs it, so it is safe for galleries and
+/// validators to inspect untrusted modules.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleInfo {
+    /// `None` if the module has no `rgeometry.schema` section.
+    pub schema: Option<Schema>,
+    pub meta: Metadata,
+}
+
+impl ModuleInfo {
+    /// Read the custom sections of a module. A schema section is checked with
+    /// `validate_schema`, so a module this accepts has no schema errors.
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut schema = None;
+        let mut meta = None;
+        for payload in Parser::new(0).parse_all(bytes) {
+            let payload = payload.map_err(|e| format!("Failed to parse module: {}", e))?;
+            let Payload::CustomSection(section) = payload else {
+                continue;
+            };
+            match section.name() {
+                SCHEMA_SECTION => {
+                    if schema.is_some() {
+                        return Err(format!("Duplicate '{}' section", SCHEMA_SECTION));
+                    }
+                    schema = Some(
+                        serde_json::from_slice(section.data())
+                            .map_err(|e| format!("Invalid schema JSON: {}", e))?,
+                    );
+                }
+                META_SECTION => {
+                    if meta.is_some() {
+                        return Err(format!("Duplicate '{}' section", META_SECTION));
+                    }
+                    meta = Some(
+                        serde_json::from_slice(section.data())
+                            .map_err(|e| format!("Invalid metadata JSON: {}", e))?,
+                    );
+                }
+                _ => {}
+            }
+        }
+        if let Some(schema) = &schema {
+            validate_schema(schema)?;
+        }
+        Ok(Self {
+            schema,
+            meta: meta.unwrap_or_default(),
+        })
+    }
+}
+
+/// Size of the element the rendered frames are displayed in, in CSS pixels.
+/// Defaults to 800x600, which `rgeometry_demo::viewport` also reports until
+/// the host calls `init`.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub width: f32,
+    pub height: f32,
+    pub device_pixel_ratio: f32,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Self {
+            width: 800.0,
+            height: 600.0,
+            device_pixel_ratio: 1.0,
+        }
+    }
+}
+
+/// What rendering a frame cost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameMetrics {
+    /// Wall time of the `request_animation_frame` call, in milliseconds
+    pub guest_time: f64,
+    /// Length of the rendered SVG, in bytes
+    pub svg_bytes: usize,
+    /// Size of the guest's linear memory after the frame, in bytes
+    pub memory_bytes: usize,
+    /// How much the memory grew during the frame. Linear memory never
+    /// shrinks.
+    pub memory_growth: usize,
+    /// Fuel the frame consumed, None unless fuel metering is enabled
+    pub fuel: Option<u64>,
+}
+
+// What the module passes back through its imports during a frame
+#[derive(Debug, Default)]
+struct HostState {
+    // The SVG given to 'render'
+    svg: String,
+    // Whether 'static_frame' was called
+    static_frame: bool,
+}
+
+#[derive(Debug)]
+pub struct Wasm {
+    instance: Instance,
+    schema: Schema,
+    meta: Metadata,
+    // Problems with the module that didn't keep it from loading
+    warnings: Vec<String>,
+    store: Store<HostState>,
+    // Playback clock: the time is `time_offset` plus the time since
+    // `running_since`, which is None while paused
+    time_offset: f64,
+    running_since: Option<Instant>,
+    // Number of successfully rendered frames and when the last one started
+    frame: i64,
+    last_frame_at: Option<Instant>,
+    // Values the user picked, by `parameter_key`, so they stay with their
+    // entry wherever it is in the schema. Resolved to positions per frame.
+    parameters: HashMap<String, Value>,
+    viewport: Viewport,
+    // Region the viewer is zoomed in on, None while the whole frame is shown
+    camera: Option<ViewBox>,
+    // Parameters copied into guest memory, and buffers of parameters that
+    // changed since, to be freed before the next frame
+    buffers: Vec<GuestBuffer>,
+    released: Vec<GuestBuffer>,
+    // Set when the optional 'init' export has to be (re-)run before the next
+    // frame, i.e. after instantiation and whenever the viewport size changes.
+    needs_init: bool,
+    failed: bool,
+    fuel_metering: bool,
+    // Fuel a frame may consume, and whether one consumed it all
+    fuel_limit: Option<u64>,
+    out_of_fuel: bool,
+    metrics: FrameMetrics,
+}
+
+impl Wasm {
+    pub fn new(bytes: &[u8]) -> Result<Self, String> {
+        Self::instantiate(bytes, false)
+    }
+
+    /// Like `new`, but count the fuel, roughly the number of instructions,
+    /// every frame consumes, which `set_fuel_limit` can cap. Metered modules
+    /// run somewhat slower.
+    pub fn with_fuel_metering(bytes: &[u8]) -> Result<Self, String> {
+        Self::instantiate(bytes, true)
+    }
+
+    /// `new` without a limit, and otherwise `with_fuel_metering` with frames
+    /// stopped after `limit` fuel.
+    pub fn with_fuel_limit(bytes: &[u8], limit: Option<u64>) -> Result<Self, String> {
+        let mut wasm = Self::instantiate(bytes, limit.is_some())?;
+        wasm.set_fuel_limit(limit);
+        Ok(wasm)
+    }
+
+    fn instantiate(bytes: &[u8], fuel_metering: bool) -> Result<Self, String> {
+        // Read and validate schema and metadata before anything is
+        // instantiated
+        let info = ModuleInfo::parse(bytes)?;
+
+        // Create a new WASM engine
+        let mut config = Config::default();
+        config.consume_fuel(fuel_metering);
+        let engine = Engine::new(&config);
+
+        // Parse the module
+        let module =
+            Module::new(&engine, bytes).map_err(|e| format!("Failed to create module: {}", e))?;
+
+        // Create store with state
+        let mut store = Store::new(&engine, HostState::default());
+
+        let mut linker = <Linker<HostState>>::new(&engine);
+        linker
+            .func_new(
+                "env",
+                "render",
+                FuncType::new([wasmi::core::ValType::I32], []),
+                |mut caller, params, _results| {
+                    // Get the pointer to the string from the first parameter
+                    let ptr = params[0]
+                        .i32()
+                        .ok_or(wasmi::Error::new("Failed to get i32 parameter"))?;
+
+                    // Get the memory from the caller
+                    let memory = caller
+                        .get_export("memory")
+                        .and_then(Extern::into_memory)
+                        .ok_or(wasmi::Error::new("Failed to get memory"))?;
+
+                    // Read the memory starting from ptr until null terminator.
+                    // The pointer comes from the guest, so it may be anywhere.
+                    let data = memory.data(&caller);
+                    let data = data
+                        .get(ptr as u32 as usize..)
+                        .ok_or(wasmi::Error::new("render pointer out of bounds"))?;
+                    let result = CStr::from_bytes_until_nul(data)
+                        .map_err(|_| wasmi::Error::new("Failed to read null-terminated string"))?
+                        .to_str()
+                        .map_err(|_| wasmi::Error::new("Invalid UTF-8 string"))?;
+
+                    // Store the rendered string in the state
+                    caller.data_mut().svg = result.to_string();
+
+                    Ok(())
+                },
+            )
+            .map_err(|e| format!("Failed to create render function: {}", e))?;
+        // Optional: the module calls 'static_frame' to say the frame it is
+        // rendering won't change until its parameters or viewport do
+        linker
+            .func_new(
+                "env",
+                "static_frame",
+                FuncType::new([], []),
+                |mut caller, _params, _results| {
+                    caller.data_mut().static_frame = true;
+                    Ok(())
+                },
+            )
+            .map_err(|e| format!("Failed to create static_frame function: {}", e))?;
+
+        let instance = linker
+            .instantiate(&mut store, &module)
+            .map_err(|e| format!("Failed to instantiate module: {}", e))?
+            .ensure_no_start(&mut store)
+            .map_err(|e| format!("Failed to start module: {}", e))?;
+
+        // Check for required 'request_animation_frame' export
+        let request_animation_frame = instance
+            .get_func(&store, "request_animation_frame")
+            .ok_or("Module must export 'request_animation_frame'")?;
+        let request_animation_frame_ty = request_animation_frame.ty(&store);
+
+        // Get memory
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or("Module must export memory")?;
+
+        // Prefer the custom section. Older modules export a SCHEMA global
+        // pointing into memory instead (default to empty vec if neither).
+        let schema = if let Some(schema) = info.schema {
+            schema
+        } else if let Some(global) = instance.get_global(&store, "SCHEMA") {
+            let ptr = global.get(&store).i32().ok_or("SCHEMA must be i32")? as u32;
+
+            // Read null-terminated string from memory using CStr
+            let schema_str = CStr::from_bytes_until_nul(&memory.data(&store)[ptr as usize..])
+                .map_err(|_| "SCHEMA must be null-terminated")?
+                .to_str()
+                .map_err(|_| "SCHEMA must be valid UTF-8")?;
+
+            // Parse the JSON string into Schema
+            let schema = serde_json::from_str(schema_str)
+                .map_err(|e| format!("Invalid schema JSON: {}", e))?;
+            validate_schema(&schema)?;
+            schema
+        } else {
+            Vec::new()
+        };
+        // A bad preset shouldn't keep the module from loading, so it is left
+        // out with a warning
+        let mut meta = info.meta;
+        let mut warnings = Vec::new();
+        meta.presets.retain(|preset| match preset.resolve(&schema) {
+            Ok(_) => true,
+            Err(err) => {
+                let warning = format!("Skipped invalid preset {:?}: {}", preset.name, err);
+                log::warn!("{}", warning);
+                warnings.push(warning);
+                false
+            }
+        });
+
+        // Convert schema types to expected parameter types
+        let expected_params: Vec<wasmi::core::ValType> = schema
+            .iter()
+            .flat_map(|param| param.ty.val_types().iter().copied())
+            .collect();
+
+        // Get the actual parameter types from the function
+        let actual_params = request_animation_frame_ty.params();
+
+        // Verify that the parameter types match
+        if actual_params != expected_params.as_slice() {
+            return Err(format!(
+                "request_animation_frame parameters don't match schema. Expected: {:?}, Got: {:?}",
+                expected_params, actual_params
+            ));
+        }
+
+        if !request_animation_frame_ty.results().is_empty() {
+            return Err("request_animation_frame must not return any values".to_string());
+        }
+
+        // Variable-sized parameters are copied into memory the module allocates
+        if schema.iter().any(|param| param.ty.uses_guest_memory()) {
+            instance
+                .get_typed_func::<i32, i32>(&store, "alloc")
+                .map_err(|_| "Module must export 'alloc(size: i32) -> i32' for this schema")?;
+            instance
+                .get_typed_func::<(i32, i32), ()>(&store, "dealloc")
+                .map_err(|_| "Module must export 'dealloc(ptr: i32, size: i32)' for this schema")?;
+        }
+
+        // The optional 'init' export receives the viewport width and height
+        if let Some(init) = instance.get_func(&store, "init") {
+            let init_ty = init.ty(&store);
+            if init_ty.params() != [ValType::F32, ValType::F32] || !init_ty.results().is_empty() {
+                return Err(format!(
+                    "init must take (f32, f32) and return nothing. Got: {:?} -> {:?}",
+                    init_ty.params(),
+                    init_ty.results()
+                ));
+            }
+        }
+
+        Ok(Self {
+            instance,
+            schema,
+            meta,
+            warnings,
+            store,
+            time_offset: 0.0,
+            running_since: Some(Instant::now()),
+            frame: 0,
+            last_frame_at: None,
+            parameters: HashMap::new(),
+            viewport: Viewport::default(),
+            camera: None,
+            buffers: Vec::new(),
+            released: Vec::new(),
+            needs_init: true,
+            failed: false,
+            fuel_metering,
+            fuel_limit: None,
+            out_of_fuel: false,
+            metrics: FrameMetrics::default(),
+        })
+    }
+
+    /// Stop frames that consume more than `limit` fuel, and fail the module
+    /// as if it trapped. Only metered instances are limited, and without a
+    /// limit their fuel is only counted.
+    pub fn set_fuel_limit(&mut self, limit: Option<u64>) {
+        self.fuel_limit = limit;
+    }
+
+    /// Whether the module failed because a frame consumed all its fuel.
+    pub fn is_out_of_fuel(&self) -> bool {
+        self.out_of_fuel
+    }
+
+    // Give the next call into the module its full fuel limit
+    fn refuel(&mut self) -> Result<(), String> {
+        if !self.fuel_metering {
+            return Ok(());
+        }
+        self.store
+            .set_fuel(self.fuel_limit.unwrap_or(u64::MAX))
+            .map_err(|e| format!("Failed to set fuel: {}", e))
+    }
+
+    // Stop rendering after a call into the module failed
+    fn fail(&mut self, function: &str, err: wasmi::Error) {
+        if err.as_trap_code() == Some(TrapCode::OutOfFuel) {
+            log::error!("{} ran out of fuel", function);
+            self.out_of_fuel = true;
+        } else {
+            log::error!("Failed to call {}: {}", function, err);
+        }
+        self.failed = true;
+    }
+
+    /// Cost of the last rendered frame.
+    pub fn metrics(&self) -> FrameMetrics {
+        self.metrics
+    }
+
+    fn memory_size(&self) -> usize {
+        self.instance
+            .get_memory(&self.store, "memory")
+            .map_or(0, |memory| memory.data(&self.store).len())
+    }
+
+    fn fuel(&self) -> Option<u64> {
+        self.fuel_metering
+            .then(|| self.store.get_fuel().ok())
+            .flatten()
+    }
+
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    pub fn meta(&self) -> &Metadata {
+        &self.meta
+    }
+
+    /// Problems found while loading that the module works around, such as
+    /// presets that don't suit its schema and were skipped.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    /// Update the viewer size. A change in width or height causes the
+    /// module's 'init' export to be called again before the next frame.
+    pub fn set_viewport(&mut self, viewport: Viewport) {
+        if viewport.width != self.viewport.width || viewport.height != self.viewport.height {
+            self.needs_init = true;
+        }
+        self.viewport = viewport;
+    }
+
+    /// Set the region of the frame the viewer is zoomed in on, for modules
+    /// with a 'camera' parameter.
+    pub fn set_camera(&mut self, camera: Option<ViewBox>) {
+        self.camera = camera;
+    }
+
+    fn time_at(&self, now: Instant) -> f64 {
+        let running = self
+            .running_since
+            .map_or(0.0, |since| (now - since).as_secs_f64());
+        self.time_offset + running
+    }
+
+    /// Seconds passed to `time` parameters, not counting paused periods.
+    pub fn time(&self) -> f64 {
+        self.time_at(Instant::now())
+    }
+
+    /// Jump to the given time. Playback continues from there unless paused.
+    pub fn set_time(&mut self, seconds: f64) {
+        self.time_offset = seconds.max(0.0);
+        if self.running_since.is_some() {
+            self.running_since = Some(Instant::now());
+        }
+    }
+
+    /// Whether the module trapped or misbehaved. Failed modules aren't run
+    /// again and keep showing their last frame.
+    pub fn has_failed(&self) -> bool {
+        self.failed
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.running_since.is_none()
+    }
+
+    /// Whether the next frame may differ from the last one with the same
+    /// parameters, viewport and camera: the clock runs and the module takes
+    /// a `time`, `frame` or `delta_time` parameter, and didn't call its
+    /// `static_frame` import during the last frame. Viewers only need to
+    /// render again when this holds or something they set changed.
+    pub fn is_animated(&self) -> bool {
+        let uses_time = self.schema.iter().any(|param| {
+            matches!(
+                param.ty,
+                SchemaType::Time | SchemaType::Frame | SchemaType::DeltaTime
+            )
+        });
+        uses_time && !self.is_paused() && !self.failed && !self.store.data().static_frame
+    }
+
+    /// Stop or restart the clock. Frames are still rendered while paused, so
+    /// parameter changes show up, but time stands still.
+    pub fn set_paused(&mut self, paused: bool) {
+        if paused == self.is_paused() {
+            return;
+        }
+        let now = Instant::now();
+        if paused {
+            self.time_offset = self.time_at(now);
+            self.running_since = None;
+        } else {
+            self.running_since = Some(now);
+        }
+    }
+
+    /// Override the value passed for a user-controlled parameter. Fails if the
+    /// index is out of bounds or the value doesn't suit the schema entry.
+    pub fn set_parameter(&mut self, index: usize, value: Value) -> Result<(), String> {
+        let param = self
+            .schema
+            .get(index)
+            .ok_or_else(|| format!("No parameter at index {}", index))?;
+        param.ty.check_value(&value)?;
+        let key = parameter_key(&self.schema, index).unwrap_or_default();
+        if self.parameters.get(&key) != Some(&value) {
+            self.release_buffer(index);
+        }
+        self.parameters.insert(key, value);
+        Ok(())
+    }
+
+    /// Like `set_parameter`, for the entry with the given name, or position
+    /// if it has none.
+    pub fn set_named_parameter(&mut self, key: &str, value: Value) -> Result<(), String> {
+        let index = parameter_by_key(&self.schema, key)
+            .ok_or_else(|| format!("No parameter is named {:?}", key))?;
+        self.set_parameter(index, value)
+    }
+
+    /// The value picked for entry `index`, None while it has its default.
+    pub fn parameter(&self, index: usize) -> Option<&Value> {
+        let key = parameter_key(&self.schema, index)?;
+        self.parameters.get(&key)
+    }
+
+    /// Go back to the schema default for every user-controlled parameter.
+    pub fn reset_parameters(&mut self) {
+        let changed: Vec<usize> = self
+            .parameters
+            .keys()
+            .filter_map(|key| parameter_by_key(&self.schema, key))
+            .collect();
+        for index in changed {
+            self.release_buffer(index);
+        }
+        self.parameters.clear();
+    }
+
+    // Have the buffer of a parameter rewritten before the next frame
+    fn release_buffer(&mut self, index: usize) {
+        if let Some(position) = self.buffers.iter().position(|b| b.index == index) {
+            let buffer = self.buffers.swap_remove(position);
+            self.released.push(buffer);
+        }
+    }
+
+    // Seconds since the last frame, 0 for the first frame and while paused
+    fn delta_time_at(&self, now: Instant) -> f64 {
+        match (self.running_since, self.last_frame_at) {
+            (Some(_), Some(last)) => (now - last).as_secs_f64(),
+            _ => 0.0,
+        }
+    }
+
+    // Merge the schema definition with the given parameters to yield a vector
+    // of values that will be passed to the request_animation_frame function.
+    fn parameters(&self, time: f64, delta_time: f64) -> Vec<Val> {
+        let mut parameters = Vec::new();
+        for (i, param) in self.schema.iter().enumerate() {
+            match &param.ty {
+                SchemaType::Time => parameters.push(Val::F64(F64::from_float(time))),
+                SchemaType::Frame => parameters.push(Val::I64(self.frame)),
+                SchemaType::DeltaTime => parameters.push(Val::F64(F64::from_float(delta_time))),
+                SchemaType::Viewport => parameters.extend([
+                    Val::F32(F32::from_float(self.viewport.width)),
+                    Val::F32(F32::from_float(self.viewport.height)),
+                    Val::F32(F32::from_float(self.viewport.device_pixel_ratio)),
+                ]),
+                SchemaType::Camera => {
+                    let camera = self.camera.map_or([0.0; 4], |camera| {
+                        [camera.x, camera.y, camera.width, camera.height]
+                    });
+                    parameters.extend(camera.map(|v| Val::F32(F32::from_float(v as f32))));
+                }
+                SchemaType::RangeF32 { .. }
+                | SchemaType::RangeI32 { .. }
+                | SchemaType::RangeF64 { .. }
+                | SchemaType::RangeI64 { .. }
+                | SchemaType::Bool { .. }
+                | SchemaType::Enum { .. }
+                | SchemaType::Color { .. }
+                | SchemaType::Seed { .. }
+                | SchemaType::Point2d { .. } => {
+                    let value = self.parameter(i).cloned();
+                    let value = value.or_else(|| param.ty.default_value());
+                    parameters.extend(value.iter().flat_map(Value::to_vals));
+                }
+                SchemaType::PointSet { .. } | SchemaType::Text { .. } | SchemaType::File { .. } => {
+                    let buffer = self.buffers.iter().find(|buffer| buffer.index == i);
+                    let (ptr, len) = buffer.map_or((0, 0), |buffer| (buffer.ptr, buffer.len));
+                    parameters.extend([Val::I32(ptr), Val::I32(len)]);
+                }
+            }
+        }
+        parameters
+    }
+
+    // Copy the variable-sized parameters that have no buffer yet into freshly
+    // allocated guest buffers, after freeing those of changed parameters. On
+    // failure every buffer is freed, so none of them leak.
+    fn write_buffers(&mut self) -> Result<(), String> {
+        if !self.schema.iter().any(|param| param.ty.uses_guest_memory()) {
+            return Ok(());
+        }
+        let written = self.free_released().and_then(|()| self.allocate_buffers());
+        if written.is_err() {
+            self.released.append(&mut self.buffers);
+            // The first error is the one worth reporting
+            _ = self.free_released();
+        }
+        written
+    }
+
+    fn allocate_buffers(&mut self) -> Result<(), String> {
+        let alloc = self
+            .instance
+            .get_typed_func::<i32, i32>(&self.store, "alloc")
+            .map_err(|e| e.to_string())?;
+        let memory = self
+            .instance
+            .get_memory(&self.store, "memory")
+            .ok_or("Module must export memory")?;
+        for (index, param) in self.schema.iter().enumerate() {
+            if self.buffers.iter().any(|buffer| buffer.index == index) {
+                continue;
+            }
+            let value = self.parameter(index).cloned();
+            let Some((bytes, len)) = value
+                .or_else(|| param.ty.default_value())
+                .and_then(|value| value.guest_bytes())
+            else {
+                continue;
+            };
+            let size = i32::try_from(bytes.len()).map_err(|_| "Parameter too large")?;
+            let ptr = alloc
+                .call(&mut self.store, size)
+                .map_err(|e| format!("Failed to call alloc: {}", e))?;
+            if ptr == 0 {
+                return Err(format!(
+                    "The module could not allocate {} bytes for parameter {}",
+                    size, index
+                ));
+            }
+            self.buffers.push(GuestBuffer {
+                index,
+                ptr,
+                size,
+                len,
+            });
+            memory
+                .write(&mut self.store, ptr as u32 as usize, &bytes)
+                .map_err(|e| format!("Failed to write parameter {}: {}", index, e))?;
+        }
+        Ok(())
+    }
+
+    // Free the buffers of parameters that changed
+    fn free_released(&mut self) -> Result<(), String> {
+        let buffers = std::mem::take(&mut self.released);
+        if buffers.is_empty() {
+            return Ok(());
+        }
+        let dealloc = self
+            .instance
+            .get_typed_func::<(i32, i32), ()>(&self.store, "dealloc")
+            .map_err(|e| e.to_string())?;
+        for buffer in buffers {
+            dealloc
+                .call(&mut self.store, (buffer.ptr, buffer.size))
+                .map_err(|e| format!("Failed to call dealloc: {}", e))?;
+        }
+        Ok(())
+    }
+
+    pub fn render(&mut self) -> String {
+        let now = Instant::now();
+        let frame = self.frame;
+        let svg = self.render_at(self.time_at(now), self.delta_time_at(now));
+        if self.frame != frame {
+            self.last_frame_at = Some(now);
+        }
+        svg
+    }
+
+    /// Render a frame as if the clock read `time` seconds and `delta_time`
+    /// seconds had passed since the previous frame. The playback clock is
+    /// left alone, so a fresh instance renders the same frames for the same
+    /// times and parameters.
+    pub fn render_at(&mut self, time: f64, delta_time: f64) -> String {
+        if self.failed {
+            return self.store.data().svg.clone();
+        }
+
+        if let Err(err) = self.refuel() {
+            log::error!("{}", err);
+            self.failed = true;
+            return self.store.data().svg.clone();
+        }
+
+        if self.needs_init {
+            self.needs_init = false;
+            if let Some(init) = self.instance.get_func(&self.store, "init") {
+                let size = [
+                    Val::F32(F32::from_float(self.viewport.width)),
+                    Val::F32(F32::from_float(self.viewport.height)),
+                ];
+                if let Err(err) = init.call(&mut self.store, &size, &mut []) {
+                    self.fail("init", err);
+                    return self.store.data().svg.clone();
+                }
+            }
+        }
+
+        let func = self
+            .instance
+            .get_func(&self.store, "request_animation_frame")
+            .expect("request_animation_frame function not found");
+
+        if let Err(err) = self.write_buffers().and_then(|()| self.refuel()) {
+            log::error!("{}", err);
+            self.failed = true;
+            return self.store.data().svg.clone();
+        }
+        let params = self.parameters(time, delta_time);
+        self.store.data_mut().static_frame = false;
+        let memory_before = self.memory_size();
+        let fuel_before = self.fuel();
+        let started = Instant::now();
+        let result = func.call(&mut self.store, &params, &mut []);
+        let guest_time = started.elapsed().as_secs_f64() * 1000.0;
+        if let Err(err) = result {
+            self.fail("request_animation_frame", err);
+        } else {
+            self.frame += 1;
+            let memory_bytes = self.memory_size();
+            self.metrics = FrameMetrics {
+                guest_time,
+                svg_bytes: self.store.data().svg.len(),
+                memory_bytes,
+                memory_growth: memory_bytes.saturating_sub(memory_before),
+                fuel: fuel_before
+                    .zip(self.fuel())
+                    .map(|(before, after)| before - after),
+            };
+        }
+
+        self.store.data().svg.clone()
+    }
+}
+// Panic: panicked at std/src/panicking.rs:131:9:cannot modify the panic hook from a panicking thread
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wat::parse_str;
+
+    fn validate(types: Vec<SchemaType>) -> Result<(), String> {
+        validate_schema(&types.into_iter().map(Parameter::from).collect())
+    }
+
+    const SIMPLE_WAT: &str = r#"
+        (module
+            (@custom "rgeometry.schema" "[{\"type\":\"time\"}]")
+            (type (;0;) (func (param i32)))
+            (import "env" "render" (func (;0;) (type 0)))
+            (memory (export "memory") 1)
+            (func (export "request_animation_frame") (param f64))
+        )"#;
+
+    const ACTUAL_WAT: &str = r#"
+        (module
+        (type (;0;) (func (param i32)))
+        (type (;1;) (func))
+        (import "env" "render" (func (;0;) (type 0)))
+        (func (;1;) (type 1)
+            i32.const 1048579
+            call 0)
+        (table (;0;) 1 1 funcref)
+        (memory (;0;) 17)
+        (global (;0;) (mut i32) (i32.const 1048576))
+        (global (;1;) i32 (i32.const 1048576))
+        (global (;2;) i32 (i32.const 1048592))
+        (global (;3;) i32 (i32.const 1048592))
+        (export "memory" (memory 0))
+        (export "request_animation_frame" (func 1))
+        (export "SCHEMA" (global 1))
+        (export "__data_end" (global 2))
+        (export "__heap_base" (global 3))
+        (data (;0;) (i32.const 1048576) "[]\00Hello, world!"))"#;
+
+    const WAT_WITH_PARAMS: &str = r#"
+        (module
+            (type (;0;) (func (param i32)))
+            (type (;1;) (func (param f32 i32)))
+            (@custom "rgeometry.schema" "[{\"type\":\"range_f32\",\"min\":0.0,\"max\":1.0,\"default\":0.5},{\"type\":\"range_i32\",\"min\":0,\"max\":100,\"default\":50}]")
+            (import "env" "render" (func (;0;) (type 0)))
+            (memory (export "memory") 1)
+            (func (export "request_animation_frame") (param f32 i32))
+        )"#;
+
+    #[test]
+    fn test_schema() {
+        let wasm_bytes = parse_str(SIMPLE_WAT).unwrap();
+        let wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.schema().len(), 1);
+        assert!(matches!(wasm.schema()[0].ty, SchemaType::Time));
+    }
+
+    #[test]
+    fn legacy_schema_global() {
+        let wat = r#"
+            (module
+                (type (;0;) (func (param i32)))
+                (import "env" "render" (func (;0;) (type 0)))
+                (global $SCHEMA (export "SCHEMA") i32 i32.const 1)
+                (memory (export "memory") 1)
+                (data (i32.const 1) "[{\"type\":\"time\"}]\00")
+                (func (export "request_animation_frame") (param f64))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.schema().len(), 1);
+        assert!(matches!(wasm.schema()[0].ty, SchemaType::Time));
+    }
+
+    #[test]
+    fn metadata_section() {
+        let wat = r#"
+            (module
+                (@custom "rgeometry.meta" "{\"title\":\"Circle\",\"license\":\"MIT\"}")
+                (type (;0;) (func (param i32)))
+                (import "env" "render" (func (;0;) (type 0)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame"))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.meta().title.as_deref(), Some("Circle"));
+        assert_eq!(wasm.meta().license.as_deref(), Some("MIT"));
+        assert_eq!(wasm.meta().author, None);
+    }
+
+    #[test]
+    fn presets() {
+        let schema: Schema = serde_json::from_str(
+            r#"[
+                {"type": "time"},
+                {"type": "bool", "default": false, "name": "show_hull"},
+                {"type": "range_i32", "min": 0, "max": 10, "default": 5},
+                {"type": "point_set", "name": "points"}
+            ]"#,
+        )
+        .unwrap();
+        let preset: Preset = serde_json::from_str(
+            r#"{"name": "Collinear", "values": {"points": "0,0;1,1;2,2", "show_hull": true, "2": 3}}"#,
+        )
+        .unwrap();
+        let mut values = preset.resolve(&schema).unwrap();
+        values.sort_by_key(|(index, _)| *index);
+        assert_eq!(
+            values,
+            [
+                (1, Value::I32(1)),
+                (2, Value::I32(3)),
+                (3, Value::PointSet(vec![[0.0, 0.0], [1.0, 1.0], [2.0, 2.0]]))
+            ]
+        );
+
+        // Capturing and resolving again gives the same values
+        let captured = Preset::capture(
+            "Copy".to_string(),
+            &schema,
+            &values.iter().cloned().collect(),
+        );
+        assert_eq!(captured.values["2"], "3");
+        let mut again = captured.resolve(&schema).unwrap();
+        again.sort_by_key(|(index, _)| *index);
+        assert_eq!(again, values);
+
+        let bad: Preset =
+            serde_json::from_str(r#"{"name": "Bad", "values": {"n": 1, "2": 11}}"#).unwrap();
+        assert!(bad.resolve_known(&schema).is_empty());
+        let mixed: Preset = serde_json::from_str(
+            r#"{"name": "Other build", "values": {"n": 1, "2": 4, "show_hull": true}}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            mixed.resolve_known(&schema),
+            [(2, Value::I32(4)), (1, Value::I32(1))]
+        );
+        assert_eq!(
+            validate_presets(&[preset, bad], &schema).unwrap_err(),
+            "Invalid preset \"Bad\": 11 is not a valid value for \"2\""
+        );
+    }
+
+    #[test]
+    fn invalid_presets_in_metadata_are_skipped() {
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"bool\",\"default\":false,\"name\":\"flag\"}]")
+                (@custom "rgeometry.meta" "{\"presets\":[{\"name\":\"On\",\"values\":{\"flag\":\"yes\"}},{\"name\":\"Off\",\"values\":{\"flag\":false}}]}")
+                (import "env" "render" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param i32))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let wasm = Wasm::new(&wasm_bytes).unwrap();
+        let names: Vec<&str> = wasm
+            .meta()
+            .presets
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(names, ["Off"]);
+        assert_eq!(
+            wasm.warnings(),
+            ["Skipped invalid preset \"On\": \"yes\" is not a valid value for \"flag\""]
+        );
+    }
+
+    #[test]
+    fn inspect_without_instantiation() {
+        // The import can't be satisfied, so this module never instantiates.
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"time\"}]")
+                (@custom "rgeometry.meta" "{\"description\":\"Never runs\"}")
+                (import "env" "missing" (func))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let info = ModuleInfo::parse(&wasm_bytes).unwrap();
+        assert_eq!(info.schema.map(|s| s.len()), Some(1));
+        assert_eq!(info.meta.description.as_deref(), Some("Never runs"));
+        assert!(Wasm::new(&wasm_bytes).is_err());
+    }
+
+    #[test]
+    fn duplicate_schema_section() {
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[]")
+                (@custom "rgeometry.schema" "[]")
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let result = ModuleInfo::parse(&wasm_bytes);
+        assert!(result.unwrap_err().contains("Duplicate"));
+    }
+
+    #[test]
+    fn minimal_schema() {
+        let wasm_bytes = parse_str(ACTUAL_WAT).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert!(wasm.schema().is_empty());
+        assert_eq!(wasm.render(), "Hello, world!");
+    }
+
+    #[test]
+    fn test_render_type_time_serialization() {
+        let time = SchemaType::Time;
+        let json = serde_json::to_string(&time).unwrap();
+        assert_eq!(json, r#"{"type":"time"}"#);
+    }
+
+    #[test]
+    fn test_render_type_time_deserialization() {
+        let json = r#"{"type":"time"}"#;
+        let time: SchemaType = serde_json::from_str(json).unwrap();
+        assert!(matches!(time, SchemaType::Time));
+    }
+
+    #[test]
+    fn test_render_type_range_f32_serialization() {
+        let range = SchemaType::RangeF32 {
+            min: 0.0,
+            max: 100.0,
+            default: 50.0,
+            options: RangeOptions::default(),
+        };
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"range_f32","min":0.0,"max":100.0,"default":50.0}"#
+        );
+    }
+
+    #[test]
+    fn test_render_type_range_f32_deserialization() {
+        let json = r#"{"type":"range_f32","min":0.0,"max":100.0,"default":50.0}"#;
+        let range: SchemaType = serde_json::from_str(json).unwrap();
+        match range {
+            SchemaType::RangeF32 {
+                min, max, default, ..
+            } => {
+                assert_eq!(min, 0.0);
+                assert_eq!(max, 100.0);
+                assert_eq!(default, 50.0);
+            }
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_render_type_range_i32_serialization() {
+        let range = SchemaType::RangeI32 {
+            min: 0,
+            max: 100,
+            default: 50,
+            options: RangeOptions::default(),
+        };
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(
+            json,
+            r#"{"type":"range_i32","min":0,"max":100,"default":50}"#
+        );
+    }
+
+    #[test]
+    fn test_render_type_range_i32_deserialization() {
+        let json = r#"{"type":"range_i32","min":0,"max":100,"default":50}"#;
+        let range: SchemaType = serde_json::from_str(json).unwrap();
+        match range {
+            SchemaType::RangeI32 {
+                min, max, default, ..
+            } => {
+                assert_eq!(min, 0);
+                assert_eq!(max, 100);
+                assert_eq!(default, 50);
+            }
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_render_type_bool_serialization() {
+        let json = serde_json::to_string(&SchemaType::Bool { default: true }).unwrap();
+        assert_eq!(json, r#"{"type":"bool","default":true}"#);
+    }
+
+    #[test]
+    fn test_render_type_enum_deserialization() {
+        let json = r#"{"type":"enum","options":["graham","melkman"],"default":1}"#;
+        match serde_json::from_str(json).unwrap() {
+            SchemaType::Enum { options, default } => {
+                assert_eq!(options, ["graham", "melkman"]);
+                assert_eq!(default, 1);
+            }
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn test_render_type_color_deserialization() {
+        let json = r##"{"type":"color","default":"#ff8000"}"##;
+        match serde_json::from_str(json).unwrap() {
+            SchemaType::Color { default } => assert_eq!(default, "#ff8000"),
+            _ => panic!("Wrong variant"),
+        }
+    }
+
+    #[test]
+    fn color_packing() {
+        assert_eq!(parse_color("#ff8000"), Some(0xff8000ff));
+        assert_eq!(parse_color("#ff800080"), Some(0xff800080));
+        assert_eq!(parse_color("#FF8000"), Some(0xff8000ff));
+        assert_eq!(parse_color("ff8000"), None);
+        assert_eq!(parse_color("#f80"), None);
+        assert_eq!(parse_color("#+f8000"), None);
+        assert_eq!(format_color(0xff800080), "#ff800080");
+        let default = SchemaType::Color {
+            default: "#00000001".to_string(),
+        }
+        .default_value();
+        assert_eq!(default, Some(Value::I32(1)));
+    }
+
+    #[test]
+    fn seed_parameter() {
+        let json = r#"{"type":"seed","reroll_every":1.0}"#;
+        let seed: SchemaType = serde_json::from_str(json).unwrap();
+        assert_eq!(seed.default_value(), Some(Value::I64(0)));
+        assert_eq!(seed.parse_value("-7"), Some(Value::I64(-7)));
+        assert_eq!(seed.format_value(&Value::I64(123)).as_deref(), Some("123"));
+        assert!(seed.check_value(&Value::I32(1)).is_err());
+        assert_eq!(
+            serde_json::to_string(&seed).unwrap(),
+            r#"{"type":"seed","default":0,"reroll_every":1.0}"#
+        );
+
+        let err = validate(vec![SchemaType::Seed {
+            default: 1,
+            reroll_every: Some(0.0),
+        }])
+        .unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid schema: schema[0].reroll_every: must be positive, got 0"
+        );
+    }
+
+    #[test]
+    fn pause_and_seek() {
+        // Renders the time in tenths of a second, modulo 10, as a digit
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"time\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param f64)
+                    (i32.store8 (i32.const 16)
+                        (i32.add (i32.const 48)
+                            (i32.rem_u
+                                (i32.trunc_f64_u (f64.nearest (f64.mul (local.get 0) (f64.const 10))))
+                                (i32.const 10))))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        wasm.set_paused(true);
+        assert!(wasm.is_paused());
+        wasm.set_time(12.5);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert_eq!(wasm.time(), 12.5);
+        assert_eq!(wasm.render(), "5");
+        assert_eq!(wasm.render(), "5");
+
+        wasm.set_paused(false);
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        assert!(wasm.time() > 12.5);
+        wasm.set_time(-1.0);
+        assert!(wasm.time() < 1.0);
+    }
+
+    #[test]
+    fn test_render_type_invalid_json() {
+        let json = r#"{"type":"invalid_type"}"#;
+        let result: Result<SchemaType, _> = serde_json::from_str(json);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_schema_params_match() {
+        let wasm_bytes = parse_str(WAT_WITH_PARAMS).unwrap();
+        let wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.schema().len(), 2);
+        assert!(matches!(&wasm.schema()[0].ty, SchemaType::RangeF32 { .. }));
+        assert!(matches!(&wasm.schema()[1].ty, SchemaType::RangeI32 { .. }));
+    }
+
+    #[test]
+    fn validate_range_f32_min_greater_than_max() {
+        let schema = vec![SchemaType::RangeF32 {
+            min: 10.0,
+            max: 0.0,
+            default: 5.0,
+            options: RangeOptions::default(),
+        }];
+        let err = validate(schema).unwrap_err();
+        assert!(
+            err.contains("schema[0].min: 10 is greater than max 0"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_range_i32_min_greater_than_max() {
+        let schema = vec![
+            SchemaType::Time,
+            SchemaType::RangeI32 {
+                min: 10,
+                max: 0,
+                default: 50,
+                options: RangeOptions::default(),
+            },
+        ];
+        let err = validate(schema).unwrap_err();
+        assert!(
+            err.contains("schema[1].min: 10 is greater than max 0"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_default_outside_range() {
+        let schema = vec![
+            SchemaType::RangeF32 {
+                min: 0.0,
+                max: 1.0,
+                default: 1.5,
+                options: RangeOptions::default(),
+            },
+            SchemaType::RangeI32 {
+                min: 0,
+                max: 100,
+                default: 150,
+                options: RangeOptions::default(),
+            },
+        ];
+        let err = validate(schema).unwrap_err();
+        assert!(
+            err.contains("schema[0].default: 1.5 is outside the range [0, 1]"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("schema[1].default: 150 is outside the range [0, 100]"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_non_finite_floats() {
+        let schema = vec![SchemaType::RangeF32 {
+            min: f32::NAN,
+            max: 1.0,
+            default: f32::INFINITY,
+            options: RangeOptions::default(),
+        }];
+        let err = validate(schema).unwrap_err();
+        assert!(
+            err.contains("schema[0].min: must be finite, got NaN"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("schema[0].default: must be finite, got inf"),
+            "{}",
+            err
+        );
+        assert!(!err.contains("schema[0].max"), "{}", err);
+    }
+
+    #[test]
+    fn validate_entry_count() {
+        let schema = vec![SchemaType::Time; MAX_SCHEMA_ENTRIES + 1];
+        let err = validate(schema).unwrap_err();
+        assert!(err.contains("at most 64"), "{}", err);
+        assert!(validate(vec![SchemaType::Time; MAX_SCHEMA_ENTRIES]).is_ok());
+    }
+
+    #[test]
+    fn validate_enum_options() {
+        let schema = vec![
+            SchemaType::Enum {
+                options: vec![],
+                default: 0,
+            },
+            SchemaType::Enum {
+                options: vec!["a".to_string(), "b".to_string()],
+                default: 2,
+            },
+            SchemaType::Enum {
+                options: vec!["a".to_string(), "a".to_string()],
+                default: 0,
+            },
+        ];
+        let err = validate(schema).unwrap_err();
+        assert!(
+            err.contains("schema[0].options: must not be empty"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("schema[1].default: index 2 is out of bounds for 2 options"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains(r#"schema[2].options: duplicate option "a""#),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_color_default() {
+        let schema = vec![SchemaType::Color {
+            default: "red".to_string(),
+        }];
+        let err = validate(schema).unwrap_err();
+        assert!(
+            err.contains(r#"schema[0].default: "red" is not a #rrggbb or #rrggbbaa color"#),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn validate_point2d() {
+        let schema = vec![
+            SchemaType::Point2d {
+                default: [f32::NAN, 0.0],
+                bounds: None,
+            },
+            SchemaType::Point2d {
+                default: [0.0, 0.0],
+                bounds: Some([10.0, 0.0, 0.0, 10.0]),
+            },
+            SchemaType::Point2d {
+                default: [20.0, 5.0],
+                bounds: Some([0.0, 0.0, 10.0, 10.0]),
+            },
+        ];
+        let err = validate(schema).unwrap_err();
+        assert!(err.contains("schema[0].default: must be finite"), "{}", err);
+        assert!(
+            err.contains("schema[1].bounds: [10.0, 0.0, 0.0, 10.0] has min greater than max"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("schema[2].default: [20.0, 5.0] is outside the bounds"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn parameters_by_name() {
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"range_f32\",\"min\":0,\"max\":1,\"default\":0.5},{\"type\":\"range_i32\",\"name\":\"n\",\"min\":0,\"max\":100,\"default\":50}]")
+                (import "env" "render" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param f32 i32))
+            )"#;
+        let mut wasm = Wasm::new(&parse_str(wat).unwrap()).unwrap();
+        let schema = wasm.schema().clone();
+        assert_eq!(parameter_key(&schema, 0).as_deref(), Some("0"));
+        assert_eq!(parameter_key(&schema, 1).as_deref(), Some("n"));
+        assert_eq!(parameter_key(&schema, 2), None);
+        assert_eq!(parameter_by_key(&schema, "n"), Some(1));
+        assert_eq!(parameter_by_key(&schema, "2"), None);
+
+        wasm.set_named_parameter("n", Value::I32(7)).unwrap();
+        wasm.set_named_parameter("0", Value::F32(0.25)).unwrap();
+        assert_eq!(wasm.parameter(1), Some(&Value::I32(7)));
+        assert_eq!(wasm.parameter(0), Some(&Value::F32(0.25)));
+        assert!(wasm.set_named_parameter("m", Value::I32(7)).is_err());
+        assert!(wasm.set_named_parameter("n", Value::I32(700)).is_err());
+
+        // Values captured by name land on the same entry after it moved
+        let mut moved = schema.clone();
+        moved.insert(0, moved[1].clone());
+        moved.remove(2);
+        let preset = Preset::capture(String::new(), &schema, &HashMap::from([(1, Value::I32(7))]));
+        assert_eq!(preset.resolve(&moved), Ok(vec![(0, Value::I32(7))]));
+        wasm.reset_parameters();
+        assert_eq!(wasm.parameter(1), None);
+    }
+
+    #[test]
+    fn parameter_presentation_fields() {
+        let json = r#"[
+            {"type": "bool", "default": false, "name": "show_hull", "label": "Show hull"},
+            {
+                "type": "enum", "options": ["graham", "melkman"], "default": 0,
+                "name": "algorithm", "description": "Convex hull algorithm", "group": "Hull",
+                "visible_if": {"name": "show_hull", "equals": true}
+            },
+            {"type": "range_i32", "min": 0, "max": 5, "default": 1,
+             "visible_if": {"name": "algorithm", "equals": "melkman"}}
+        ]"#;
+        let schema: Schema = serde_json::from_str(json).unwrap();
+        assert!(validate_schema(&schema).is_ok());
+        assert_eq!(schema[0].display_label(0), "Show hull");
+        assert_eq!(schema[1].display_label(1), "algorithm");
+        assert_eq!(schema[2].display_label(2), "Parameter 2");
+        assert_eq!(schema[1].group.as_deref(), Some("Hull"));
+        assert!(matches!(schema[1].ty, SchemaType::Enum { .. }));
+        assert_eq!(parameter_index(&schema, "algorithm"), Some(1));
+
+        // The range is hidden until the hull is shown and melkman is picked
+        let mut values = HashMap::new();
+        assert!(!is_visible(&schema, 1, &values));
+        assert!(!is_visible(&schema, 2, &values));
+        values.insert(0, Value::I32(1));
+        assert!(is_visible(&schema, 1, &values));
+        assert!(!is_visible(&schema, 2, &values));
+        values.insert(1, Value::I32(1));
+        assert!(is_visible(&schema, 2, &values));
+        values.insert(0, Value::I32(0));
+        assert!(!is_visible(&schema, 2, &values));
+
+        // Serializing keeps the fields next to the type tag
+        let value = serde_json::to_value(&schema[0]).unwrap();
+        assert_eq!(value["type"], "bool");
+        assert_eq!(value["name"], "show_hull");
+        assert!(value.get("group").is_none());
+    }
+
+    #[test]
+    fn validate_names_and_conditions() {
+        let json = r#"[
+            {"type": "bool", "default": false, "name": "a b"},
+            {"type": "bool", "default": false, "name": "flag"},
+            {"type": "bool", "default": false, "name": "flag"},
+            {"type": "time", "visible_if": {"name": "missing", "equals": true}},
+            {"type": "bool", "default": false, "name": "me", "visible_if": {"name": "me", "equals": true}},
+            {"type": "time", "visible_if": {"name": "flag", "equals": "maybe"}}
+        ]"#;
+        let schema: Schema = serde_json::from_str(json).unwrap();
+        let err = validate_schema(&schema).unwrap_err();
+        for expected in [
+            "schema[0].name: \"a b\" may only contain ASCII letters, digits and '_'",
+            "schema[2].name: \"flag\" is already used",
+            "schema[3].visible_if: no parameter is named \"missing\"",
+            "schema[4].visible_if: must not refer to itself",
+            "schema[5].visible_if: \"maybe\" is not a valid value for \"flag\"",
+        ] {
+            assert!(err.contains(expected), "{} missing from {}", expected, err);
+        }
+        assert!(!err.contains("schema[1]"), "{}", err);
+    }
+
+    #[test]
+    fn parse_and_format_values() {
+        let cases = [
+            (
+                SchemaType::RangeF32 {
+                    min: 0.0,
+                    max: 1.0,
+                    default: 0.5,
+                    options: RangeOptions::default(),
+                },
+                "0.25",
+                Value::F32(0.25),
+            ),
+            (SchemaType::Bool { default: false }, "true", Value::I32(1)),
+            (
+                SchemaType::Enum {
+                    options: vec!["a".to_string(), "b".to_string()],
+                    default: 0,
+                },
+                "b",
+                Value::I32(1),
+            ),
+            (
+                SchemaType::Color {
+                    default: "#000000".to_string(),
+                },
+                "#ff800080",
+                Value::I32(0xff800080u32 as i32),
+            ),
+            (
+                SchemaType::Point2d {
+                    default: [0.0, 0.0],
+                    bounds: None,
+                },
+                "1.5,-2",
+                Value::Point2d([1.5, -2.0]),
+            ),
+            (
+                SchemaType::PointSet {
+                    default: vec![],
+                    bounds: None,
+                },
+                "0,0;1,2",
+                Value::PointSet(vec![[0.0, 0.0], [1.0, 2.0]]),
+            ),
+        ];
+        for (ty, text, value) in cases {
+            assert_eq!(ty.parse_value(text), Some(value.clone()), "{}", text);
+            assert_eq!(ty.format_value(&value).as_deref(), Some(text));
+        }
+
+        let range = SchemaType::RangeI32 {
+            min: 0,
+            max: 10,
+            default: 5,
+            options: RangeOptions::default(),
+        };
+        assert_eq!(range.parse_value("11"), None);
+        assert_eq!(range.parse_value("x"), None);
+        assert_eq!(SchemaType::Time.parse_value("1"), None);
+        let enum_ty = SchemaType::Enum {
+            options: vec!["a".to_string()],
+            default: 0,
+        };
+        assert_eq!(enum_ty.parse_value("0"), Some(Value::I32(0)));
+    }
+
+    #[test]
+    fn validate_file_extensions() {
+        let schema = vec![SchemaType::File {
+            extensions: vec!["csv".to_string(), ".wkt".to_string()],
+        }];
+        let err = validate(schema).unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid schema: schema[0].extensions: \".wkt\" must be alphanumeric, without a leading dot"
+        );
+    }
+
+    #[test]
+    fn validate_point_set() {
+        let schema = vec![SchemaType::PointSet {
+            default: vec![[1.0, 1.0], [f32::INFINITY, 0.0], [20.0, 5.0]],
+            bounds: Some([0.0, 0.0, 10.0, 10.0]),
+        }];
+        let err = validate(schema).unwrap_err();
+        assert!(
+            err.contains("schema[0].default: point 1 must be finite"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("schema[0].default: point 2 [20.0, 5.0] is outside the bounds"),
+            "{}",
+            err
+        );
+        assert!(!err.contains("point 0"), "{}", err);
+    }
+
+    #[test]
+    fn invalid_schema_rejected_at_load() {
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"range_i32\",\"min\":10,\"max\":0,\"default\":50}]")
+                (type (;0;) (func (param i32)))
+                (import "env" "render" (func (;0;) (type 0)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param i32))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let err = Wasm::new(&wasm_bytes).unwrap_err();
+        assert!(err.starts_with("Invalid schema: schema[0].min"), "{}", err);
+    }
+
+    #[test]
+    fn invalid_schema_rejected_before_instantiation() {
+        // The import can't be satisfied, so instantiating would fail with
+        // another error
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"range_f32\",\"min\":0,\"max\":1,\"default\":2}]")
+                (import "env" "missing" (func))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let err = ModuleInfo::parse(&wasm_bytes).unwrap_err();
+        assert!(
+            err.starts_with("Invalid schema: schema[0].default"),
+            "{}",
+            err
+        );
+        assert_eq!(Wasm::new(&wasm_bytes).unwrap_err(), err);
+    }
+
+    #[test]
+    fn viewport_parameter() {
+        // Renders "wide" if the viewport width exceeds its height
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"viewport\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "wide\00tall\00")
+                (func (export "request_animation_frame") (param f32 f32 f32)
+                    (call $render
+                        (select (i32.const 16) (i32.const 21)
+                            (f32.gt (local.get 0) (local.get 1)))))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        wasm.set_viewport(Viewport {
+            width: 200.0,
+            height: 100.0,
+            device_pixel_ratio: 2.0,
+        });
+        assert_eq!(wasm.render(), "wide");
+        wasm.set_viewport(Viewport {
+            width: 100.0,
+            height: 200.0,
+            device_pixel_ratio: 2.0,
+        });
+        assert_eq!(wasm.render(), "tall");
+    }
+
+    #[test]
+    fn camera_parameter() {
+        // Renders "zoomed" once the camera is narrower than 100 units
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"camera\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "whole\00zoomed\00")
+                (func (export "request_animation_frame") (param f32 f32 f32 f32)
+                    (call $render
+                        (select (i32.const 22) (i32.const 16)
+                            (i32.and
+                                (f32.gt (local.get 2) (f32.const 0))
+                                (f32.lt (local.get 2) (f32.const 100))))))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "whole");
+        wasm.set_camera(Some("10 10 50 25".parse().unwrap()));
+        assert_eq!(wasm.render(), "zoomed");
+        wasm.set_camera(None);
+        assert_eq!(wasm.render(), "whole");
+    }
+
+    #[test]
+    fn init_called_on_resize() {
+        // init counts its calls in a global which the frame renders as a digit
+        let wat = r#"
+            (module
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (global $calls (mut i32) (i32.const 0))
+                (func (export "init") (param f32 f32)
+                    (global.set $calls (i32.add (global.get $calls) (i32.const 1))))
+                (func (export "request_animation_frame")
+                    (i32.store8 (i32.const 16) (i32.add (i32.const 48) (global.get $calls)))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "1");
+        assert_eq!(wasm.render(), "1");
+        wasm.set_viewport(Viewport {
+            device_pixel_ratio: 2.0,
+            ..wasm.viewport()
+        });
+        assert_eq!(wasm.render(), "1");
+        wasm.set_viewport(Viewport {
+            width: 320.0,
+            ..wasm.viewport()
+        });
+        assert_eq!(wasm.render(), "2");
+    }
+
+    #[test]
+    fn init_signature_checked() {
+        let wat = r#"
+            (module
+                (import "env" "render" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "init") (param i32 i32))
+                (func (export "request_animation_frame"))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let err = Wasm::new(&wasm_bytes).unwrap_err();
+        assert!(err.contains("init must take (f32, f32)"), "{}", err);
+    }
+
+    #[test]
+    fn fuel_limit() {
+        // Loops as often as the parameter says
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"range_i32\",\"min\":0,\"max\":1000000,\"default\":10}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "<svg/>")
+                (func (export "request_animation_frame") (param i32)
+                    (block
+                        (loop
+                            (br_if 1 (i32.eqz (local.get 0)))
+                            (local.set 0 (i32.sub (local.get 0) (i32.const 1)))
+                            (br 0)))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::with_fuel_metering(&wasm_bytes).unwrap();
+        wasm.set_fuel_limit(Some(10_000));
+        assert_eq!(wasm.render(), "<svg/>");
+        let fuel = wasm.metrics().fuel.unwrap();
+        assert!(fuel > 10 && fuel < 10_000, "{}", fuel);
+        assert!(!wasm.is_out_of_fuel());
+
+        wasm.set_parameter(0, Value::I32(1_000_000)).unwrap();
+        wasm.render();
+        assert!(wasm.has_failed() && wasm.is_out_of_fuel());
+
+        // Without a limit, fuel is only counted
+        let mut wasm = Wasm::with_fuel_metering(&wasm_bytes).unwrap();
+        wasm.set_parameter(0, Value::I32(1_000_000)).unwrap();
+        assert_eq!(wasm.render(), "<svg/>");
+        assert!(wasm.metrics().fuel.unwrap() > 1_000_000);
+    }
+
+    #[test]
+    fn frame_counter() {
+        // Renders the last digit of the frame number
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"frame\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param i64)
+                    (i64.store8 (i32.const 16)
+                        (i64.add (i64.const 48) (i64.rem_u (local.get 0) (i64.const 10))))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "0");
+        assert_eq!(wasm.render(), "1");
+        assert_eq!(wasm.render(), "2");
+    }
+
+    #[test]
+    fn delta_time() {
+        // Renders the delta time in tenths of a second, at most 9, and the
+        // last digit of the frame number
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"delta_time\"},{\"type\":\"frame\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param f64 i64)
+                    (i32.store8 (i32.const 16)
+                        (i32.add (i32.const 48)
+                            (i32.trunc_f64_u (f64.min (f64.const 9) (f64.mul (local.get 0) (f64.const 10))))))
+                    (i64.store8 (i32.const 17)
+                        (i64.add (i64.const 48) (i64.rem_u (local.get 1) (i64.const 10))))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "00");
+
+        std::thread::sleep(std::time::Duration::from_millis(250));
+        let second = wasm.render();
+        assert!(second.as_bytes()[0] >= b'2', "{}", second);
+        assert_eq!(&second[1..], "1");
+
+        // No time passes while paused
+        wasm.set_paused(true);
+        assert_eq!(wasm.render(), "02");
+    }
+
+    #[test]
+    fn render_at_fixed_times() {
+        // Renders the time and delta time, rounded to tenths, as two digits
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"time\"},{\"type\":\"delta_time\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (func $digit (param i32 f64)
+                    (i32.store8 (local.get 0)
+                        (i32.add (i32.const 48)
+                            (i32.trunc_f64_u (f64.nearest (f64.mul (local.get 1) (f64.const 10)))))))
+                (func (export "request_animation_frame") (param f64 f64)
+                    (call $digit (i32.const 16) (local.get 0))
+                    (call $digit (i32.const 17) (local.get 1))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render_at(0.0, 0.0), "00");
+        assert_eq!(wasm.render_at(0.5, 0.5), "55");
+        assert_eq!(wasm.render_at(0.7, 0.2), "72");
+        // The playback clock didn't move
+        assert!(wasm.time() < 0.5);
+        assert!(wasm.last_frame_at.is_none());
+    }
+
+    #[test]
+    fn frame_metrics() {
+        // Loops `time` times and grows the memory by a page every frame
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"time\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "ok")
+                (func (export "request_animation_frame") (param f64)
+                    (local $n i32)
+                    (local.set $n (i32.trunc_f64_u (local.get 0)))
+                    (block $done
+                        (loop $next
+                            (br_if $done (i32.eqz (local.get $n)))
+                            (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+                            (br $next)))
+                    (drop (memory.grow (i32.const 1)))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::with_fuel_metering(&wasm_bytes).unwrap();
+        assert_eq!(wasm.metrics(), FrameMetrics::default());
+        wasm.render_at(1.0, 0.0);
+        let short = wasm.metrics();
+        assert_eq!(short.svg_bytes, 2);
+        assert_eq!(short.memory_bytes, 2 * 65536);
+        assert_eq!(short.memory_growth, 65536);
+        wasm.render_at(100.0, 0.0);
+        let long = wasm.metrics();
+        assert_eq!(long.memory_bytes, 3 * 65536);
+        assert!(long.fuel.unwrap() > short.fuel.unwrap() + 99);
+
+        let mut unmetered = Wasm::new(&wasm_bytes).unwrap();
+        unmetered.render_at(1.0, 0.0);
+        assert_eq!(unmetered.metrics().fuel, None);
+    }
+
+    #[test]
+    fn animated_frames() {
+        // Calls static_frame when its parameter is set
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"time\"},{\"type\":\"bool\",\"default\":false}]")
+                (import "env" "render" (func $render (param i32)))
+                (import "env" "static_frame" (func $static_frame))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param f64 i32)
+                    (if (local.get 1) (then (call $static_frame)))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        wasm.render();
+        assert!(wasm.is_animated());
+        wasm.set_paused(true);
+        assert!(!wasm.is_animated());
+        wasm.set_paused(false);
+
+        wasm.set_parameter(1, Value::I32(1)).unwrap();
+        wasm.render();
+        assert!(!wasm.is_animated());
+        wasm.set_parameter(1, Value::I32(0)).unwrap();
+        wasm.render();
+        assert!(wasm.is_animated());
+
+        // Modules without a time parameter only change with their parameters
+        let still = Wasm::new(&parse_str(WAT_WITH_PARAMS).unwrap()).unwrap();
+        assert!(!still.is_animated());
+    }
+
+    #[test]
+    fn bool_and_enum_parameters() {
+        // Renders "<bool><enum>" as two digits
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"bool\",\"default\":true},{\"type\":\"enum\",\"options\":[\"a\",\"b\",\"c\"],\"default\":1}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param i32 i32)
+                    (i32.store8 (i32.const 16) (i32.add (i32.const 48) (local.get 0)))
+                    (i32.store8 (i32.const 17) (i32.add (i32.const 48) (local.get 1)))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "11");
+        wasm.set_parameter(0, Value::I32(0)).unwrap();
+        wasm.set_parameter(1, Value::I32(2)).unwrap();
+        assert_eq!(wasm.render(), "02");
+
+        assert!(wasm.set_parameter(0, Value::I32(2)).is_err());
+        assert!(wasm.set_parameter(1, Value::I32(3)).is_err());
+        assert!(wasm.set_parameter(1, Value::F32(1.0)).is_err());
+        assert!(wasm.set_parameter(2, Value::I32(0)).is_err());
+    }
+
+    #[test]
+    fn range_64_bit_parameters() {
+        // Renders "y" if the i64 exceeds 2^53 and the f64 is exactly 0.1
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"range_i64\",\"min\":0,\"max\":9007199254740993,\"default\":9007199254740993},{\"type\":\"range_f64\",\"min\":0,\"max\":1,\"default\":0.1}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "y\00n\00")
+                (func (export "request_animation_frame") (param i64 f64)
+                    (call $render
+                        (select (i32.const 16) (i32.const 18)
+                            (i32.and
+                                (i64.gt_s (local.get 0) (i64.const 9007199254740992))
+                                (f64.eq (local.get 1) (f64.const 0.1))))))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "y");
+        wasm.set_parameter(0, Value::I64(1)).unwrap();
+        assert_eq!(wasm.render(), "n");
+        assert!(wasm.set_parameter(0, Value::I32(1)).is_err());
+        assert!(wasm.set_parameter(1, Value::F64(1.5)).is_err());
+    }
+
+    #[test]
+    fn range_options() {
+        let json = r#"{"type":"range_i64","min":10,"max":1000000,"default":1000,"step":10.0,"scale":"log","unit":"points"}"#;
+        let range: SchemaType = serde_json::from_str(json).unwrap();
+        let SchemaType::RangeI64 { ref options, .. } = range else {
+            panic!("Wrong variant");
+        };
+        assert_eq!(options.step, Some(10.0));
+        assert_eq!(options.scale, Scale::Log);
+        assert_eq!(options.unit.as_deref(), Some("points"));
+        assert_eq!(options.precision, None);
+        assert_eq!(serde_json::to_string(&range).unwrap(), json);
+
+        let schema = vec![
+            SchemaType::RangeF64 {
+                min: 0.0,
+                max: 1.0,
+                default: 0.5,
+                options: RangeOptions {
+                    step: Some(0.0),
+                    scale: Scale::Log,
+                    unit: None,
+                    precision: Some(30),
+                },
+            },
+            SchemaType::RangeF32 {
+                min: 1.0,
+                max: 10.0,
+                default: 2.0,
+                options: RangeOptions {
+                    scale: Scale::Log,
+                    ..RangeOptions::default()
+                },
+            },
+        ];
+        let err = validate(schema).unwrap_err();
+        assert_eq!(
+            err,
+            "Invalid schema: schema[0].step: must be positive, got 0; \
+             schema[0].scale: log scale needs min > 0, got 0; \
+             schema[0].precision: must be at most 20"
+        );
+    }
+
+    #[test]
+    fn scale_positions() {
+        assert_eq!(Scale::Linear.position(0.0, 10.0, 2.5), 0.25);
+        assert_eq!(Scale::Linear.value(0.0, 10.0, 0.25), 2.5);
+        assert!((Scale::Log.position(10.0, 1e6, 1e3) - 0.4).abs() < 1e-9);
+        assert!((Scale::Log.value(10.0, 1e6, 0.4) - 1e3).abs() < 1e-6);
+        assert_eq!(Scale::Log.value(10.0, 1e6, 1.0), 1e6);
+        assert_eq!(Scale::Linear.position(5.0, 5.0, 5.0), 0.0);
+    }
+
+    #[test]
+    fn point2d_parameter() {
+        // Renders "x" if the point lies left of x = 50, otherwise "-"
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"point2d\",\"default\":[10,20],\"bounds\":[0,0,100,100]}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "x\00-\00")
+                (func (export "request_animation_frame") (param f32 f32)
+                    (call $render
+                        (select (i32.const 16) (i32.const 18)
+                            (f32.lt (local.get 0) (f32.const 50)))))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "x");
+        wasm.set_parameter(0, Value::Point2d([75.0, 20.0])).unwrap();
+        assert_eq!(wasm.render(), "-");
+        assert!(wasm
+            .set_parameter(0, Value::Point2d([175.0, 20.0]))
+            .is_err());
+        assert!(wasm.set_parameter(0, Value::F32(1.0)).is_err());
+    }
+
+    #[test]
+    fn point_set_parameter() {
+        // Renders the number of points followed by "y" if the first point has
+        // x = 1.5. alloc always returns 512, and both count their calls.
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"point_set\",\"default\":[[1.5,2]]}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (global $allocated (export "allocated") (mut i32) (i32.const 0))
+                (global $freed (export "freed") (mut i32) (i32.const 0))
+                (func (export "alloc") (param i32) (result i32)
+                    (global.set $allocated (i32.add (global.get $allocated) (i32.const 1)))
+                    (i32.const 512))
+                (func (export "dealloc") (param i32 i32)
+                    (global.set $freed (i32.add (global.get $freed) (i32.const 1))))
+                (func (export "request_animation_frame") (param i32 i32)
+                    (i32.store8 (i32.const 16) (i32.add (i32.const 48) (local.get 1)))
+                    (i32.store8 (i32.const 17)
+                        (select (i32.const 121) (i32.const 110)
+                            (f32.eq (f32.load (local.get 0)) (f32.const 1.5))))
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "1y");
+        wasm.set_parameter(0, Value::PointSet(vec![[0.0, 0.0], [1.5, 1.0], [3.0, 4.0]]))
+            .unwrap();
+        assert_eq!(wasm.render(), "3n");
+        wasm.set_parameter(0, Value::PointSet(vec![])).unwrap();
+        assert_eq!(wasm.render(), "0n");
+
+        // Buffers are only rewritten when the value changes
+        let count = |wasm: &Wasm, name| {
+            let global = wasm.instance.get_global(&wasm.store, name).unwrap();
+            global.get(&wasm.store).i32().unwrap()
+        };
+        wasm.set_parameter(0, Value::PointSet(vec![])).unwrap();
+        assert_eq!(wasm.render(), "0n");
+        assert_eq!((count(&wasm, "allocated"), count(&wasm, "freed")), (3, 2));
+        wasm.reset_parameters();
+        assert_eq!(wasm.render(), "1y");
+        assert_eq!((count(&wasm, "allocated"), count(&wasm, "freed")), (4, 3));
+    }
+
+    #[test]
+    fn failed_allocation() {
+        // alloc succeeds once and then returns null, dealloc counts its calls
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"point_set\"},{\"type\":\"point_set\"}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 512))
+                (global $freed (export "freed") (mut i32) (i32.const 0))
+                (func (export "alloc") (param i32) (result i32)
+                    (global.get $next)
+                    (global.set $next (i32.const 0)))
+                (func (export "dealloc") (param i32 i32)
+                    (global.set $freed (i32.add (global.get $freed) (i32.const 1))))
+                (func (export "request_animation_frame") (param i32 i32 i32 i32)
+                    (call $render (i32.const 16)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        wasm.render();
+        assert!(wasm.has_failed());
+        // The buffer allocated before the failure is freed
+        let freed = wasm.instance.get_global(&wasm.store, "freed").unwrap();
+        assert_eq!(freed.get(&wasm.store).i32(), Some(1));
+        assert!(wasm.buffers.is_empty());
+    }
+
+    #[test]
+    fn render_pointer_out_of_bounds() {
+        // -1 is far past the end of the single page of memory
+        let wat = r#"
+            (module
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame")
+                    (call $render (i32.const -1)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        wasm.render();
+        assert!(wasm.has_failed());
+    }
+
+    #[test]
+    fn text_and_file_parameters() {
+        // Both parameters are rendered back by null-terminating the buffers.
+        // alloc hands out consecutive 256 byte blocks.
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"text\",\"default\":\"hello\"},{\"type\":\"file\",\"extensions\":[\"csv\"]}]")
+                (import "env" "render" (func $render (param i32)))
+                (memory (export "memory") 1)
+                (global $next (mut i32) (i32.const 1024))
+                (func (export "alloc") (param i32) (result i32)
+                    (global.set $next (i32.add (global.get $next) (i32.const 256)))
+                    (global.get $next))
+                (func (export "dealloc") (param i32 i32)
+                    (global.set $next (i32.const 1024)))
+                (func (export "request_animation_frame") (param i32 i32 i32 i32)
+                    (i32.store8 (i32.add (local.get 0) (local.get 1)) (i32.const 124))
+                    (memory.copy
+                        (i32.add (i32.add (local.get 0) (local.get 1)) (i32.const 1))
+                        (local.get 2)
+                        (local.get 3))
+                    (i32.store8
+                        (i32.add (i32.add (local.get 0) (local.get 1))
+                            (i32.add (local.get 3) (i32.const 1)))
+                        (i32.const 0))
+                    (call $render (local.get 0)))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let mut wasm = Wasm::new(&wasm_bytes).unwrap();
+        assert_eq!(wasm.render(), "hello|");
+        wasm.set_parameter(0, Value::Text("POINT (1 2)".to_string()))
+            .unwrap();
+        wasm.set_parameter(1, Value::Bytes(b"x,y\n1,2".to_vec()))
+            .unwrap();
+        assert_eq!(wasm.render(), "POINT (1 2)|x,y\n1,2");
+        assert!(wasm.set_parameter(0, Value::Bytes(vec![1])).is_err());
+        assert!(wasm.set_parameter(1, Value::Text(String::new())).is_err());
+    }
+
+    #[test]
+    fn point_set_requires_allocator() {
+        let wat = r#"
+            (module
+                (@custom "rgeometry.schema" "[{\"type\":\"point_set\"}]")
+                (import "env" "render" (func (param i32)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param i32 i32))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let err = Wasm::new(&wasm_bytes).unwrap_err();
+        assert!(err.contains("must export 'alloc"), "{}", err);
+    }
+
+    #[test]
+    fn test_schema_params_mismatch() {
+        let wat = r#"
+            (module
+                (type (;0;) (func (param i32)))
+                (type (;1;) (func (param i32)))
+                (@custom "rgeometry.schema" "[{\"type\":\"range_f32\",\"min\":0.0,\"max\":1.0,\"default\":0.5}]")
+                (import "env" "render" (func (;0;) (type 0)))
+                (memory (export "memory") 1)
+                (func (export "request_animation_frame") (param i32))
+            )"#;
+        let wasm_bytes = parse_str(wat).unwrap();
+        let result = Wasm::new(&wasm_bytes);
+        assert!(result.is_err());
+        assert!(result
+            .unwrap_err()
+            .contains("parameters don't match schema"));
+    }
+}